@@ -44,3 +44,51 @@ pub fn softmax(input: &Matrix) -> Matrix {
 
     output
 }
+
+// log(softmax(x)) computed as x - max - ln(sum(exp(x - max))), row by row
+// stays finite for very confident predictions, where softmax rounds to 0
+pub fn log_softmax(input: &Matrix) -> Matrix {
+    let mut output: Matrix = Matrix::init_zero(input.height, input.width);
+
+    for r in 0..input.height {
+        let row: Vec<f64> = input.get_row(r);
+        let max: f64 = *row.iter().max_by(|a, b| a.total_cmp(b)).unwrap();
+        let log_sum: f64 = row.iter().map(|v| (v - max).exp()).sum::<f64>().ln();
+
+        for (c, v) in row.iter().enumerate() {
+            output.set(v - max - log_sum, r, c);
+        }
+    }
+
+    output
+}
+
+// element wise 1 / (1 + exp(-x)), written to never compute exp of a large positive value
+pub fn sigmoid(input: &Matrix) -> Matrix {
+    let mut output: Matrix = input.clone();
+    output.data = input
+        .data
+        .iter()
+        .map(|v| {
+            if *v >= 0.0 {
+                1.0 / (1.0 + (-v).exp())
+            } else {
+                v.exp() / (1.0 + v.exp())
+            }
+        })
+        .collect();
+
+    output
+}
+
+// element wise log(sigmoid(x)) = -(max(-x, 0) + ln(1 + exp(-|x|)))
+pub fn log_sigmoid(input: &Matrix) -> Matrix {
+    let mut output: Matrix = input.clone();
+    output.data = input.data.iter().map(|v| log_sigmoid_scalar(*v)).collect();
+
+    output
+}
+
+pub fn log_sigmoid_scalar(input: f64) -> f64 {
+    -((-input).max(0.0) + (-input.abs()).exp().ln_1p())
+}
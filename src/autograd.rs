@@ -0,0 +1,443 @@
+use crate::layers::Layer;
+use crate::matrix::Matrix;
+
+// handle on a value recorded in a tape
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Var {
+    index: usize,
+}
+
+#[derive(Clone)]
+enum Op {
+    Leaf,
+    Dot(Var, Var),
+    Add(Var, Var),
+    Sub(Var, Var),
+    // adds a 1 x width matrix to all the rows of the first operand
+    AddRow(Var, Var),
+    Mult(Var, f64),
+    Square(Var),
+    Relu(Var),
+    Softmax(Var),
+    LogSoftmax(Var),
+    Log(Var),
+    Exp(Var),
+    Sum(Var),
+    // picks one column per row, output is a 1 x height matrix
+    Pick(Var, Vec<usize>),
+}
+
+#[derive(Clone)]
+struct Node {
+    value: Matrix,
+    op: Op,
+}
+
+// records every operation applied to the matrices so the gradients can be
+// computed automatically by walking the tape backward (reverse-mode)
+// nodes are always pushed after their operands, the tape is therefore
+// already in topological order
+#[derive(Clone, Default)]
+pub struct Tape {
+    nodes: Vec<Node>,
+}
+
+pub struct Gradients {
+    values: Vec<Option<Matrix>>,
+}
+
+impl Gradients {
+    // None if the variable does not contribute to the differentiated output
+    pub fn get(&self, var: Var) -> Option<&Matrix> {
+        self.values[var.index].as_ref()
+    }
+}
+
+impl Tape {
+    pub fn new() -> Tape {
+        Tape { nodes: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn value(&self, var: Var) -> &Matrix {
+        &self.nodes[var.index].value
+    }
+
+    fn push(&mut self, value: Matrix, op: Op) -> Var {
+        self.nodes.push(Node { value, op });
+        Var {
+            index: self.nodes.len() - 1,
+        }
+    }
+
+    pub fn leaf(&mut self, value: Matrix) -> Var {
+        self.push(contiguous(&value), Op::Leaf)
+    }
+
+    pub fn dot(&mut self, a: Var, b: Var) -> Var {
+        let value = self.value(a).dot(self.value(b));
+        self.push(value, Op::Dot(a, b))
+    }
+
+    pub fn add(&mut self, a: Var, b: Var) -> Var {
+        let value = self.value(a).add_two_matrices(self.value(b));
+        self.push(value, Op::Add(a, b))
+    }
+
+    pub fn sub(&mut self, a: Var, b: Var) -> Var {
+        let value = self.value(a).add_two_matrices(&self.value(b).mult(-1.0));
+        self.push(value, Op::Sub(a, b))
+    }
+
+    pub fn add_row(&mut self, a: Var, row: Var) -> Var {
+        let value = self.value(a).add_1d_matrix_to_all_rows(self.value(row));
+        self.push(value, Op::AddRow(a, row))
+    }
+
+    pub fn mult(&mut self, a: Var, factor: f64) -> Var {
+        let value = self.value(a).mult(factor);
+        self.push(value, Op::Mult(a, factor))
+    }
+
+    pub fn square(&mut self, a: Var) -> Var {
+        let value = self.value(a).pow(2);
+        self.push(value, Op::Square(a))
+    }
+
+    pub fn relu(&mut self, a: Var) -> Var {
+        let value = map(self.value(a), crate::activation::relu);
+        self.push(value, Op::Relu(a))
+    }
+
+    pub fn softmax(&mut self, a: Var) -> Var {
+        let value = crate::activation::softmax(self.value(a));
+        self.push(value, Op::Softmax(a))
+    }
+
+    pub fn log_softmax(&mut self, a: Var) -> Var {
+        let value = crate::activation::log_softmax(self.value(a));
+        self.push(value, Op::LogSoftmax(a))
+    }
+
+    pub fn log(&mut self, a: Var) -> Var {
+        let value = map(self.value(a), f64::ln);
+        self.push(value, Op::Log(a))
+    }
+
+    pub fn exp(&mut self, a: Var) -> Var {
+        let value = self.value(a).exp();
+        self.push(value, Op::Exp(a))
+    }
+
+    pub fn sum(&mut self, a: Var) -> Var {
+        let value = Matrix::init(1, 1, vec![self.value(a).sum()]);
+        self.push(value, Op::Sum(a))
+    }
+
+    // labels is a 1 x height matrix of column indexes, same layout as the
+    // labels used by the model
+    pub fn pick(&mut self, a: Var, labels: &Matrix) -> Var {
+        let input = self.value(a);
+        assert_eq!(
+            input.height, labels.width,
+            "Input height and labels width should be equal"
+        );
+
+        let columns: Vec<usize> = (0..labels.width)
+            .map(|c| labels.get(0, c) as usize)
+            .collect();
+        let data: Vec<f64> = columns
+            .iter()
+            .enumerate()
+            .map(|(r, c)| input.get(r, *c))
+            .collect();
+        let value = Matrix::init(1, columns.len(), data);
+
+        self.push(value, Op::Pick(a, columns))
+    }
+
+    // computes the gradient of every recorded value relative to output
+    // if output is not a scalar, its gradient is seeded with ones, i.e.
+    // the gradient of the sum of its elements
+    pub fn backward(&self, output: Var) -> Gradients {
+        let mut values: Vec<Option<Matrix>> = vec![None; self.nodes.len()];
+        let seed = self.value(output);
+        values[output.index] = Some(Matrix::init(
+            seed.height,
+            seed.width,
+            vec![1.0; seed.height * seed.width],
+        ));
+
+        for index in (0..=output.index).rev() {
+            let grad: Matrix = match values[index].take() {
+                Some(grad) => grad,
+                None => continue,
+            };
+            let node = &self.nodes[index];
+
+            match &node.op {
+                Op::Leaf => (),
+                Op::Dot(a, b) => {
+                    accumulate(&mut values, *a, grad.dot(&self.value(*b).t()));
+                    accumulate(&mut values, *b, self.value(*a).t().dot(&grad));
+                }
+                Op::Add(a, b) => {
+                    accumulate(&mut values, *a, grad.clone());
+                    accumulate(&mut values, *b, grad.clone());
+                }
+                Op::Sub(a, b) => {
+                    accumulate(&mut values, *a, grad.clone());
+                    accumulate(&mut values, *b, grad.mult(-1.0));
+                }
+                Op::AddRow(a, row) => {
+                    accumulate(&mut values, *a, grad.clone());
+                    accumulate(&mut values, *row, grad.sum_rows());
+                }
+                Op::Mult(a, factor) => {
+                    accumulate(&mut values, *a, grad.mult(*factor));
+                }
+                Op::Square(a) => {
+                    let input = self.value(*a);
+                    accumulate(&mut values, *a, zip(&grad, input, |g, x| 2.0 * g * x));
+                }
+                Op::Relu(a) => {
+                    let input = self.value(*a);
+                    let d_input = zip(&grad, input, |g, x| if x <= 0.0 { 0.0 } else { g });
+                    accumulate(&mut values, *a, d_input);
+                }
+                Op::Softmax(a) => {
+                    // d_input(r, j) = s(r, j) * (grad(r, j) - sum_k grad(r, k) * s(r, k))
+                    let s = &node.value;
+                    let mut d_input = Matrix::init_zero(s.height, s.width);
+                    for r in 0..s.height {
+                        let weighted_sum: f64 =
+                            (0..s.width).map(|k| grad.get(r, k) * s.get(r, k)).sum();
+                        for j in 0..s.width {
+                            d_input.set(s.get(r, j) * (grad.get(r, j) - weighted_sum), r, j);
+                        }
+                    }
+                    accumulate(&mut values, *a, d_input);
+                }
+                Op::LogSoftmax(a) => {
+                    // d_input(r, j) = grad(r, j) - softmax(r, j) * sum_k grad(r, k)
+                    let log_s = &node.value;
+                    let mut d_input = Matrix::init_zero(log_s.height, log_s.width);
+                    for r in 0..log_s.height {
+                        let grad_sum: f64 = grad.get_row(r).iter().sum();
+                        for j in 0..log_s.width {
+                            d_input.set(grad.get(r, j) - log_s.get(r, j).exp() * grad_sum, r, j);
+                        }
+                    }
+                    accumulate(&mut values, *a, d_input);
+                }
+                Op::Log(a) => {
+                    let input = self.value(*a);
+                    accumulate(&mut values, *a, zip(&grad, input, |g, x| g / x));
+                }
+                Op::Exp(a) => {
+                    accumulate(&mut values, *a, zip(&grad, &node.value, |g, e| g * e));
+                }
+                Op::Sum(a) => {
+                    let input = self.value(*a);
+                    let d_input = Matrix::init(
+                        input.height,
+                        input.width,
+                        vec![grad.get(0, 0); input.height * input.width],
+                    );
+                    accumulate(&mut values, *a, d_input);
+                }
+                Op::Pick(a, columns) => {
+                    let input = self.value(*a);
+                    let mut d_input = Matrix::init_zero(input.height, input.width);
+                    for (r, c) in columns.iter().enumerate() {
+                        d_input.set(grad.get(0, r), r, *c);
+                    }
+                    accumulate(&mut values, *a, d_input);
+                }
+            }
+
+            values[index] = Some(grad);
+        }
+
+        Gradients { values }
+    }
+}
+
+// the variables of a dense layer recorded in a tape
+pub struct DenseVars {
+    pub weights_t: Var,
+    pub biases: Var,
+    pub output: Var,
+}
+
+// same computation as Layer::forward : relu(input * weights_t + biases)
+pub fn dense(tape: &mut Tape, input: Var, layer: &Layer) -> DenseVars {
+    let weights_t = tape.leaf(layer.weights_t.clone());
+    let biases = tape.leaf(layer.biases.clone());
+
+    let z = tape.dot(input, weights_t);
+    let mut output = tape.add_row(z, biases);
+    if layer.relu {
+        output = tape.relu(output);
+    }
+
+    DenseVars {
+        weights_t,
+        biases,
+        output,
+    }
+}
+
+fn accumulate(values: &mut [Option<Matrix>], var: Var, grad: Matrix) {
+    match &mut values[var.index] {
+        Some(existing) => existing.add_two_matrices_inplace(&grad),
+        None => values[var.index] = Some(grad),
+    }
+}
+
+// the elementwise operations below work on the underlying vec, the matrices
+// stored in the tape are therefore kept in a non transposed layout
+fn contiguous(input: &Matrix) -> Matrix {
+    if !input.transposed {
+        return input.clone();
+    }
+
+    let mut output = Matrix::init_zero(input.height, input.width);
+    for r in 0..input.height {
+        output.set_row(&input.get_row(r), r);
+    }
+
+    output
+}
+
+fn map(input: &Matrix, f: fn(f64) -> f64) -> Matrix {
+    let data: Vec<f64> = input.data.iter().map(|x| f(*x)).collect();
+    Matrix::init(input.height, input.width, data)
+}
+
+fn zip(a: &Matrix, b: &Matrix, f: impl Fn(f64, f64) -> f64) -> Matrix {
+    let a = contiguous(a);
+    let b = contiguous(b);
+    let data: Vec<f64> = a
+        .data
+        .iter()
+        .zip(b.data.iter())
+        .map(|(x, y)| f(*x, *y))
+        .collect();
+    Matrix::init(a.height, a.width, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tape;
+    use crate::matrix::Matrix;
+
+    #[test]
+    fn square_sum_gradient() {
+        let mut tape = Tape::new();
+        let x = tape.leaf(Matrix::init(2, 2, vec![1.0, -2.0, 3.0, 0.5]));
+        let squared = tape.square(x);
+        let output = tape.sum(squared);
+
+        let grads = tape.backward(output);
+
+        assert_eq!(tape.value(output).get(0, 0), 14.25);
+        assert!(grads
+            .get(x)
+            .unwrap()
+            .is_equal(&Matrix::init(2, 2, vec![2.0, -4.0, 6.0, 1.0]), 10));
+    }
+
+    #[test]
+    fn dot_and_add_row_gradient() {
+        let mut tape = Tape::new();
+        let a = tape.leaf(Matrix::init(2, 3, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]));
+        let b = tape.leaf(Matrix::init(3, 1, vec![0.5, -1.0, 2.0]));
+        let row = tape.leaf(Matrix::init(1, 1, vec![3.0]));
+        let product = tape.dot(a, b);
+        let shifted = tape.add_row(product, row);
+        let output = tape.sum(shifted);
+
+        let grads = tape.backward(output);
+
+        // d_a = ones * b^T, d_b = a^T * ones, d_row = number of rows
+        assert!(grads.get(a).unwrap().is_equal(
+            &Matrix::init(2, 3, vec![0.5, -1.0, 2.0, 0.5, -1.0, 2.0]),
+            10
+        ));
+        assert!(grads
+            .get(b)
+            .unwrap()
+            .is_equal(&Matrix::init(3, 1, vec![5.0, 7.0, 9.0]), 10));
+        assert!(grads
+            .get(row)
+            .unwrap()
+            .is_equal(&Matrix::init(1, 1, vec![2.0]), 10));
+    }
+
+    #[test]
+    fn softmax_cross_entropy_gradient() {
+        let scores = Matrix::init(2, 3, vec![0.2, -1.0, 3.0, 1.5, 0.1, -0.4]);
+        let labels = Matrix::init(1, 2, vec![2.0, 0.0]);
+
+        let mut tape = Tape::new();
+        let x = tape.leaf(scores.clone());
+        let probabilities = tape.softmax(x);
+        let picked = tape.pick(probabilities, &labels);
+        let log = tape.log(picked);
+        let sum = tape.sum(log);
+        let output = tape.mult(sum, -1.0);
+
+        let grads = tape.backward(output);
+
+        // hand derived gradient : softmax - one hot
+        let mut expected = crate::activation::softmax(&scores);
+        expected.set(expected.get(0, 2) - 1.0, 0, 2);
+        expected.set(expected.get(1, 0) - 1.0, 1, 0);
+
+        assert!(grads.get(x).unwrap().is_equal(&expected, 10));
+    }
+
+    #[test]
+    fn log_softmax_matches_softmax_then_log() {
+        let scores = Matrix::init(2, 3, vec![0.2, -1.0, 3.0, 1.5, 0.1, -0.4]);
+        let labels = Matrix::init(1, 2, vec![2.0, 0.0]);
+
+        let mut tape = Tape::new();
+        let x = tape.leaf(scores.clone());
+        let log_probabilities = tape.log_softmax(x);
+        let picked = tape.pick(log_probabilities, &labels);
+        let output = tape.sum(picked);
+        let fused = tape.backward(output);
+
+        let mut tape = Tape::new();
+        let y = tape.leaf(scores);
+        let probabilities = tape.softmax(y);
+        let picked = tape.pick(probabilities, &labels);
+        let log = tape.log(picked);
+        let output = tape.sum(log);
+        let unfused = tape.backward(output);
+
+        assert!(fused.get(x).unwrap().is_equal(unfused.get(y).unwrap(), 10));
+    }
+
+    #[test]
+    fn unused_variable_has_no_gradient() {
+        let mut tape = Tape::new();
+        let x = tape.leaf(Matrix::init(1, 2, vec![1.0, 2.0]));
+        let unused = tape.leaf(Matrix::init(1, 2, vec![1.0, 2.0]));
+        let output = tape.sum(x);
+
+        let grads = tape.backward(output);
+
+        assert!(grads.get(unused).is_none());
+        assert!(grads.get(x).is_some());
+    }
+}
@@ -0,0 +1,406 @@
+use crate::error::BriqueError;
+use crate::loss::TargetMode;
+use crate::matrix::Matrix;
+use crate::model::Model;
+use crate::parse_test_csv::is_line_empty;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+#[derive(Debug)]
+pub enum BatchPredictionError {
+    CouldNotReadSource(String),
+    CouldNotParseLine {
+        line: usize,
+        details: String,
+    },
+    // the rows do not match the model, first_row is the index of the first row of the chunk
+    InvalidChunkShape {
+        chunk: usize,
+        first_row: usize,
+        details: String,
+    },
+    CouldNotWriteOutput(String),
+}
+
+impl fmt::Display for BatchPredictionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchPredictionError::CouldNotReadSource(msg) => {
+                write!(f, "Could not read the data source, details : {}", msg)
+            }
+            BatchPredictionError::CouldNotParseLine { line, details } => {
+                write!(f, "Could not parse line {}, details : {}", line, details)
+            }
+            BatchPredictionError::InvalidChunkShape {
+                chunk,
+                first_row,
+                details,
+            } => write!(
+                f,
+                "The chunk {} (from the row {}) does not match the model, details : {}",
+                chunk, first_row, details
+            ),
+            BatchPredictionError::CouldNotWriteOutput(msg) => {
+                write!(f, "Could not write the predictions, details : {}", msg)
+            }
+        }
+    }
+}
+
+// a source of input rows read chunk by chunk, so the whole dataset never
+// has to be loaded in memory
+pub trait DataSource {
+    // next rows of the source, at most max_rows, None when the source is exhausted
+    fn next_chunk(&mut self, max_rows: usize) -> Result<Option<Matrix>, BatchPredictionError>;
+}
+
+// rows of a matrix already in memory
+pub struct MatrixSource<'a> {
+    data: &'a Matrix,
+    position: usize,
+}
+
+impl<'a> MatrixSource<'a> {
+    pub fn init(data: &'a Matrix) -> MatrixSource<'a> {
+        MatrixSource { data, position: 0 }
+    }
+}
+
+impl DataSource for MatrixSource<'_> {
+    fn next_chunk(&mut self, max_rows: usize) -> Result<Option<Matrix>, BatchPredictionError> {
+        if self.position >= self.data.height {
+            return Ok(None);
+        }
+
+        let end: usize = (self.position + max_rows).min(self.data.height);
+        let mut output: Matrix = Matrix::init_zero(end - self.position, self.data.width);
+        for r in self.position..end {
+            output.set_row(&self.data.get_row(r), r - self.position);
+        }
+        self.position = end;
+
+        Ok(Some(output))
+    }
+}
+
+// comma separated rows of features, read line by line
+// the empty lines are skipped, an empty value is an error
+pub struct CsvSource<R: BufRead> {
+    reader: R,
+    // number of lines read, used in the error messages
+    line: usize,
+    width: Option<usize>,
+}
+
+impl CsvSource<BufReader<File>> {
+    pub fn open(file_path: &str, has_header: bool) -> Result<Self, BatchPredictionError> {
+        let file: File = File::open(file_path)
+            .map_err(|e| BatchPredictionError::CouldNotReadSource(e.to_string()))?;
+
+        CsvSource::from_reader(BufReader::new(file), has_header)
+    }
+}
+
+impl<R: BufRead> CsvSource<R> {
+    pub fn from_reader(reader: R, has_header: bool) -> Result<Self, BatchPredictionError> {
+        let mut output = CsvSource {
+            reader,
+            line: 0,
+            width: None,
+        };
+
+        if has_header {
+            output.read_line()?;
+        }
+
+        Ok(output)
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>, BatchPredictionError> {
+        let mut line: String = String::new();
+        let read: usize = self
+            .reader
+            .read_line(&mut line)
+            .map_err(|e| BatchPredictionError::CouldNotReadSource(e.to_string()))?;
+        self.line += 1;
+
+        if read == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(line))
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<Vec<f64>, BatchPredictionError> {
+        let row: Vec<f64> = line
+            .trim()
+            .split(',')
+            .enumerate()
+            .map(|(column, s)| {
+                if s.trim().is_empty() {
+                    return Err(BatchPredictionError::CouldNotParseLine {
+                        line: self.line,
+                        details: format!("the value of the column {} is empty", column),
+                    });
+                }
+                s.trim()
+                    .parse::<f64>()
+                    .map_err(|e| BatchPredictionError::CouldNotParseLine {
+                        line: self.line,
+                        details: format!("{:?} : {}", s, e),
+                    })
+            })
+            .collect::<Result<Vec<f64>, BatchPredictionError>>()?;
+
+        match self.width {
+            Some(width) if width != row.len() => Err(BatchPredictionError::CouldNotParseLine {
+                line: self.line,
+                details: format!("expected {} values, found {}", width, row.len()),
+            }),
+            _ => {
+                self.width = Some(row.len());
+                Ok(row)
+            }
+        }
+    }
+}
+
+impl<R: BufRead> DataSource for CsvSource<R> {
+    fn next_chunk(&mut self, max_rows: usize) -> Result<Option<Matrix>, BatchPredictionError> {
+        let mut rows: Vec<f64> = vec![];
+        let mut height: usize = 0;
+
+        while height < max_rows {
+            let line: String = match self.read_line()? {
+                Some(line) => line,
+                None => break,
+            };
+            if is_line_empty(line.trim_end().as_bytes()) {
+                continue;
+            }
+
+            rows.append(&mut self.parse_line(&line)?);
+            height += 1;
+        }
+
+        if height == 0 {
+            return Ok(None);
+        }
+
+        let width: usize = rows.len() / height;
+        Ok(Some(Matrix::init(height, width, rows)))
+    }
+}
+
+// runs the model on a data source chunk by chunk, only one chunk of inputs
+// and its intermediate results are in memory at the same time
+pub struct BatchPredictor<'a> {
+    model: &'a Model,
+    chunk_size: usize,
+}
+
+impl<'a> BatchPredictor<'a> {
+    pub fn init(model: &'a Model, chunk_size: usize) -> BatchPredictor<'a> {
+        assert!(chunk_size > 0, "Error : the chunk size should be positive");
+
+        BatchPredictor { model, chunk_size }
+    }
+
+    // writes one line per sample : the predicted class then the probability of every class
+    // (the predicted labels then the probabilities for multi-label models)
+    // returns the number of predictions written
+    pub fn predict_to_csv<S: DataSource, W: Write>(
+        &self,
+        source: &mut S,
+        output: W,
+    ) -> Result<usize, BatchPredictionError> {
+        let mut writer: BufWriter<W> = BufWriter::new(output);
+        let mut header_written: bool = false;
+        let mut count: usize = 0;
+        // the classes are 1 x N for class indexes, N x C for multi-label
+        let thresholds: Option<&Vec<f64>> = match &self.model.target_mode {
+            TargetMode::MultiLabel { thresholds } => Some(thresholds),
+            _ => None,
+        };
+        let single_label: bool = thresholds.is_none();
+        let mut chunk_index: usize = 0;
+
+        while let Some(chunk) = source.next_chunk(self.chunk_size)? {
+            chunk_index += 1;
+            let logits: Matrix = self.model.try_predict(&chunk).map_err(|e| {
+                BatchPredictionError::InvalidChunkShape {
+                    chunk: chunk_index,
+                    first_row: count,
+                    details: match e {
+                        BriqueError::Shape(msg) => msg,
+                        e => e.to_string(),
+                    },
+                }
+            })?;
+            let probabilities: Matrix = self.model.output_activation(&logits);
+            let classes: Matrix = match thresholds {
+                Some(thresholds) => Model::multi_label_output(&probabilities, thresholds),
+                None => Model::evaluation_output(&logits),
+            };
+
+            if !header_written {
+                let mut header: Vec<String> = if single_label {
+                    vec!["class".to_string()]
+                } else {
+                    (0..classes.width).map(|c| format!("label_{}", c)).collect()
+                };
+                header.extend((0..probabilities.width).map(|c| format!("probability_{}", c)));
+                write_line(&mut writer, &header.join(","))?;
+                header_written = true;
+            }
+
+            for r in 0..probabilities.height {
+                let mut line: Vec<String> = if single_label {
+                    vec![classes.get(0, r).to_string()]
+                } else {
+                    classes.get_row(r).iter().map(|v| v.to_string()).collect()
+                };
+                line.extend(probabilities.get_row(r).iter().map(|v| v.to_string()));
+                write_line(&mut writer, &line.join(","))?;
+            }
+            count += probabilities.height;
+        }
+
+        writer
+            .flush()
+            .map_err(|e| BatchPredictionError::CouldNotWriteOutput(e.to_string()))?;
+
+        Ok(count)
+    }
+
+    pub fn predict_to_csv_file<S: DataSource>(
+        &self,
+        source: &mut S,
+        file_path: &str,
+    ) -> Result<usize, BatchPredictionError> {
+        let file: File = File::create(file_path)
+            .map_err(|e| BatchPredictionError::CouldNotWriteOutput(e.to_string()))?;
+
+        self.predict_to_csv(source, file)
+    }
+}
+
+fn write_line<W: Write>(writer: &mut W, line: &str) -> Result<(), BatchPredictionError> {
+    writeln!(writer, "{}", line)
+        .map_err(|e| BatchPredictionError::CouldNotWriteOutput(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BatchPredictionError, BatchPredictor, CsvSource, DataSource, MatrixSource};
+    use crate::{layers::Layer, matrix::Matrix, model::Model, optimizer::Optimizer};
+
+    fn get_test_model() -> Model {
+        Model::init(
+            vec![Layer::init(3, 4, true), Layer::init(4, 2, false)],
+            Optimizer::SGD { learning_step: 0.1 },
+            0.0,
+        )
+    }
+
+    fn get_test_data() -> Matrix {
+        Matrix::init(
+            5,
+            3,
+            vec![
+                0.1, 0.5, -0.3, 1.0, -0.2, 0.4, 0.0, 0.3, 0.3, -1.0, 0.2, 0.7, 0.5, 0.5, 0.5,
+            ],
+        )
+    }
+
+    #[test]
+    fn matrix_source_chunks() {
+        let data = get_test_data();
+        let mut source = MatrixSource::init(&data);
+
+        let heights: Vec<usize> = std::iter::from_fn(|| source.next_chunk(2).unwrap())
+            .map(|chunk| chunk.height)
+            .collect();
+
+        assert_eq!(heights, vec![2, 2, 1]);
+    }
+
+    #[test]
+    fn csv_source_chunks() {
+        let csv: &str = "a,b,c\n0.1,0.5,-0.3\n\n1.0,-0.2,0.4\n0.0,0.3,0.3\n";
+        let mut source = CsvSource::from_reader(csv.as_bytes(), true).unwrap();
+
+        let first = source.next_chunk(2).unwrap().unwrap();
+        let second = source.next_chunk(2).unwrap().unwrap();
+
+        assert!(first.is_equal(
+            &Matrix::init(2, 3, vec![0.1, 0.5, -0.3, 1.0, -0.2, 0.4]),
+            15
+        ));
+        assert!(second.is_equal(&Matrix::init(1, 3, vec![0.0, 0.3, 0.3]), 15));
+        assert!(source.next_chunk(2).unwrap().is_none());
+    }
+
+    #[test]
+    fn csv_source_errors() {
+        let mut source = CsvSource::from_reader("0.1,0.5\n0.1,x\n".as_bytes(), false).unwrap();
+        match source.next_chunk(10) {
+            Err(BatchPredictionError::CouldNotParseLine { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("the parsing error was not detected"),
+        }
+
+        let mut source = CsvSource::from_reader("0.1,0.5\n0.1\n".as_bytes(), false).unwrap();
+        assert!(source.next_chunk(10).is_err());
+
+        // an empty value would shift the following columns
+        let mut source = CsvSource::from_reader("1,2,3\n1,,3\n".as_bytes(), false).unwrap();
+        match source.next_chunk(10) {
+            Err(BatchPredictionError::CouldNotParseLine { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("the empty value was not detected"),
+        }
+    }
+
+    #[test]
+    fn invalid_chunk_shape() {
+        let model = get_test_model();
+        let data = Matrix::init(3, 2, vec![0.1, 0.5, 1.0, -0.2, 0.0, 0.3]);
+        let mut output: Vec<u8> = vec![];
+
+        let result = BatchPredictor::init(&model, 2)
+            .predict_to_csv(&mut MatrixSource::init(&data), &mut output);
+
+        match result {
+            Err(BatchPredictionError::InvalidChunkShape {
+                chunk, first_row, ..
+            }) => assert_eq!((chunk, first_row), (1, 0)),
+            _ => panic!("the shape error was not returned"),
+        }
+    }
+
+    #[test]
+    fn chunked_predictions_match_full_evaluation() {
+        let model = get_test_model();
+        let data = get_test_data();
+        let mut output: Vec<u8> = vec![];
+
+        let count: usize = BatchPredictor::init(&model, 2)
+            .predict_to_csv(&mut MatrixSource::init(&data), &mut output)
+            .unwrap();
+
+        let probabilities = model.predict_proba(&data);
+        let classes = model.predict_classes(&data);
+        let lines: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+
+        assert_eq!(count, 5);
+        assert_eq!(lines[0], "class,probability_0,probability_1");
+        for r in 0..5 {
+            let values: Vec<f64> = lines[r + 1]
+                .split(',')
+                .map(|v| v.parse::<f64>().unwrap())
+                .collect();
+            assert_eq!(values[0], classes.get(0, r));
+            assert_eq!(values[1..].to_vec(), probabilities.get_row(r));
+        }
+    }
+}
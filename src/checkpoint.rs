@@ -1,5 +1,9 @@
+use crate::metrics::Metric;
+
 #[derive(Clone, Debug)]
 pub enum Checkpoint {
     ValLoss { save_path: String },
     ValAcc { save_path: String },
+    // saves the model when the metric computed on the validation dataset improves
+    ValMetric { save_path: String, metric: Metric },
 }
@@ -0,0 +1,197 @@
+use crate::error::BriqueError;
+use crate::matrix::Matrix;
+use std::fs::read;
+
+// the binary version of the CIFAR datasets : https://www.cs.toronto.edu/~kriz/cifar.html
+// every record is the label byte(s) then a 32x32 color image, the 1024 red values,
+// the 1024 green values and the 1024 blue values, each channel row by row
+const IMAGE_SIDE: usize = 32;
+const CHANNELS: usize = 3;
+const PIXELS_PER_CHANNEL: usize = IMAGE_SIDE * IMAGE_SIDE;
+const IMAGE_SIZE: usize = PIXELS_PER_CHANNEL * CHANNELS;
+
+// CIFAR-100 records have two labels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CifarLabel {
+    // one of the 20 superclasses
+    Coarse,
+    // one of the 100 classes
+    Fine,
+}
+
+// order of the 3072 values of an image in a row of the output matrix
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelLayout {
+    // the layout of the file, channel by channel (CHW), for convolutional layers
+    ChannelMajor,
+    // the 3 channels of every pixel next to each other (HWC)
+    Interleaved,
+}
+
+// reads CIFAR-10 batch files (data_batch_1.bin ... test_batch.bin) one after the other
+// returns the N x 3072 images, values in [0, 255], and the 1 x N labels
+pub fn extract_cifar10(
+    paths: &[&str],
+    layout: PixelLayout,
+) -> Result<(Matrix, Matrix), BriqueError> {
+    extract_cifar(paths, 1, 0, 10, layout)
+}
+
+// reads CIFAR-100 files (train.bin, test.bin), see extract_cifar10
+pub fn extract_cifar100(
+    paths: &[&str],
+    label: CifarLabel,
+    layout: PixelLayout,
+) -> Result<(Matrix, Matrix), BriqueError> {
+    match label {
+        CifarLabel::Coarse => extract_cifar(paths, 2, 0, 20, layout),
+        CifarLabel::Fine => extract_cifar(paths, 2, 1, 100, layout),
+    }
+}
+
+fn extract_cifar(
+    paths: &[&str],
+    label_bytes: usize,
+    label_index: usize,
+    number_of_classes: usize,
+    layout: PixelLayout,
+) -> Result<(Matrix, Matrix), BriqueError> {
+    let mut images: Vec<f64> = vec![];
+    let mut labels: Vec<f64> = vec![];
+
+    for path in paths {
+        let bytes: Vec<u8> = read(path)?;
+        let (mut file_images, mut file_labels): (Vec<f64>, Vec<f64>) =
+            decode_cifar(&bytes, label_bytes, label_index, number_of_classes, layout)
+                .map_err(|e| BriqueError::Format(format!("{} : {}", path, e)))?;
+        images.append(&mut file_images);
+        labels.append(&mut file_labels);
+    }
+
+    Ok((
+        Matrix::init(labels.len(), IMAGE_SIZE, images),
+        Matrix::init(1, labels.len(), labels),
+    ))
+}
+
+fn decode_cifar(
+    bytes: &[u8],
+    label_bytes: usize,
+    label_index: usize,
+    number_of_classes: usize,
+    layout: PixelLayout,
+) -> Result<(Vec<f64>, Vec<f64>), String> {
+    let record_size: usize = label_bytes + IMAGE_SIZE;
+    if bytes.is_empty() || !bytes.len().is_multiple_of(record_size) {
+        return Err(format!(
+            "the file size ({} bytes) is not a multiple of the record size ({} bytes), is it a {} file ?",
+            bytes.len(),
+            record_size,
+            if label_bytes == 1 { "CIFAR-10" } else { "CIFAR-100" }
+        ));
+    }
+
+    let mut images: Vec<f64> = Vec::with_capacity(bytes.len() / record_size * IMAGE_SIZE);
+    let mut labels: Vec<f64> = vec![];
+    for (index, record) in bytes.chunks(record_size).enumerate() {
+        let label: u8 = record[label_index];
+        if label as usize >= number_of_classes {
+            return Err(format!(
+                "the label of the record {} is {}, expected less than {}",
+                index, label, number_of_classes
+            ));
+        }
+        labels.push(label as f64);
+
+        let image: &[u8] = &record[label_bytes..];
+        match layout {
+            PixelLayout::ChannelMajor => images.extend(image.iter().map(|v| *v as f64)),
+            PixelLayout::Interleaved => {
+                for pixel in 0..PIXELS_PER_CHANNEL {
+                    for channel in 0..CHANNELS {
+                        images.push(image[channel * PIXELS_PER_CHANNEL + pixel] as f64);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((images, labels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_cifar10, extract_cifar100, CifarLabel, PixelLayout};
+    use crate::error::BriqueError;
+
+    // the fixtures are synthetic : in the record i, the value of the channel c
+    // at the position p is (7 * i + 50 * c + p) % 256
+    // labels : cifar10 3, 0, 9 / cifar100 (coarse, fine) (1, 15), (19, 99)
+    const CIFAR10_FIXTURE: &str = "tests/test_data/cifar10_batch.bin";
+    const CIFAR100_FIXTURE: &str = "tests/test_data/cifar100_batch.bin";
+
+    fn expected_value(record: usize, channel: usize, position: usize) -> f64 {
+        ((7 * record + 50 * channel + position) % 256) as f64
+    }
+
+    #[test]
+    fn cifar10_layouts() {
+        let (images, labels) =
+            extract_cifar10(&[CIFAR10_FIXTURE], PixelLayout::ChannelMajor).unwrap();
+        assert_eq!((images.height, images.width), (3, 3072));
+        assert_eq!(labels.data, vec![3.0, 0.0, 9.0]);
+        assert_eq!(images.get(2, 1024 + 5), expected_value(2, 1, 5));
+
+        let (images, _) = extract_cifar10(&[CIFAR10_FIXTURE], PixelLayout::Interleaved).unwrap();
+        // pixel 5 : red, green, blue
+        assert_eq!(images.get(1, 15), expected_value(1, 0, 5));
+        assert_eq!(images.get(1, 16), expected_value(1, 1, 5));
+        assert_eq!(images.get(1, 17), expected_value(1, 2, 5));
+
+        // several files are concatenated
+        let (images, labels) = extract_cifar10(
+            &[CIFAR10_FIXTURE, CIFAR10_FIXTURE],
+            PixelLayout::ChannelMajor,
+        )
+        .unwrap();
+        assert_eq!(images.height, 6);
+        assert_eq!(labels.get(0, 5), 9.0);
+    }
+
+    #[test]
+    fn cifar100_labels() {
+        let (images, coarse) = extract_cifar100(
+            &[CIFAR100_FIXTURE],
+            CifarLabel::Coarse,
+            PixelLayout::ChannelMajor,
+        )
+        .unwrap();
+        let (_, fine) = extract_cifar100(
+            &[CIFAR100_FIXTURE],
+            CifarLabel::Fine,
+            PixelLayout::ChannelMajor,
+        )
+        .unwrap();
+
+        assert_eq!(coarse.data, vec![1.0, 19.0]);
+        assert_eq!(fine.data, vec![15.0, 99.0]);
+        assert_eq!(images.get(1, 3071), expected_value(1, 2, 1023));
+    }
+
+    #[test]
+    fn invalid_files() {
+        // 3 records of 3073 bytes are not a whole number of 3074 bytes records
+        assert!(matches!(
+            extract_cifar100(
+                &[CIFAR10_FIXTURE],
+                CifarLabel::Fine,
+                PixelLayout::ChannelMajor
+            ),
+            Err(BriqueError::Format(_))
+        ));
+        assert!(matches!(
+            extract_cifar10(&["file_that_does_not_exist"], PixelLayout::ChannelMajor),
+            Err(BriqueError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,357 @@
+use crate::error::BriqueError;
+use crate::loss::{data_loss, TargetMode};
+use crate::matrix::Matrix;
+use crate::metrics::Metric;
+use crate::model::Model;
+use crate::model_builder::ModelBuilder;
+use crate::validation::{sample_classes, ValidationSplit};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::fmt;
+
+// results of one model on its validation fold
+#[derive(Clone, Debug, PartialEq)]
+pub struct FoldResult {
+    pub training_samples: usize,
+    pub validation_samples: usize,
+    // data loss, without the L2 penalty
+    pub loss: f64,
+    pub accuracy: f64,
+    // one value per metric of the report
+    pub metrics: Vec<f64>,
+}
+
+pub struct CrossValidationReport {
+    pub metrics: Vec<Metric>,
+    pub folds: Vec<FoldResult>,
+    // the model trained for every fold, in the order of folds
+    pub models: Vec<Model>,
+}
+
+impl CrossValidationReport {
+    // (mean, standard deviation) over the folds
+    pub fn loss(&self) -> (f64, f64) {
+        mean_and_std(&self.folds.iter().map(|f| f.loss).collect::<Vec<f64>>())
+    }
+
+    pub fn accuracy(&self) -> (f64, f64) {
+        mean_and_std(&self.folds.iter().map(|f| f.accuracy).collect::<Vec<f64>>())
+    }
+
+    // index : position of the metric in self.metrics
+    pub fn metric(&self, index: usize) -> (f64, f64) {
+        mean_and_std(
+            &self
+                .folds
+                .iter()
+                .map(|f| f.metrics[index])
+                .collect::<Vec<f64>>(),
+        )
+    }
+
+    // fold with the best validation accuracy, the lowest loss breaks the ties
+    pub fn best_fold(&self) -> usize {
+        (0..self.folds.len())
+            .max_by(|a, b| {
+                let (a, b) = (&self.folds[*a], &self.folds[*b]);
+                a.accuracy
+                    .total_cmp(&b.accuracy)
+                    .then(b.loss.total_cmp(&a.loss))
+            })
+            .unwrap()
+    }
+
+    pub fn best_model(&self) -> &Model {
+        &self.models[self.best_fold()]
+    }
+
+    pub fn into_best_model(mut self) -> Model {
+        let best_fold: usize = self.best_fold();
+        self.models.swap_remove(best_fold)
+    }
+}
+
+impl fmt::Display for CrossValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut header: String = format!("{:<6} {:>12} {:>12}", "Fold", "Loss", "Accuracy");
+        for metric in &self.metrics {
+            header.push_str(&format!(" {:>12}", format!("{:?}", metric)));
+        }
+        writeln!(f, "{}", header)?;
+
+        for (index, fold) in self.folds.iter().enumerate() {
+            let mut line: String =
+                format!("{:<6} {:>12.6} {:>12.6}", index, fold.loss, fold.accuracy);
+            for value in &fold.metrics {
+                line.push_str(&format!(" {:>12.6}", value));
+            }
+            writeln!(f, "{}", line)?;
+        }
+
+        let (loss_mean, loss_std) = self.loss();
+        let (acc_mean, acc_std) = self.accuracy();
+        write!(f, "Loss : {:.6} +/- {:.6}", loss_mean, loss_std)?;
+        write!(f, "\nAccuracy : {:.6} +/- {:.6}", acc_mean, acc_std)?;
+        for (index, metric) in self.metrics.iter().enumerate() {
+            let (mean, std) = self.metric(index);
+            write!(f, "\n{:?} : {:.6} +/- {:.6}", metric, mean, std)?;
+        }
+
+        Ok(())
+    }
+}
+
+// population standard deviation
+fn mean_and_std(values: &[f64]) -> (f64, f64) {
+    let mean: f64 = values.iter().sum::<f64>() / values.len() as f64;
+    let variance: f64 =
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+
+    (mean, variance.sqrt())
+}
+
+pub fn cross_validate(
+    builder: &ModelBuilder,
+    data: &Matrix,
+    labels: &Matrix,
+    k: usize,
+    stratified: bool,
+    batch_size: u32,
+    epochs: u32,
+) -> Result<CrossValidationReport, BriqueError> {
+    cross_validate_with_metrics(
+        builder,
+        data,
+        labels,
+        k,
+        stratified,
+        batch_size,
+        epochs,
+        &[],
+    )
+}
+
+// trains a fresh model built from the builder on k - 1 folds and evaluates it on the last one, k times
+// the layers added with add_layer start with the same weights for every fold, use dense to
+// initialize them again for every model
+// the validation fold is also the validation dataset of the training (checkpoints, logs)
+// the seed of the builder, if any, also seeds the folds so the scores can be reproduced
+// metrics : computed on every validation fold, not available for multi-label targets
+#[allow(clippy::too_many_arguments)]
+pub fn cross_validate_with_metrics(
+    builder: &ModelBuilder,
+    data: &Matrix,
+    labels: &Matrix,
+    k: usize,
+    stratified: bool,
+    batch_size: u32,
+    epochs: u32,
+    metrics: &[Metric],
+) -> Result<CrossValidationReport, BriqueError> {
+    // the model is built once to check the builder and the data before splitting
+    let (target_mode, seed): (TargetMode, Option<u64>) = {
+        let model: Model = builder.clone().try_build()?;
+        model.check_data_shape(data, labels)?;
+        (model.target_mode, model.seed)
+    };
+    if !metrics.is_empty() {
+        if let TargetMode::MultiLabel { .. } = target_mode {
+            return Err(BriqueError::Config(
+                "the metrics are not available for multi-label targets".to_string(),
+            ));
+        }
+    }
+
+    let folds: Vec<Vec<usize>> =
+        fold_indexes(labels, data.height, k, stratified, &target_mode, seed)?;
+
+    let mut fold_results: Vec<FoldResult> = vec![];
+    let mut models: Vec<Model> = vec![];
+    for (fold, validation_indexes) in folds.iter().enumerate() {
+        let training_indexes: Vec<usize> = folds
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != fold)
+            .flat_map(|(_, indexes)| indexes.iter().copied())
+            .collect();
+
+        let validation_data: Matrix = select_rows(data, validation_indexes);
+        let validation_labels: Matrix = target_mode.select(labels, validation_indexes);
+
+        let model: Model = builder.clone().try_build_and_train(
+            &select_rows(data, &training_indexes),
+            &target_mode.select(labels, &training_indexes),
+            batch_size,
+            epochs,
+            ValidationSplit::Explicit {
+                data: validation_data.clone(),
+                labels: validation_labels.clone(),
+            },
+        )?;
+
+        let logits: Matrix = model.predict(&validation_data);
+        let probabilities: Matrix = model.output_activation(&logits);
+        let metric_values: Vec<f64> = if metrics.is_empty() {
+            vec![]
+        } else {
+            let classes: Matrix = sample_classes(&validation_labels, &target_mode)?;
+            metrics
+                .iter()
+                .map(|metric| metric.compute(&probabilities, &classes))
+                .collect()
+        };
+
+        fold_results.push(FoldResult {
+            training_samples: training_indexes.len(),
+            validation_samples: validation_indexes.len(),
+            loss: data_loss(
+                &logits,
+                &validation_labels,
+                None,
+                &model.loss_function,
+                &target_mode,
+            ),
+            accuracy: model.accuracy(&probabilities, &validation_labels),
+            metrics: metric_values,
+        });
+        models.push(model);
+    }
+
+    Ok(CrossValidationReport {
+        metrics: metrics.to_vec(),
+        folds: fold_results,
+        models,
+    })
+}
+
+// shuffled indexes of the samples of every fold, the sizes of the folds differ by at most one
+// stratified : the samples of every class are dealt to the folds in turn, so every fold
+// keeps the class proportions
+// seed : seeds the shuffle, a random seed is used if None
+pub fn fold_indexes(
+    labels: &Matrix,
+    number_of_samples: usize,
+    k: usize,
+    stratified: bool,
+    target_mode: &TargetMode,
+    seed: Option<u64>,
+) -> Result<Vec<Vec<usize>>, BriqueError> {
+    if k < 2 || k > number_of_samples {
+        return Err(BriqueError::Config(format!(
+            "the number of folds should be between 2 and the number of samples ({}), found {}",
+            number_of_samples, k
+        )));
+    }
+
+    let mut indexes: Vec<usize> = (0..number_of_samples).collect();
+    let mut rng: StdRng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_rng(&mut rand::rng()),
+    };
+    indexes.shuffle(&mut rng);
+
+    if stratified {
+        let classes: Matrix = sample_classes(labels, target_mode)?;
+        // stable sort, the samples of a class stay shuffled
+        indexes.sort_by(|a, b| classes.get(0, *a).total_cmp(&classes.get(0, *b)));
+    }
+
+    let mut folds: Vec<Vec<usize>> = vec![vec![]; k];
+    for (i, index) in indexes.iter().enumerate() {
+        folds[i % k].push(*index);
+    }
+
+    Ok(folds)
+}
+
+pub(crate) fn select_rows(data: &Matrix, indexes: &[usize]) -> Matrix {
+    let mut output: Matrix = Matrix::init_zero(indexes.len(), data.width);
+    for (i, index) in indexes.iter().enumerate() {
+        output.set_row(&data.get_row(*index), i);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cross_validate_with_metrics, fold_indexes};
+    use crate::{
+        error::BriqueError,
+        loss::TargetMode,
+        matrix::Matrix,
+        metrics::{Average, Metric},
+        model_builder::ModelBuilder,
+        optimizer::Optimizer,
+    };
+
+    #[test]
+    fn stratified_folds() {
+        // 6 samples of class 0, 3 of class 1
+        let labels = Matrix::init(1, 9, vec![0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0]);
+        let folds = fold_indexes(&labels, 9, 3, true, &TargetMode::ClassIndex, None).unwrap();
+
+        let mut all: Vec<usize> = folds.concat();
+        all.sort();
+        assert_eq!(all, (0..9).collect::<Vec<usize>>());
+        for fold in folds {
+            let ones: usize = fold.iter().filter(|i| labels.get(0, **i) == 1.0).count();
+            assert_eq!((fold.len(), ones), (3, 1));
+        }
+
+        assert!(matches!(
+            fold_indexes(&labels, 9, 10, false, &TargetMode::ClassIndex, None),
+            Err(BriqueError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn cross_validation_report() {
+        let data = Matrix::init(
+            6,
+            2,
+            vec![
+                1.0, 0.0, 0.9, 0.1, 1.1, -0.1, -1.0, 0.0, -0.9, 0.1, -1.1, -0.1,
+            ],
+        );
+        let labels = Matrix::init(1, 6, vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0]);
+        let builder = ModelBuilder::new()
+            .input_size(2)
+            .dense(4, true)
+            .dense(2, false)
+            .optimizer(Optimizer::SGD { learning_step: 0.1 })
+            .seed(4)
+            .verbose(10, true);
+        let run = || {
+            cross_validate_with_metrics(
+                &builder,
+                &data,
+                &labels,
+                3,
+                true,
+                2,
+                20,
+                &[Metric::F1(Average::Macro)],
+            )
+            .unwrap()
+        };
+
+        let report = run();
+        // the seed of the builder gives the same folds and the same models
+        assert_eq!(report.folds, run().folds);
+
+        assert_eq!(report.folds.len(), 3);
+        assert_eq!(report.models.len(), 3);
+        for fold in &report.folds {
+            assert_eq!((fold.training_samples, fold.validation_samples), (4, 2));
+            assert_eq!(fold.metrics.len(), 1);
+        }
+
+        let (mean, std) = report.accuracy();
+        let accuracies: Vec<f64> = report.folds.iter().map(|f| f.accuracy).collect();
+        assert!((mean - accuracies.iter().sum::<f64>() / 3.0).abs() < 1e-12);
+        assert!(std >= 0.0);
+        let best: usize = report.best_fold();
+        assert!(accuracies.iter().all(|a| *a <= accuracies[best]));
+        assert!(report.to_string().contains("Accuracy :"));
+    }
+}
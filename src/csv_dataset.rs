@@ -0,0 +1,542 @@
+use crate::dataset::Dataset;
+use crate::matrix::Matrix;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+#[derive(Debug, PartialEq)]
+pub enum CsvDatasetError {
+    CouldNotReadFile(String),
+    CouldNotParseLine { line: usize, details: String },
+    // a column of the options does not exist in the file
+    UnknownColumn(String),
+}
+
+impl fmt::Display for CsvDatasetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvDatasetError::CouldNotReadFile(msg) => {
+                write!(f, "Could not read the csv file, details : {}", msg)
+            }
+            CsvDatasetError::CouldNotParseLine { line, details } => {
+                write!(f, "Could not parse line {}, details : {}", line, details)
+            }
+            CsvDatasetError::UnknownColumn(column) => {
+                write!(f, "Could not find the column {}", column)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    Index(usize),
+    // name of the column in the header
+    Name(String),
+    Last,
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Column::Index(index) => write!(f, "{}", index),
+            Column::Name(name) => write!(f, "{:?}", name),
+            Column::Last => write!(f, "last"),
+        }
+    }
+}
+
+// what to do with the empty cells and the missing value tokens (NA, NaN, ?)
+// a missing label is an error, or drops the row with DropRows
+#[derive(Clone, Debug, PartialEq)]
+pub enum MissingValues {
+    Error,
+    DropRows,
+    Fill(f64),
+    // mean of the values of the column
+    Mean,
+}
+
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    has_header: bool,
+    delimiter: char,
+    // None to disable the quoting, a quote inside a quoted field is doubled
+    quote: Option<char>,
+    // None when the file only contains features
+    label_column: Option<Column>,
+    missing_values: MissingValues,
+    missing_tokens: Vec<String>,
+    // text columns, every distinct value gets an index, in alphabetical order
+    categorical_columns: Vec<Column>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions::new()
+    }
+}
+
+impl CsvOptions {
+    // by default : a header, comma separated values quoted with ", the label in the last
+    // column, missing values are errors
+    pub fn new() -> CsvOptions {
+        CsvOptions {
+            has_header: true,
+            delimiter: ',',
+            quote: Some('"'),
+            label_column: Some(Column::Last),
+            missing_values: MissingValues::Error,
+            missing_tokens: vec![
+                "".to_string(),
+                "NA".to_string(),
+                "NaN".to_string(),
+                "?".to_string(),
+            ],
+            categorical_columns: vec![],
+        }
+    }
+
+    pub fn has_header(mut self, has_header: bool) -> CsvOptions {
+        self.has_header = has_header;
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: char) -> CsvOptions {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn quote(mut self, quote: Option<char>) -> CsvOptions {
+        self.quote = quote;
+        self
+    }
+
+    pub fn label_column(mut self, label_column: Option<Column>) -> CsvOptions {
+        self.label_column = label_column;
+        self
+    }
+
+    pub fn missing_values(mut self, missing_values: MissingValues) -> CsvOptions {
+        self.missing_values = missing_values;
+        self
+    }
+
+    pub fn missing_tokens(mut self, missing_tokens: Vec<String>) -> CsvOptions {
+        self.missing_tokens = missing_tokens;
+        self
+    }
+
+    pub fn categorical_columns(mut self, categorical_columns: Vec<Column>) -> CsvOptions {
+        self.categorical_columns = categorical_columns;
+        self
+    }
+}
+
+pub struct CsvDataset {
+    // N x F features
+    pub data: Matrix,
+    // 1 x N labels, 1 x 0 without label column
+    pub labels: Matrix,
+    pub feature_names: Vec<String>,
+    pub label_name: Option<String>,
+    // values of every categorical feature, the index of a value is its encoding
+    pub feature_categories: Vec<Option<Vec<String>>>,
+    // class names of a categorical label column
+    pub label_categories: Option<Vec<String>>,
+}
+
+impl CsvDataset {
+    pub fn open(file_path: &str, options: &CsvOptions) -> Result<CsvDataset, CsvDatasetError> {
+        let file: File =
+            File::open(file_path).map_err(|e| CsvDatasetError::CouldNotReadFile(e.to_string()))?;
+
+        CsvDataset::from_reader(BufReader::new(file), options)
+    }
+
+    pub fn from_reader<R: BufRead>(
+        reader: R,
+        options: &CsvOptions,
+    ) -> Result<CsvDataset, CsvDatasetError> {
+        // (line number, fields) of the non empty lines
+        let mut rows: Vec<(usize, Vec<String>)> = vec![];
+        for (index, line) in reader.lines().enumerate() {
+            let line: String =
+                line.map_err(|e| CsvDatasetError::CouldNotReadFile(e.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            rows.push((index + 1, split_line(&line, index + 1, options)?));
+        }
+
+        let names: Vec<String> = if options.has_header && !rows.is_empty() {
+            rows.remove(0)
+                .1
+                .iter()
+                .map(|name| name.trim().to_string())
+                .collect()
+        } else {
+            let width: usize = rows.first().map_or(0, |(_, fields)| fields.len());
+            (0..width).map(|c| format!("column_{}", c)).collect()
+        };
+        for (line, fields) in &rows {
+            if fields.len() != names.len() {
+                return Err(CsvDatasetError::CouldNotParseLine {
+                    line: *line,
+                    details: format!("expected {} values, found {}", names.len(), fields.len()),
+                });
+            }
+        }
+
+        let label_index: Option<usize> = options
+            .label_column
+            .as_ref()
+            .map(|column| column_index(column, &names))
+            .transpose()?;
+        let categorical: Vec<usize> = options
+            .categorical_columns
+            .iter()
+            .map(|column| column_index(column, &names))
+            .collect::<Result<Vec<usize>, CsvDatasetError>>()?;
+
+        let is_missing = |field: &str| -> bool {
+            options
+                .missing_tokens
+                .iter()
+                .any(|token| token == field.trim())
+        };
+
+        // sorted distinct values of the categorical columns
+        let mut categories: Vec<Option<Vec<String>>> = vec![None; names.len()];
+        for c in &categorical {
+            let mut values: Vec<String> = rows
+                .iter()
+                .map(|(_, fields)| fields[*c].trim().to_string())
+                .filter(|field| !is_missing(field))
+                .collect();
+            values.sort();
+            values.dedup();
+            categories[*c] = Some(values);
+        }
+
+        // None for the missing values
+        let mut values: Vec<Vec<Option<f64>>> = vec![];
+        // line of every kept row
+        let mut value_lines: Vec<usize> = vec![];
+        for (line, fields) in &rows {
+            let mut row: Vec<Option<f64>> = vec![];
+            for (c, field) in fields.iter().enumerate() {
+                let field: &str = field.trim();
+                if is_missing(field) {
+                    row.push(None);
+                    continue;
+                }
+
+                let value: f64 =
+                    match &categories[c] {
+                        Some(category) => category.iter().position(|v| v == field).unwrap() as f64,
+                        None => field.parse::<f64>().map_err(|_| {
+                            CsvDatasetError::CouldNotParseLine {
+                                line: *line,
+                                details: format!(
+                                    "the value {:?} of the column {:?} is not a number",
+                                    field, names[c]
+                                ),
+                            }
+                        })?,
+                    };
+                row.push(Some(value));
+            }
+
+            let missing: Option<usize> = row.iter().position(|v| v.is_none());
+            match (missing, &options.missing_values) {
+                (None, _) => {
+                    values.push(row);
+                    value_lines.push(*line);
+                }
+                (Some(_), MissingValues::DropRows) => (),
+                (Some(c), MissingValues::Error) => {
+                    return Err(missing_value_error(*line, &names[c]));
+                }
+                (Some(_), _) => {
+                    if let Some(label_index) = label_index {
+                        if row[label_index].is_none() {
+                            return Err(missing_value_error(*line, &names[label_index]));
+                        }
+                    }
+                    values.push(row);
+                    value_lines.push(*line);
+                }
+            }
+        }
+
+        let fill_values: Vec<f64> = match options.missing_values {
+            MissingValues::Fill(value) => vec![value; names.len()],
+            MissingValues::Mean => {
+                let mut means: Vec<f64> = vec![];
+                for (c, name) in names.iter().enumerate() {
+                    let column: Vec<f64> = values.iter().filter_map(|row| row[c]).collect();
+                    // a column without any value has no mean
+                    if column.is_empty() && !values.is_empty() {
+                        return Err(CsvDatasetError::CouldNotParseLine {
+                            line: value_lines[0],
+                            details: format!(
+                                "every value of the column {:?} is missing, its mean can not replace them",
+                                name
+                            ),
+                        });
+                    }
+                    means.push(column.iter().sum::<f64>() / column.len() as f64);
+                }
+                means
+            }
+            _ => vec![0.0; names.len()],
+        };
+
+        let feature_indexes: Vec<usize> = (0..names.len())
+            .filter(|c| Some(*c) != label_index)
+            .collect();
+        let mut data: Matrix = Matrix::init_zero(values.len(), feature_indexes.len());
+        let mut labels: Matrix = Matrix::init_zero(1, values.len());
+        for (r, row) in values.iter().enumerate() {
+            for (i, c) in feature_indexes.iter().enumerate() {
+                data.set(row[*c].unwrap_or(fill_values[*c]), r, i);
+            }
+            if let Some(label_index) = label_index {
+                labels.set(row[label_index].unwrap(), 0, r);
+            }
+        }
+        if label_index.is_none() {
+            labels = Matrix::init_zero(1, 0);
+        }
+
+        Ok(CsvDataset {
+            data,
+            labels,
+            feature_names: feature_indexes.iter().map(|c| names[*c].clone()).collect(),
+            label_name: label_index.map(|c| names[c].clone()),
+            feature_categories: feature_indexes
+                .iter()
+                .map(|c| categories[*c].clone())
+                .collect(),
+            label_categories: label_index.and_then(|c| categories[c].clone()),
+        })
+    }
+}
+
+impl Dataset for CsvDataset {
+    fn len(&self) -> usize {
+        self.data.height
+    }
+
+    fn width(&self) -> usize {
+        self.data.width
+    }
+
+    fn get(&self, index: usize) -> (Vec<f64>, Vec<f64>) {
+        (self.data.get_row(index), vec![self.labels.get(0, index)])
+    }
+}
+
+fn missing_value_error(line: usize, column: &str) -> CsvDatasetError {
+    CsvDatasetError::CouldNotParseLine {
+        line,
+        details: format!("missing value in the column {:?}", column),
+    }
+}
+
+fn column_index(column: &Column, names: &[String]) -> Result<usize, CsvDatasetError> {
+    let index: Option<usize> = match column {
+        Column::Index(index) => Some(*index).filter(|index| *index < names.len()),
+        Column::Name(name) => names.iter().position(|n| n == name),
+        Column::Last => names.len().checked_sub(1),
+    };
+
+    index.ok_or_else(|| CsvDatasetError::UnknownColumn(column.to_string()))
+}
+
+// splits a line on the delimiter, the delimiters inside the quoted fields are kept
+fn split_line(
+    line: &str,
+    line_number: usize,
+    options: &CsvOptions,
+) -> Result<Vec<String>, CsvDatasetError> {
+    let mut fields: Vec<String> = vec![];
+    let mut field: String = String::new();
+    let mut in_quotes: bool = false;
+    // only spaces may follow the closing quote before the delimiter
+    let mut after_quotes: bool = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if Some(c) == options.quote {
+                if chars.peek().copied() == options.quote {
+                    field.push(c);
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                    after_quotes = true;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if after_quotes && c != options.delimiter {
+            if !c.is_whitespace() {
+                return Err(CsvDatasetError::CouldNotParseLine {
+                    line: line_number,
+                    details: format!("unexpected character {:?} after the closing quote", c),
+                });
+            }
+        } else if Some(c) == options.quote && field.trim().is_empty() {
+            field.clear();
+            in_quotes = true;
+        } else if c == options.delimiter {
+            fields.push(std::mem::take(&mut field));
+            after_quotes = false;
+        } else {
+            field.push(c);
+        }
+    }
+
+    if in_quotes {
+        return Err(CsvDatasetError::CouldNotParseLine {
+            line: line_number,
+            details: "unterminated quoted field".to_string(),
+        });
+    }
+    fields.push(field);
+
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Column, CsvDataset, CsvDatasetError, CsvOptions, MissingValues};
+    use crate::{dataset::Dataset, matrix::Matrix};
+
+    #[test]
+    fn header_label_and_categories() {
+        let csv: &str = "species,length,color\nsetosa,1.5,red\n\nvirginica,2.5,\"dark, blue\"\nsetosa,0.5,red\n";
+        let options = CsvOptions::new()
+            .label_column(Some(Column::Name("species".to_string())))
+            .categorical_columns(vec![Column::Name("species".to_string()), Column::Index(2)]);
+        let dataset = CsvDataset::from_reader(csv.as_bytes(), &options).unwrap();
+
+        assert_eq!(dataset.feature_names, vec!["length", "color"]);
+        assert_eq!(dataset.label_name, Some("species".to_string()));
+        assert_eq!(
+            dataset.label_categories,
+            Some(vec!["setosa".to_string(), "virginica".to_string()])
+        );
+        assert_eq!(
+            dataset.feature_categories[1],
+            Some(vec!["dark, blue".to_string(), "red".to_string()])
+        );
+        assert!(dataset
+            .data
+            .is_equal(&Matrix::init(3, 2, vec![1.5, 1.0, 2.5, 0.0, 0.5, 1.0]), 15));
+        assert!(dataset
+            .labels
+            .is_equal(&Matrix::init(1, 3, vec![0.0, 1.0, 0.0]), 15));
+        assert_eq!(dataset.get(1), (vec![2.5, 0.0], vec![1.0]));
+    }
+
+    #[test]
+    fn delimiter_and_quotes() {
+        let csv: &str = "'1;5';2;0\n3;'4''';1\n";
+        let options = CsvOptions::new()
+            .has_header(false)
+            .delimiter(';')
+            .quote(Some('\''))
+            .label_column(Some(Column::Index(0)))
+            .categorical_columns(vec![Column::Index(0), Column::Index(1)]);
+        let dataset = CsvDataset::from_reader(csv.as_bytes(), &options).unwrap();
+
+        assert_eq!(
+            dataset.label_categories,
+            Some(vec!["1;5".to_string(), "3".to_string()])
+        );
+        assert_eq!(
+            dataset.feature_categories[0],
+            Some(vec!["2".to_string(), "4'".to_string()])
+        );
+        assert_eq!(dataset.feature_names, vec!["column_1", "column_2"]);
+    }
+
+    #[test]
+    fn missing_values() {
+        let csv: &str = "a,b,label\n1,NA,0\n3,4,1\n,6,1\n";
+
+        let options = CsvOptions::new().missing_values(MissingValues::Mean);
+        let dataset = CsvDataset::from_reader(csv.as_bytes(), &options).unwrap();
+        assert!(dataset
+            .data
+            .is_equal(&Matrix::init(3, 2, vec![1.0, 5.0, 3.0, 4.0, 2.0, 6.0]), 15));
+
+        // no value to compute the mean of the column "b"
+        let result = CsvDataset::from_reader("a,b,label\n1,NA,0\n3,,1\n".as_bytes(), &options);
+        assert_eq!(
+            result.err(),
+            Some(CsvDatasetError::CouldNotParseLine {
+                line: 2,
+                details:
+                    "every value of the column \"b\" is missing, its mean can not replace them"
+                        .to_string()
+            })
+        );
+
+        let options = CsvOptions::new().missing_values(MissingValues::DropRows);
+        let dataset = CsvDataset::from_reader(csv.as_bytes(), &options).unwrap();
+        assert_eq!(dataset.len(), 1);
+
+        let options = CsvOptions::new().missing_values(MissingValues::Fill(-1.0));
+        let dataset = CsvDataset::from_reader(csv.as_bytes(), &options).unwrap();
+        assert_eq!(dataset.data.get(0, 1), -1.0);
+
+        let result = CsvDataset::from_reader(csv.as_bytes(), &CsvOptions::new());
+        assert_eq!(
+            result.err(),
+            Some(CsvDatasetError::CouldNotParseLine {
+                line: 2,
+                details: "missing value in the column \"b\"".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        let options = CsvOptions::new();
+
+        let result = CsvDataset::from_reader("a,b\n1,2\n\n3,x\n".as_bytes(), &options);
+        assert!(matches!(
+            result,
+            Err(CsvDatasetError::CouldNotParseLine { line: 4, .. })
+        ));
+
+        let result = CsvDataset::from_reader("a,b\n1,2\n3\n".as_bytes(), &options);
+        assert!(matches!(
+            result,
+            Err(CsvDatasetError::CouldNotParseLine { line: 3, .. })
+        ));
+
+        let result = CsvDataset::from_reader("a,b\n\"1,2\n".as_bytes(), &options);
+        assert!(matches!(
+            result,
+            Err(CsvDatasetError::CouldNotParseLine { line: 2, .. })
+        ));
+
+        // text after a closing quote is not merged into the field
+        let result = CsvDataset::from_reader("a,b\n1,2\n\"3\"4,5\n".as_bytes(), &options);
+        assert!(matches!(
+            result,
+            Err(CsvDatasetError::CouldNotParseLine { line: 3, .. })
+        ));
+        let result = CsvDataset::from_reader("a,b\n\"1\" ,\"2\"\n".as_bytes(), &options);
+        assert!(result.is_ok());
+
+        let options = CsvOptions::new().label_column(Some(Column::Name("c".to_string())));
+        let result = CsvDataset::from_reader("a,b\n1,2\n".as_bytes(), &options);
+        assert!(matches!(result, Err(CsvDatasetError::UnknownColumn(_))));
+    }
+}
@@ -0,0 +1,464 @@
+use crate::error::BriqueError;
+use crate::loss::TargetMode;
+use crate::matrix::Matrix;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::fmt;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+// inputs, labels and optional sample weights of a batch
+// the labels are 1 x B class indexes or B x C targets, depending on the target mode
+pub struct Batch {
+    pub data: Matrix,
+    pub labels: Matrix,
+    pub weights: Option<Matrix>,
+}
+
+// a source of samples accessed by index
+// Sync so the batches can be prepared on a background thread
+pub trait Dataset: Sync {
+    // number of samples
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // number of features of a sample
+    fn width(&self) -> usize;
+
+    // features and label of a sample, the label is one class index or one row of targets
+    fn get(&self, index: usize) -> (Vec<f64>, Vec<f64>);
+
+    // weight of the loss of the sample, None if the dataset is not weighted
+    fn sample_weight(&self, _index: usize) -> Option<f64> {
+        None
+    }
+
+    fn get_batch(&self, indexes: &[usize], target_mode: &TargetMode) -> Batch {
+        let mut data: Matrix = Matrix::init_zero(indexes.len(), self.width());
+        let mut labels: Option<Matrix> = None;
+        let mut weights: Option<Matrix> = None;
+
+        for (i, index) in indexes.iter().enumerate() {
+            let (features, label): (Vec<f64>, Vec<f64>) = self.get(*index);
+            data.set_row(&features, i);
+
+            let labels: &mut Matrix = labels.get_or_insert_with(|| match target_mode {
+                TargetMode::ClassIndex => Matrix::init_zero(1, indexes.len()),
+                _ => Matrix::init_zero(indexes.len(), label.len()),
+            });
+            match target_mode {
+                TargetMode::ClassIndex => labels.set(label[0], 0, i),
+                _ => labels.set_row(&label, i),
+            }
+
+            if let Some(weight) = self.sample_weight(*index) {
+                weights
+                    .get_or_insert_with(|| Matrix::init_zero(1, indexes.len()))
+                    .set(weight, 0, i);
+            }
+        }
+
+        Batch {
+            data,
+            labels: labels.unwrap_or_else(|| Matrix::init_zero(1, 0)),
+            weights,
+        }
+    }
+}
+
+// in memory dataset, the labels are 1 x N class indexes or N x C targets
+pub struct MatrixDataset<'a> {
+    data: &'a Matrix,
+    labels: &'a Matrix,
+    sample_weights: Option<&'a Matrix>,
+}
+
+impl<'a> MatrixDataset<'a> {
+    pub fn init(data: &'a Matrix, labels: &'a Matrix) -> MatrixDataset<'a> {
+        MatrixDataset {
+            data,
+            labels,
+            sample_weights: None,
+        }
+    }
+
+    // sample_weights : 1 x N matrix
+    pub fn with_sample_weights(mut self, sample_weights: &'a Matrix) -> MatrixDataset<'a> {
+        self.sample_weights = Some(sample_weights);
+        self
+    }
+
+    fn class_indexes(&self) -> bool {
+        self.labels.height == 1 && self.labels.width == self.data.height
+    }
+}
+
+impl Dataset for MatrixDataset<'_> {
+    fn len(&self) -> usize {
+        self.data.height
+    }
+
+    fn width(&self) -> usize {
+        self.data.width
+    }
+
+    fn get(&self, index: usize) -> (Vec<f64>, Vec<f64>) {
+        let label: Vec<f64> = if self.class_indexes() {
+            vec![self.labels.get(0, index)]
+        } else {
+            self.labels.get_row(index)
+        };
+
+        (self.data.get_row(index), label)
+    }
+
+    fn sample_weight(&self, index: usize) -> Option<f64> {
+        self.sample_weights.map(|weights| weights.get(0, index))
+    }
+
+    fn get_batch(&self, indexes: &[usize], target_mode: &TargetMode) -> Batch {
+        let mut data: Matrix = Matrix::init_zero(indexes.len(), self.data.width);
+        let mut weights: Option<Matrix> = self
+            .sample_weights
+            .map(|_| Matrix::init_zero(1, indexes.len()));
+
+        for (i, index) in indexes.iter().enumerate() {
+            data.set_row(&self.data.get_row(*index), i);
+            if let (Some(sample_weights), Some(weights)) = (self.sample_weights, weights.as_mut()) {
+                weights.set(sample_weights.get(0, *index), 0, i);
+            }
+        }
+
+        Batch {
+            data,
+            labels: target_mode.select(self.labels, indexes),
+            weights,
+        }
+    }
+}
+
+// user supplied sampler, receives the length of the dataset, the epoch and the rng of the loader
+pub type SamplerFn = Arc<dyn Fn(usize, usize, &mut StdRng) -> Vec<usize> + Send + Sync>;
+
+// order in which the samples of an epoch are read
+#[derive(Clone)]
+pub enum Sampler {
+    // every sample, shuffled if the loader shuffles
+    Sequential,
+    // only these samples, in this order or shuffled if the loader shuffles
+    Subset(Vec<usize>),
+    // the returned indexes are used as they are, samples can be repeated or skipped
+    Custom(SamplerFn),
+}
+
+impl fmt::Debug for Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sampler::Sequential => write!(f, "Sequential"),
+            Sampler::Subset(indexes) => write!(f, "Subset({:?})", indexes),
+            Sampler::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+// splits a dataset into batches, epoch after epoch
+pub struct DataLoader<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    batch_size: usize,
+    shuffle: bool,
+    drop_last: bool,
+    sampler: Sampler,
+    // number of batches prepared in advance on a background thread, 0 to disable
+    prefetch: usize,
+    seed: Option<u64>,
+}
+
+impl<'a, D: Dataset + ?Sized> DataLoader<'a, D> {
+    // by default : every sample, shuffled every epoch, the last batch can be smaller,
+    // no prefetching
+    pub fn init(dataset: &'a D, batch_size: usize) -> DataLoader<'a, D> {
+        DataLoader {
+            dataset,
+            batch_size,
+            shuffle: true,
+            drop_last: false,
+            sampler: Sampler::Sequential,
+            prefetch: 0,
+            seed: None,
+        }
+    }
+
+    pub fn shuffle(mut self, shuffle: bool) -> DataLoader<'a, D> {
+        self.shuffle = shuffle;
+        self
+    }
+
+    // if true the last batch is skipped when it is smaller than the batch size
+    pub fn drop_last(mut self, drop_last: bool) -> DataLoader<'a, D> {
+        self.drop_last = drop_last;
+        self
+    }
+
+    pub fn sampler(mut self, sampler: Sampler) -> DataLoader<'a, D> {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn prefetch(mut self, number_of_batches: usize) -> DataLoader<'a, D> {
+        self.prefetch = number_of_batches;
+        self
+    }
+
+    // the shuffling of every epoch is reproducible
+    pub fn seed(mut self, seed: u64) -> DataLoader<'a, D> {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn dataset(&self) -> &'a D {
+        self.dataset
+    }
+
+    // indexes of the samples of every batch of the epoch
+    pub fn batches(&self, epoch: usize) -> Result<Vec<Vec<usize>>, BriqueError> {
+        let mut rng: StdRng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(epoch as u64)),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+
+        let mut indexes: Vec<usize> = match &self.sampler {
+            Sampler::Sequential => (0..self.dataset.len()).collect(),
+            Sampler::Subset(indexes) => indexes.clone(),
+            Sampler::Custom(sampler) => sampler(self.dataset.len(), epoch, &mut rng),
+        };
+        if let Some(index) = indexes.iter().find(|i| **i >= self.dataset.len()) {
+            return Err(BriqueError::Config(format!(
+                "the sampler returned the index {} for a dataset of {} samples",
+                index,
+                self.dataset.len()
+            )));
+        }
+        if indexes.len() < self.batch_size {
+            return Err(BriqueError::Config(
+                "Batch size cannot be bigger than training dataset size".to_string(),
+            ));
+        }
+        if self.batch_size == 0 {
+            return Err(BriqueError::Config(
+                "Batch size must be strictly positive".to_string(),
+            ));
+        }
+
+        if self.shuffle && !matches!(self.sampler, Sampler::Custom(_)) {
+            indexes.shuffle(&mut rng);
+        }
+
+        Ok(indexes
+            .chunks(self.batch_size)
+            .filter(|batch| !self.drop_last || batch.len() == self.batch_size)
+            .map(|batch| batch.to_vec())
+            .collect())
+    }
+
+    // batches of the epoch, prepared when they are read
+    pub fn iter(
+        &self,
+        epoch: usize,
+        target_mode: &'a TargetMode,
+    ) -> Result<impl Iterator<Item = Batch> + 'a, BriqueError> {
+        let dataset: &'a D = self.dataset;
+
+        Ok(self
+            .batches(epoch)?
+            .into_iter()
+            .map(move |indexes| dataset.get_batch(&indexes, target_mode)))
+    }
+
+    // calls f(batch index, number of batches, batch) for every batch of the epoch
+    // f returns false to stop the epoch
+    // with prefetching, the next batches are prepared on a background thread while f runs
+    pub fn for_each_batch<F>(
+        &self,
+        epoch: usize,
+        target_mode: &TargetMode,
+        mut f: F,
+    ) -> Result<(), BriqueError>
+    where
+        F: FnMut(usize, usize, Batch) -> Result<bool, BriqueError>,
+    {
+        let batches: Vec<Vec<usize>> = self.batches(epoch)?;
+        let number_of_batches: usize = batches.len();
+
+        if self.prefetch == 0 {
+            for (batch_index, indexes) in batches.iter().enumerate() {
+                let batch: Batch = self.dataset.get_batch(indexes, target_mode);
+                if !f(batch_index, number_of_batches, batch)? {
+                    break;
+                }
+            }
+            return Ok(());
+        }
+
+        let dataset: &D = self.dataset;
+        thread::scope(|s| {
+            let (sender, receiver) = mpsc::sync_channel::<Batch>(self.prefetch);
+            s.spawn(move || {
+                for indexes in &batches {
+                    // the receiver is dropped when f stops the epoch
+                    if sender
+                        .send(dataset.get_batch(indexes, target_mode))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+
+            for (batch_index, batch) in receiver.iter().enumerate() {
+                if !f(batch_index, number_of_batches, batch)? {
+                    break;
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataLoader, Dataset, MatrixDataset, Sampler};
+    use crate::{error::BriqueError, loss::TargetMode, matrix::Matrix};
+    use std::sync::Arc;
+
+    fn get_test_data() -> (Matrix, Matrix) {
+        let data = Matrix::init(5, 2, vec![0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 3.5, 4.0, 4.5]);
+        let labels = Matrix::init(1, 5, vec![0.0, 1.0, 0.0, 1.0, 0.0]);
+
+        (data, labels)
+    }
+
+    // a dataset computing its samples, the features of sample i are [i, 2i]
+    struct GeneratedDataset;
+
+    impl Dataset for GeneratedDataset {
+        fn len(&self) -> usize {
+            7
+        }
+
+        fn width(&self) -> usize {
+            2
+        }
+
+        fn get(&self, index: usize) -> (Vec<f64>, Vec<f64>) {
+            (
+                vec![index as f64, 2.0 * index as f64],
+                vec![(index % 2) as f64],
+            )
+        }
+    }
+
+    #[test]
+    fn batches_cover_the_dataset() {
+        let (data, labels) = get_test_data();
+        let dataset = MatrixDataset::init(&data, &labels);
+
+        let batches = DataLoader::init(&dataset, 2).seed(3).batches(0).unwrap();
+        assert_eq!(
+            batches.iter().map(|b| b.len()).collect::<Vec<usize>>(),
+            vec![2, 2, 1]
+        );
+        let mut all: Vec<usize> = batches.concat();
+        all.sort();
+        assert_eq!(all, vec![0, 1, 2, 3, 4]);
+
+        // reproducible with a seed
+        let loader = DataLoader::init(&dataset, 2).seed(3);
+        assert_eq!(loader.batches(1).unwrap(), loader.batches(1).unwrap());
+
+        let loader = DataLoader::init(&dataset, 2).shuffle(false).drop_last(true);
+        assert_eq!(loader.batches(0).unwrap(), vec![vec![0, 1], vec![2, 3]]);
+
+        assert!(matches!(
+            DataLoader::init(&dataset, 6).batches(0),
+            Err(BriqueError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn samplers() {
+        let (data, labels) = get_test_data();
+        let dataset = MatrixDataset::init(&data, &labels);
+
+        let loader = DataLoader::init(&dataset, 2)
+            .shuffle(false)
+            .sampler(Sampler::Subset(vec![4, 2, 0]));
+        assert_eq!(loader.batches(0).unwrap(), vec![vec![4, 2], vec![0]]);
+
+        // the epoch is used to pick the samples
+        let loader =
+            DataLoader::init(&dataset, 2).sampler(Sampler::Custom(Arc::new(|len, epoch, _| {
+                vec![epoch % len; 2]
+            })));
+        assert_eq!(loader.batches(3).unwrap(), vec![vec![3, 3]]);
+
+        let loader = DataLoader::init(&dataset, 1).sampler(Sampler::Subset(vec![5]));
+        assert!(loader.batches(0).is_err());
+    }
+
+    #[test]
+    fn prefetched_batches_match_the_dataset() {
+        let dataset = GeneratedDataset;
+        let loader = DataLoader::init(&dataset, 3).seed(0).prefetch(2);
+        let expected = loader.batches(0).unwrap();
+
+        let mut received: Vec<Vec<f64>> = vec![];
+        loader
+            .for_each_batch(0, &TargetMode::ClassIndex, |batch_index, count, batch| {
+                assert_eq!(count, 3);
+                for (i, index) in expected[batch_index].iter().enumerate() {
+                    assert_eq!(
+                        batch.data.get_row(i),
+                        vec![*index as f64, 2.0 * *index as f64]
+                    );
+                    assert_eq!(batch.labels.get(0, i), (*index % 2) as f64);
+                }
+                received.push(batch.data.get_row(0));
+                // stops after the second batch
+                Ok(batch_index < 1)
+            })
+            .unwrap();
+
+        assert_eq!(received.len(), 2);
+    }
+
+    #[test]
+    fn weighted_matrix_dataset() {
+        let (data, labels) = get_test_data();
+        let weights = Matrix::init(1, 5, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        let dataset = MatrixDataset::init(&data, &labels).with_sample_weights(&weights);
+
+        let batch = DataLoader::init(&dataset, 2)
+            .shuffle(false)
+            .iter(0, &TargetMode::ClassIndex)
+            .unwrap()
+            .nth(1)
+            .unwrap();
+
+        assert!(batch
+            .data
+            .is_equal(&Matrix::init(2, 2, vec![2.0, 2.5, 3.0, 3.5]), 15));
+        assert!(batch
+            .labels
+            .is_equal(&Matrix::init(1, 2, vec![0.0, 1.0]), 15));
+        assert!(batch
+            .weights
+            .unwrap()
+            .is_equal(&Matrix::init(1, 2, vec![3.0, 4.0]), 15));
+    }
+}
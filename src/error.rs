@@ -0,0 +1,160 @@
+use crate::batch_predictor::BatchPredictionError;
+use crate::csv_dataset::CsvDatasetError;
+use crate::save_load::ModelManagementError;
+use std::fmt;
+
+// error returned by the try_ variants of the api
+#[derive(Debug, PartialEq)]
+pub enum BriqueError {
+    // incompatible dimensions or out of bound access
+    Shape(String),
+    // invalid model, the error names the offending layer
+    Build(ModelBuildError),
+    // file system errors
+    Io(String),
+    // unexpected or corrupted file content
+    Format(String),
+    // invalid parameter, e.g. a batch size of 0
+    Config(String),
+}
+
+impl fmt::Display for BriqueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BriqueError::Shape(msg) => write!(f, "Shape error : {}", msg),
+            BriqueError::Build(e) => write!(f, "Invalid model : {}", e),
+            BriqueError::Io(msg) => write!(f, "IO error : {}", msg),
+            BriqueError::Format(msg) => write!(f, "Format error : {}", msg),
+            BriqueError::Config(msg) => write!(f, "Configuration error : {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for BriqueError {}
+
+// invalid model or data, see ModelBuilder::try_build and Model::check_data_shape
+#[derive(Debug, PartialEq)]
+pub enum ModelBuildError {
+    NoLayers,
+    // the input size of the layer does not match the output size of the previous one
+    LayerShapeMismatch {
+        layer: usize,
+        expected_input_size: usize,
+        input_size: usize,
+    },
+    // the biases of the layer do not have one value per neuron
+    BiasesShapeMismatch {
+        layer: usize,
+        expected_width: usize,
+        width: usize,
+    },
+    // a layer added with dense has no previous layer and input_size was not called
+    MissingInputSize {
+        layer: usize,
+    },
+    // the width of the data does not match the input size of the first layer
+    InputWidthMismatch {
+        expected_width: usize,
+        width: usize,
+    },
+    // the number of samples of the data and the labels are not the same
+    LabelsMismatch {
+        samples: usize,
+        labels: usize,
+    },
+    // a gradient clipping threshold must be finite and strictly positive
+    InvalidClipThreshold {
+        value: f64,
+    },
+}
+
+impl fmt::Display for ModelBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelBuildError::NoLayers => write!(f, "No layers have been added to the model"),
+            ModelBuildError::LayerShapeMismatch {
+                layer,
+                expected_input_size,
+                input_size,
+            } => write!(
+                f,
+                "Layer {} has an input size of {}, expected {} (output size of the previous layer or model input size)",
+                layer, input_size, expected_input_size
+            ),
+            ModelBuildError::BiasesShapeMismatch {
+                layer,
+                expected_width,
+                width,
+            } => write!(
+                f,
+                "Layer {} has {} biases, expected {} (one per neuron)",
+                layer, width, expected_width
+            ),
+            ModelBuildError::MissingInputSize { layer } => write!(
+                f,
+                "The input size of layer {} can not be inferred, call input_size before adding it",
+                layer
+            ),
+            ModelBuildError::InputWidthMismatch {
+                expected_width,
+                width,
+            } => write!(
+                f,
+                "The data has {} features, layer 0 expects {}",
+                width, expected_width
+            ),
+            ModelBuildError::LabelsMismatch { samples, labels } => write!(
+                f,
+                "The data has {} samples but there are {} labels",
+                samples, labels
+            ),
+            ModelBuildError::InvalidClipThreshold { value } => write!(
+                f,
+                "The gradient clipping threshold is {}, expected a finite and strictly positive value",
+                value
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for BriqueError {
+    fn from(e: std::io::Error) -> Self {
+        BriqueError::Io(e.to_string())
+    }
+}
+
+impl From<ModelBuildError> for BriqueError {
+    fn from(e: ModelBuildError) -> Self {
+        BriqueError::Build(e)
+    }
+}
+
+impl From<ModelManagementError> for BriqueError {
+    fn from(e: ModelManagementError) -> Self {
+        match e {
+            ModelManagementError::CouldNotSaveModel(_)
+            | ModelManagementError::CouldNotReadFile(_) => BriqueError::Io(e.to_string()),
+            ModelManagementError::CouldNotDecodeBinary(_) => BriqueError::Format(e.to_string()),
+        }
+    }
+}
+
+impl From<BatchPredictionError> for BriqueError {
+    fn from(e: BatchPredictionError) -> Self {
+        match e {
+            BatchPredictionError::CouldNotParseLine { .. } => BriqueError::Format(e.to_string()),
+            BatchPredictionError::InvalidChunkShape { .. } => BriqueError::Shape(e.to_string()),
+            _ => BriqueError::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<CsvDatasetError> for BriqueError {
+    fn from(e: CsvDatasetError) -> Self {
+        match e {
+            CsvDatasetError::CouldNotReadFile(_) => BriqueError::Io(e.to_string()),
+            CsvDatasetError::CouldNotParseLine { .. } => BriqueError::Format(e.to_string()),
+            CsvDatasetError::UnknownColumn(_) => BriqueError::Config(e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use crate::layers::LayerGradients;
+use crate::matrix::Matrix;
+use crate::model::Model;
+
+#[derive(Clone, Debug)]
+pub struct LayerGradientCheck {
+    pub layer: usize,
+    pub weights_max_relative_error: f64,
+    pub biases_max_relative_error: f64,
+}
+
+impl LayerGradientCheck {
+    pub fn max_relative_error(&self) -> f64 {
+        self.weights_max_relative_error
+            .max(self.biases_max_relative_error)
+    }
+}
+
+// compares the analytic gradients of the backprop against central finite differences
+// (loss(p + epsilon) - loss(p - epsilon)) / (2 * epsilon), for every weight and bias
+// the model is not modified, the checks are done on a copy
+pub fn gradient_check(
+    model: &Model,
+    data: &Matrix,
+    labels: &Matrix,
+    epsilon: f64,
+) -> Vec<LayerGradientCheck> {
+    let mut model: Model = model.clone();
+    // the gradients are checked on the input of the first layer
+    let transformed: Option<Matrix> = model.preprocessing.take().map(|p| p.transform(data));
+    let data: &Matrix = transformed.as_ref().unwrap_or(data);
+
+    let logits: Matrix = model.evaluate_logits(data, false);
+    let d_score: Matrix = Model::compute_d_logits(
+        &logits,
+        labels,
+        None,
+        &model.loss_function,
+        &model.target_mode,
+    );
+    let analytic_gradients: Vec<LayerGradients> = model.compute_gradients(d_score, data);
+
+    let mut output: Vec<LayerGradientCheck> = vec![];
+    for (index, gradients) in analytic_gradients.iter().enumerate() {
+        let mut weights_max_relative_error: f64 = 0.0;
+        for r in 0..gradients.d_weights.height {
+            for c in 0..gradients.d_weights.width {
+                let initial_value: f64 = model.layers[index].weights_t.get(r, c);
+
+                model.layers[index]
+                    .weights_t
+                    .set(initial_value + epsilon, r, c);
+                let loss_plus: f64 = summed_loss(&mut model, data, labels);
+                model.layers[index]
+                    .weights_t
+                    .set(initial_value - epsilon, r, c);
+                let loss_minus: f64 = summed_loss(&mut model, data, labels);
+                model.layers[index].weights_t.set(initial_value, r, c);
+
+                let numerical: f64 = (loss_plus - loss_minus) / (2.0 * epsilon);
+                weights_max_relative_error = weights_max_relative_error
+                    .max(relative_error(gradients.d_weights.get(r, c), numerical));
+            }
+        }
+
+        let mut biases_max_relative_error: f64 = 0.0;
+        for c in 0..gradients.d_biases.width {
+            let initial_value: f64 = model.layers[index].biases.get(0, c);
+
+            model.layers[index]
+                .biases
+                .set(initial_value + epsilon, 0, c);
+            let loss_plus: f64 = summed_loss(&mut model, data, labels);
+            model.layers[index]
+                .biases
+                .set(initial_value - epsilon, 0, c);
+            let loss_minus: f64 = summed_loss(&mut model, data, labels);
+            model.layers[index].biases.set(initial_value, 0, c);
+
+            let numerical: f64 = (loss_plus - loss_minus) / (2.0 * epsilon);
+            biases_max_relative_error = biases_max_relative_error
+                .max(relative_error(gradients.d_biases.get(0, c), numerical));
+        }
+
+        output.push(LayerGradientCheck {
+            layer: index,
+            weights_max_relative_error,
+            biases_max_relative_error,
+        });
+    }
+
+    output
+}
+
+// uses the loss function of the model
+// the backprop computes the gradient of the data loss summed over the batch
+// (d_score is not divided by the batch size) plus the l2 penalty
+fn summed_loss(model: &mut Model, data: &Matrix, labels: &Matrix) -> f64 {
+    let logits: Matrix = model.evaluate_logits(data, false);
+    let (data_loss, reg_loss): (f64, f64) =
+        model.compute_loss_from_logits(&logits, labels, None, false);
+
+    data_loss * data.height as f64 + reg_loss
+}
+
+fn relative_error(analytic: f64, numerical: f64) -> f64 {
+    if analytic == numerical {
+        return 0.0;
+    }
+
+    (analytic - numerical).abs() / analytic.abs().max(numerical.abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::gradient_check;
+    use crate::{
+        layers::Layer, matrix::Matrix, model::Model, optimizer::Optimizer,
+        parse_test_csv::parse_test_csv,
+    };
+
+    fn get_test_model() -> Model {
+        let input_weights: Vec<Matrix> =
+            parse_test_csv("tests/test_data/test_input_weights.csv".to_string());
+
+        // non zero biases, with zero biases a whole row of the hidden layers
+        // can sit exactly on the relu kink, where finite differences are not valid
+        let biases = Matrix::init(1, 3, vec![0.1, -0.05, 0.2]);
+
+        Model::init(
+            vec![
+                Layer::init_with_data(input_weights[0].clone(), biases.clone(), true),
+                Layer::init_with_data(input_weights[1].clone(), biases.clone(), true),
+                Layer::init_with_data(input_weights[2].clone(), biases, false),
+            ],
+            Optimizer::SGD { learning_step: 0.1 },
+            0.001,
+        )
+    }
+
+    #[test]
+    fn backprop_matches_finite_differences() {
+        let test_data: Vec<Matrix> = parse_test_csv("tests/test_data/test_data.csv".to_string());
+        let model = get_test_model();
+
+        let checks = gradient_check(&model, &test_data[0], &test_data[1], 1e-5);
+
+        assert_eq!(checks.len(), 3);
+        for check in checks {
+            assert!(
+                check.max_relative_error() < 1e-4,
+                "Gradient check failed for layer {} : weights error {}, biases error {}",
+                check.layer,
+                check.weights_max_relative_error,
+                check.biases_max_relative_error
+            );
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use crate::numerical_guard::NumericalIssue;
+
+// values recorded by Model::train, reset at the begining of every training
+#[derive(Clone, Debug, Default)]
+pub struct TrainingHistory {
+    // global L2 norm of the weights and biases gradients of the trainable
+    // layers, before clipping, one value per iteration
+    pub gradient_norms: Vec<f64>,
+    // non finite values caught by the numerical guard
+    pub numerical_issues: Vec<NumericalIssue>,
+}
+
+impl TrainingHistory {
+    pub fn new() -> TrainingHistory {
+        TrainingHistory::default()
+    }
+}
@@ -0,0 +1,681 @@
+use crate::cross_validation::select_rows;
+use crate::error::BriqueError;
+use crate::loss::{data_loss, TargetMode};
+use crate::matrix::Matrix;
+use crate::metrics::Metric;
+use crate::model::Model;
+use crate::model_builder::ModelBuilder;
+use crate::optimizer::Optimizer;
+use crate::validation::{sample_classes, ValidationSplit};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// values tried for every option of the model builder
+#[derive(Clone, Debug)]
+pub struct SearchSpace {
+    pub optimizers: Vec<Optimizer>,
+    pub l2_regs: Vec<f64>,
+    pub batch_sizes: Vec<u32>,
+    // widths of the hidden layers (with ReLU), the output layer is added after them
+    pub hidden_layers: Vec<Vec<u32>>,
+    // number of neurons of the output layer
+    pub output_size: u32,
+}
+
+impl SearchSpace {
+    // starts with the defaults of the model builder and no hidden layer
+    pub fn new(output_size: u32) -> SearchSpace {
+        SearchSpace {
+            optimizers: vec![Optimizer::SGD {
+                learning_step: 0.01,
+            }],
+            l2_regs: vec![0.001],
+            batch_sizes: vec![32],
+            hidden_layers: vec![vec![]],
+            output_size,
+        }
+    }
+
+    pub fn optimizers(mut self, optimizers: Vec<Optimizer>) -> SearchSpace {
+        self.optimizers = optimizers;
+        self
+    }
+
+    pub fn l2_regs(mut self, l2_regs: Vec<f64>) -> SearchSpace {
+        self.l2_regs = l2_regs;
+        self
+    }
+
+    pub fn batch_sizes(mut self, batch_sizes: Vec<u32>) -> SearchSpace {
+        self.batch_sizes = batch_sizes;
+        self
+    }
+
+    pub fn hidden_layers(mut self, hidden_layers: Vec<Vec<u32>>) -> SearchSpace {
+        self.hidden_layers = hidden_layers;
+        self
+    }
+
+    // number of configurations of the grid
+    pub fn size(&self) -> usize {
+        self.optimizers.len()
+            * self.l2_regs.len()
+            * self.batch_sizes.len()
+            * self.hidden_layers.len()
+    }
+
+    // every combination of the values
+    pub fn grid(&self) -> Vec<TrialConfig> {
+        let mut output: Vec<TrialConfig> = vec![];
+        for optimizer in &self.optimizers {
+            for l2_reg in &self.l2_regs {
+                for batch_size in &self.batch_sizes {
+                    for hidden_layers in &self.hidden_layers {
+                        output.push(TrialConfig {
+                            optimizer: optimizer.clone(),
+                            l2_reg: *l2_reg,
+                            batch_size: *batch_size,
+                            hidden_layers: hidden_layers.clone(),
+                            output_size: self.output_size,
+                        });
+                    }
+                }
+            }
+        }
+
+        output
+    }
+
+    // one value picked at random for every option
+    pub fn sample(&self, rng: &mut StdRng) -> TrialConfig {
+        TrialConfig {
+            optimizer: self.optimizers[rng.random_range(0..self.optimizers.len())].clone(),
+            l2_reg: self.l2_regs[rng.random_range(0..self.l2_regs.len())],
+            batch_size: self.batch_sizes[rng.random_range(0..self.batch_sizes.len())],
+            hidden_layers: self.hidden_layers[rng.random_range(0..self.hidden_layers.len())]
+                .clone(),
+            output_size: self.output_size,
+        }
+    }
+
+    fn check(&self) -> Result<(), BriqueError> {
+        if self.size() == 0 {
+            return Err(BriqueError::Config(
+                "every option of the search space needs at least one value".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+// one point of the search space
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrialConfig {
+    pub optimizer: Optimizer,
+    pub l2_reg: f64,
+    pub batch_size: u32,
+    pub hidden_layers: Vec<u32>,
+    pub output_size: u32,
+}
+
+impl TrialConfig {
+    // the base builder with the options and the layers of the trial, without its checkpoint
+    // the base builder should not have any layer
+    pub fn apply(&self, base: &ModelBuilder, input_size: u32) -> ModelBuilder {
+        let mut builder: ModelBuilder = base
+            .clone()
+            .without_checkpoint()
+            .optimizer(self.optimizer.clone())
+            .l2_reg(self.l2_reg)
+            .input_size(input_size);
+        for width in &self.hidden_layers {
+            builder = builder.dense(*width, true);
+        }
+
+        builder.dense(self.output_size, false)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SearchStrategy {
+    // every configuration of the search space
+    Grid,
+    // trials configurations picked at random
+    Random {
+        trials: usize,
+        seed: u64,
+    },
+    // trials random configurations are trained for min_epochs, the best 1 / reduction_factor
+    // of them are trained again with reduction_factor times more epochs, until one is
+    // left or the number of epochs of the search is reached
+    SuccessiveHalving {
+        trials: usize,
+        min_epochs: u32,
+        reduction_factor: usize,
+        seed: u64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrialResult {
+    // index of the configuration in the list of tried configurations
+    pub trial: usize,
+    pub config: TrialConfig,
+    // epochs of the last training of the configuration
+    pub epochs: u32,
+    pub validation_loss: f64,
+    pub validation_accuracy: f64,
+    // value of the objective on the validation dataset
+    pub score: f64,
+}
+
+pub struct SearchReport {
+    pub objective: Metric,
+    // best trial first
+    pub trials: Vec<TrialResult>,
+}
+
+impl SearchReport {
+    pub fn best(&self) -> &TrialResult {
+        &self.trials[0]
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut output: String = "rank,trial,optimizer,learning_step,beta1,beta2,l2_reg,batch_size,hidden_layers,epochs,validation_loss,validation_accuracy,score\n".to_string();
+        for (rank, result) in self.trials.iter().enumerate() {
+            let optimizer: String = match result.config.optimizer {
+                Optimizer::SGD { learning_step } => format!("SGD,{},,", learning_step),
+                Optimizer::Adam {
+                    learning_step,
+                    beta1,
+                    beta2,
+                } => format!("Adam,{},{},{}", learning_step, beta1, beta2),
+            };
+            // the widths are separated by x, e.g. 128x64
+            let hidden_layers: Vec<String> = result
+                .config
+                .hidden_layers
+                .iter()
+                .map(|width| width.to_string())
+                .collect();
+
+            output.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{},{}\n",
+                rank + 1,
+                result.trial,
+                optimizer,
+                result.config.l2_reg,
+                result.config.batch_size,
+                hidden_layers.join("x"),
+                result.epochs,
+                result.validation_loss,
+                result.validation_accuracy,
+                result.score
+            ));
+        }
+
+        output
+    }
+
+    pub fn write_leaderboard(&self, file_path: &str) -> Result<(), BriqueError> {
+        fs::write(file_path, self.to_csv())?;
+
+        Ok(())
+    }
+}
+
+pub struct HyperparameterSearch {
+    // options shared by every trial (loss, target mode, seed, verbosity...), without layers
+    // its checkpoint is ignored, the trials run at the same time would overwrite each other
+    base: ModelBuilder,
+    space: SearchSpace,
+    strategy: SearchStrategy,
+    // maximum number of epochs of a training
+    epochs: u32,
+    objective: Metric,
+    validation: ValidationSplit,
+    threads: usize,
+}
+
+impl HyperparameterSearch {
+    // by default the trials are ranked by validation accuracy, on a random fifth of the
+    // data, one at a time
+    pub fn init(
+        base: ModelBuilder,
+        space: SearchSpace,
+        strategy: SearchStrategy,
+        epochs: u32,
+    ) -> HyperparameterSearch {
+        HyperparameterSearch {
+            base,
+            space,
+            strategy,
+            epochs,
+            objective: Metric::Accuracy,
+            validation: ValidationSplit::Fraction(0.2),
+            threads: 1,
+        }
+    }
+
+    pub fn objective(mut self, objective: Metric) -> HyperparameterSearch {
+        self.objective = objective;
+        self
+    }
+
+    // the split is done once, every trial uses the same validation dataset
+    pub fn validation(mut self, validation: ValidationSplit) -> HyperparameterSearch {
+        self.validation = validation;
+        self
+    }
+
+    // number of trials trained at the same time
+    pub fn threads(mut self, threads: usize) -> HyperparameterSearch {
+        self.threads = threads;
+        self
+    }
+
+    pub fn run(&self, data: &Matrix, labels: &Matrix) -> Result<SearchReport, BriqueError> {
+        self.space.check()?;
+        if self.base.has_layers() {
+            return Err(BriqueError::Config(
+                "the base builder of the search should not have any layer".to_string(),
+            ));
+        }
+        if self.threads == 0 {
+            return Err(BriqueError::Config(
+                "the number of threads must be strictly positive".to_string(),
+            ));
+        }
+
+        // the first configuration is built to check the base builder and the data
+        let (target_mode, seed): (TargetMode, Option<u64>) = {
+            let model: Model = self.space.grid()[0]
+                .apply(&self.base, data.width as u32)
+                .try_build()?;
+            model.check_data_shape(data, labels)?;
+            (model.target_mode, model.seed)
+        };
+        if self.objective != Metric::Accuracy {
+            if let TargetMode::MultiLabel { .. } = target_mode {
+                return Err(BriqueError::Config(
+                    "only the accuracy objective is available for multi-label targets".to_string(),
+                ));
+            }
+        }
+
+        let (training_data, training_labels, validation_data, validation_labels): (
+            Matrix,
+            Matrix,
+            Matrix,
+            Matrix,
+        ) = match &self.validation {
+            ValidationSplit::Explicit {
+                data: validation_data,
+                labels: validation_labels,
+            } => (
+                data.clone(),
+                labels.clone(),
+                validation_data.clone(),
+                validation_labels.clone(),
+            ),
+            validation => {
+                let (training, validation): (Vec<u32>, Vec<u32>) =
+                    validation.split(labels, data.height, &target_mode, true, seed)?;
+                let training: Vec<usize> = training.iter().map(|i| *i as usize).collect();
+                let validation: Vec<usize> = validation.iter().map(|i| *i as usize).collect();
+                (
+                    select_rows(data, &training),
+                    target_mode.select(labels, &training),
+                    select_rows(data, &validation),
+                    target_mode.select(labels, &validation),
+                )
+            }
+        };
+        if validation_data.height == 0 {
+            return Err(BriqueError::Config(
+                "the trials can not be ranked without a validation dataset".to_string(),
+            ));
+        }
+
+        let trial_data: TrialData = TrialData {
+            training_data,
+            training_labels,
+            validation_data,
+            validation_labels,
+            target_mode,
+        };
+
+        let mut trials: Vec<TrialResult> = match &self.strategy {
+            SearchStrategy::Grid => {
+                self.run_trials(&self.space.grid(), self.epochs, &trial_data)?
+            }
+            SearchStrategy::Random { trials, seed } => {
+                let mut rng: StdRng = StdRng::seed_from_u64(*seed);
+                let configs: Vec<TrialConfig> =
+                    (0..*trials).map(|_| self.space.sample(&mut rng)).collect();
+                self.run_trials(&configs, self.epochs, &trial_data)?
+            }
+            SearchStrategy::SuccessiveHalving {
+                trials,
+                min_epochs,
+                reduction_factor,
+                seed,
+            } => self.successive_halving(
+                *trials,
+                *min_epochs,
+                *reduction_factor,
+                *seed,
+                &trial_data,
+            )?,
+        };
+        self.rank(&mut trials);
+
+        Ok(SearchReport {
+            objective: self.objective.clone(),
+            trials,
+        })
+    }
+
+    fn successive_halving(
+        &self,
+        trials: usize,
+        min_epochs: u32,
+        reduction_factor: usize,
+        seed: u64,
+        trial_data: &TrialData,
+    ) -> Result<Vec<TrialResult>, BriqueError> {
+        if trials == 0 || min_epochs == 0 || reduction_factor < 2 {
+            return Err(BriqueError::Config(
+                "successive halving needs at least one trial and one epoch, and a reduction factor of at least 2".to_string(),
+            ));
+        }
+
+        let mut rng: StdRng = StdRng::seed_from_u64(seed);
+        let mut candidates: Vec<TrialConfig> =
+            (0..trials).map(|_| self.space.sample(&mut rng)).collect();
+        // index of every candidate in the first rung
+        let mut candidate_ids: Vec<usize> = (0..trials).collect();
+        let mut epochs: u32 = min_epochs.min(self.epochs);
+        let mut output: Vec<TrialResult> = vec![];
+
+        loop {
+            let mut results: Vec<TrialResult> = self.run_trials(&candidates, epochs, trial_data)?;
+            for (result, id) in results.iter_mut().zip(candidate_ids.iter()) {
+                result.trial = *id;
+            }
+            self.rank(&mut results);
+
+            if results.len() == 1 || epochs >= self.epochs {
+                output.append(&mut results);
+                return Ok(output);
+            }
+
+            // the eliminated candidates keep the result of their last rung
+            let kept: usize = (results.len() / reduction_factor).max(1);
+            output.extend(results.split_off(kept));
+            candidates = results.iter().map(|r| r.config.clone()).collect();
+            candidate_ids = results.iter().map(|r| r.trial).collect();
+            epochs = epochs
+                .saturating_mul(reduction_factor as u32)
+                .min(self.epochs);
+        }
+    }
+
+    // trains the configurations on self.threads threads, the results are in the order of configs
+    fn run_trials(
+        &self,
+        configs: &[TrialConfig],
+        epochs: u32,
+        trial_data: &TrialData,
+    ) -> Result<Vec<TrialResult>, BriqueError> {
+        let next: AtomicUsize = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<Result<TrialResult, BriqueError>>>> =
+            Mutex::new((0..configs.len()).map(|_| None).collect());
+
+        thread::scope(|s| {
+            for _ in 0..self.threads.min(configs.len()) {
+                s.spawn(|| loop {
+                    let index: usize = next.fetch_add(1, Ordering::SeqCst);
+                    if index >= configs.len() {
+                        break;
+                    }
+                    let result = self.run_trial(index, &configs[index], epochs, trial_data);
+                    results.lock().unwrap()[index] = Some(result);
+                });
+            }
+        });
+
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect()
+    }
+
+    fn run_trial(
+        &self,
+        trial: usize,
+        config: &TrialConfig,
+        epochs: u32,
+        trial_data: &TrialData,
+    ) -> Result<TrialResult, BriqueError> {
+        let model: Model = config
+            .apply(&self.base, trial_data.training_data.width as u32)
+            .try_build_and_train(
+                &trial_data.training_data,
+                &trial_data.training_labels,
+                config.batch_size,
+                epochs,
+                ValidationSplit::Explicit {
+                    data: trial_data.validation_data.clone(),
+                    labels: trial_data.validation_labels.clone(),
+                },
+            )?;
+
+        let logits: Matrix = model.predict(&trial_data.validation_data);
+        let probabilities: Matrix = model.output_activation(&logits);
+        let validation_accuracy: f64 =
+            model.accuracy(&probabilities, &trial_data.validation_labels);
+        let score: f64 = match self.objective {
+            Metric::Accuracy => validation_accuracy,
+            _ => {
+                let classes: Matrix =
+                    sample_classes(&trial_data.validation_labels, &trial_data.target_mode)?;
+                self.objective.compute(&probabilities, &classes)
+            }
+        };
+
+        Ok(TrialResult {
+            trial,
+            config: config.clone(),
+            epochs,
+            validation_loss: data_loss(
+                &logits,
+                &trial_data.validation_labels,
+                None,
+                &model.loss_function,
+                &trial_data.target_mode,
+            ),
+            validation_accuracy,
+            score,
+        })
+    }
+
+    // the trials trained for the most epochs first (last rung of successive halving),
+    // then best score first, a NaN score (diverged training) is last
+    fn rank(&self, trials: &mut [TrialResult]) {
+        let higher_is_better: bool = self.objective.higher_is_better();
+        let key = |result: &TrialResult| -> f64 {
+            if result.score.is_nan() {
+                f64::INFINITY
+            } else if higher_is_better {
+                -result.score
+            } else {
+                result.score
+            }
+        };
+
+        trials.sort_by(|a, b| b.epochs.cmp(&a.epochs).then(key(a).total_cmp(&key(b))));
+    }
+}
+
+// datasets shared by the trials
+struct TrialData {
+    training_data: Matrix,
+    training_labels: Matrix,
+    validation_data: Matrix,
+    validation_labels: Matrix,
+    target_mode: TargetMode,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HyperparameterSearch, SearchSpace, SearchStrategy};
+    use crate::{
+        checkpoint::Checkpoint, error::BriqueError, matrix::Matrix, model_builder::ModelBuilder,
+        optimizer::Optimizer, validation::ValidationSplit,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::path::Path;
+
+    fn get_test_data() -> (Matrix, Matrix) {
+        let data = Matrix::init(
+            8,
+            2,
+            vec![
+                1.0, 0.0, 0.9, 0.1, 1.1, -0.1, 0.8, 0.2, -1.0, 0.0, -0.9, 0.1, -1.1, -0.1, -0.8,
+                0.2,
+            ],
+        );
+        let labels = Matrix::init(1, 8, vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]);
+
+        (data, labels)
+    }
+
+    fn get_test_space() -> SearchSpace {
+        SearchSpace::new(2)
+            .optimizers(vec![
+                Optimizer::SGD { learning_step: 0.1 },
+                Optimizer::Adam {
+                    learning_step: 0.01,
+                    beta1: 0.9,
+                    beta2: 0.999,
+                },
+            ])
+            .l2_regs(vec![0.0, 0.001])
+            .batch_sizes(vec![2])
+            .hidden_layers(vec![vec![], vec![4]])
+    }
+
+    #[test]
+    fn grid_search_leaderboard() {
+        let (data, labels) = get_test_data();
+        let report = HyperparameterSearch::init(
+            ModelBuilder::new().verbose(10, true),
+            get_test_space(),
+            SearchStrategy::Grid,
+            3,
+        )
+        .validation(ValidationSplit::Stratified { fraction: 0.25 })
+        .threads(3)
+        .run(&data, &labels)
+        .unwrap();
+
+        assert_eq!(report.trials.len(), 8);
+        for pair in report.trials.windows(2) {
+            assert!(pair[0].score >= pair[1].score);
+        }
+
+        let mut trials: Vec<usize> = report.trials.iter().map(|t| t.trial).collect();
+        trials.sort();
+        assert_eq!(trials, (0..8).collect::<Vec<usize>>());
+
+        let csv: String = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert!(lines[1].starts_with("1,"));
+        assert_eq!(lines[1].split(',').count(), lines[0].split(',').count());
+    }
+
+    #[test]
+    fn random_search_is_seedable() {
+        let space = get_test_space();
+        let configs = |seed: u64| -> Vec<super::TrialConfig> {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..5).map(|_| space.sample(&mut rng)).collect()
+        };
+
+        assert_eq!(configs(3), configs(3));
+    }
+
+    #[test]
+    fn successive_halving_rungs() {
+        let (data, labels) = get_test_data();
+        let report = HyperparameterSearch::init(
+            ModelBuilder::new().verbose(10, true),
+            get_test_space(),
+            SearchStrategy::SuccessiveHalving {
+                trials: 4,
+                min_epochs: 1,
+                reduction_factor: 2,
+                seed: 0,
+            },
+            4,
+        )
+        .validation(ValidationSplit::Fraction(0.25))
+        .run(&data, &labels)
+        .unwrap();
+
+        // 4 trials for 1 epoch, 2 for 2 epochs, 1 for 4 epochs
+        let mut epochs: Vec<u32> = report.trials.iter().map(|t| t.epochs).collect();
+        epochs.sort();
+        assert_eq!(epochs, vec![1, 1, 2, 4]);
+
+        let error = HyperparameterSearch::init(
+            ModelBuilder::new().verbose(10, true),
+            get_test_space().batch_sizes(vec![]),
+            SearchStrategy::Grid,
+            1,
+        )
+        .run(&data, &labels);
+        assert!(matches!(error, Err(BriqueError::Config(_))));
+    }
+
+    #[test]
+    fn base_builder_requirements() {
+        let (data, labels) = get_test_data();
+
+        let error = HyperparameterSearch::init(
+            ModelBuilder::new().verbose(10, true).dense(3, true),
+            get_test_space(),
+            SearchStrategy::Grid,
+            1,
+        )
+        .run(&data, &labels);
+        assert!(matches!(error, Err(BriqueError::Config(_))));
+
+        // the checkpoint of the base builder is not shared by the trials
+        let save_path: String = "test_search_checkpoint".to_string();
+        HyperparameterSearch::init(
+            ModelBuilder::new()
+                .verbose(10, true)
+                .checkpoint(Checkpoint::ValLoss {
+                    save_path: save_path.clone(),
+                }),
+            get_test_space(),
+            SearchStrategy::Grid,
+            2,
+        )
+        .validation(ValidationSplit::Fraction(0.25))
+        .threads(2)
+        .run(&data, &labels)
+        .unwrap();
+        assert!(!Path::new(&(save_path + ".brq")).exists());
+    }
+}
@@ -0,0 +1,342 @@
+use crate::error::BriqueError;
+use crate::matrix::Matrix;
+use std::fs::{read, write};
+
+// the idx format, used by MNIST, Fashion-MNIST and EMNIST :
+//  - 2 zero bytes
+//  - 1 byte, the type of the values
+//  - 1 byte, the number of dimensions
+//  - 4 bytes per dimension, big endian u32 sizes
+//  - the values, big endian, the last dimension changes the fastest
+// check out the documentation : http://yann.lecun.com/exdb/mnist/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IdxType {
+    U8,
+    I8,
+    I16,
+    I32,
+    F32,
+    F64,
+}
+
+impl IdxType {
+    pub fn code(&self) -> u8 {
+        match self {
+            IdxType::U8 => 0x08,
+            IdxType::I8 => 0x09,
+            IdxType::I16 => 0x0B,
+            IdxType::I32 => 0x0C,
+            IdxType::F32 => 0x0D,
+            IdxType::F64 => 0x0E,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Result<IdxType, BriqueError> {
+        match code {
+            0x08 => Ok(IdxType::U8),
+            0x09 => Ok(IdxType::I8),
+            0x0B => Ok(IdxType::I16),
+            0x0C => Ok(IdxType::I32),
+            0x0D => Ok(IdxType::F32),
+            0x0E => Ok(IdxType::F64),
+            _ => Err(BriqueError::Format(format!(
+                "unknown idx data type {:#04x}",
+                code
+            ))),
+        }
+    }
+
+    // number of bytes of a value
+    pub fn size(&self) -> usize {
+        match self {
+            IdxType::U8 | IdxType::I8 => 1,
+            IdxType::I16 => 2,
+            IdxType::I32 | IdxType::F32 => 4,
+            IdxType::F64 => 8,
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> f64 {
+        match self {
+            IdxType::U8 => bytes[0] as f64,
+            IdxType::I8 => bytes[0] as i8 as f64,
+            IdxType::I16 => i16::from_be_bytes([bytes[0], bytes[1]]) as f64,
+            IdxType::I32 => i32::from_be_bytes(bytes.try_into().unwrap()) as f64,
+            IdxType::F32 => f32::from_be_bytes(bytes.try_into().unwrap()) as f64,
+            IdxType::F64 => f64::from_be_bytes(bytes.try_into().unwrap()),
+        }
+    }
+
+    // the integer types only accept integers in their range
+    fn encode(&self, value: f64, output: &mut Vec<u8>) -> Result<(), BriqueError> {
+        let (min, max): (f64, f64) = match self {
+            IdxType::U8 => (u8::MIN as f64, u8::MAX as f64),
+            IdxType::I8 => (i8::MIN as f64, i8::MAX as f64),
+            IdxType::I16 => (i16::MIN as f64, i16::MAX as f64),
+            IdxType::I32 => (i32::MIN as f64, i32::MAX as f64),
+            IdxType::F32 | IdxType::F64 => (f64::NEG_INFINITY, f64::INFINITY),
+        };
+        let is_float: bool = matches!(self, IdxType::F32 | IdxType::F64);
+        if !is_float && (value.fract() != 0.0 || value < min || value > max) {
+            return Err(BriqueError::Config(format!(
+                "the value {} can not be stored as {:?}",
+                value, self
+            )));
+        }
+
+        match self {
+            IdxType::U8 => output.push(value as u8),
+            IdxType::I8 => output.extend_from_slice(&(value as i8).to_be_bytes()),
+            IdxType::I16 => output.extend_from_slice(&(value as i16).to_be_bytes()),
+            IdxType::I32 => output.extend_from_slice(&(value as i32).to_be_bytes()),
+            IdxType::F32 => output.extend_from_slice(&(value as f32).to_be_bytes()),
+            IdxType::F64 => output.extend_from_slice(&value.to_be_bytes()),
+        }
+
+        Ok(())
+    }
+}
+
+// content of an idx file, the values are converted to f64
+#[derive(Clone, Debug, PartialEq)]
+pub struct IdxArray {
+    pub data_type: IdxType,
+    pub dimensions: Vec<usize>,
+    pub data: Vec<f64>,
+}
+
+impl IdxArray {
+    // the first dimension is the number of samples
+    // one dimension (labels) : 1 x N matrix
+    // more dimensions : N x (product of the other dimensions) matrix, one flattened sample per row
+    pub fn to_matrix(&self) -> Matrix {
+        match self.dimensions.len() {
+            0 => Matrix::init_zero(1, 0),
+            1 => Matrix::init(1, self.dimensions[0], self.data.clone()),
+            _ => Matrix::init(
+                self.dimensions[0],
+                self.dimensions[1..].iter().product(),
+                self.data.clone(),
+            ),
+        }
+    }
+
+    // a 1 x N matrix is saved with one dimension, a N x W matrix with two
+    pub fn from_matrix(matrix: &Matrix, data_type: IdxType) -> IdxArray {
+        let dimensions: Vec<usize> = if matrix.height == 1 {
+            vec![matrix.width]
+        } else {
+            vec![matrix.height, matrix.width]
+        };
+
+        IdxArray {
+            data_type,
+            dimensions,
+            data: (0..matrix.height).flat_map(|r| matrix.get_row(r)).collect(),
+        }
+    }
+
+    // same values with other dimensions, e.g. [N, 28, 28] for flattened images
+    pub fn reshape(mut self, dimensions: Vec<usize>) -> Result<IdxArray, BriqueError> {
+        if dimensions.iter().product::<usize>() != self.data.len() {
+            return Err(BriqueError::Shape(format!(
+                "{} values can not have the dimensions {:?}",
+                self.data.len(),
+                dimensions
+            )));
+        }
+        self.dimensions = dimensions;
+
+        Ok(self)
+    }
+}
+
+pub fn read_idx(path: &str) -> Result<IdxArray, BriqueError> {
+    decode_idx(&read(path)?)
+}
+
+// the matrix of read_idx, see IdxArray::to_matrix
+pub fn read_idx_matrix(path: &str) -> Result<Matrix, BriqueError> {
+    Ok(read_idx(path)?.to_matrix())
+}
+
+pub fn write_idx(path: &str, array: &IdxArray) -> Result<(), BriqueError> {
+    write(path, encode_idx(array)?)?;
+
+    Ok(())
+}
+
+pub fn decode_idx(bytes: &[u8]) -> Result<IdxArray, BriqueError> {
+    if bytes.len() < 4 || bytes[0] != 0 || bytes[1] != 0 {
+        return Err(BriqueError::Format(
+            "the file does not start with an idx header".to_string(),
+        ));
+    }
+
+    let data_type: IdxType = IdxType::from_code(bytes[2])?;
+    let number_of_dimensions: usize = bytes[3] as usize;
+    let header_size: usize = 4 + 4 * number_of_dimensions;
+    if bytes.len() < header_size {
+        return Err(BriqueError::Format(format!(
+            "the idx header announces {} dimensions but the file is only {} bytes long",
+            number_of_dimensions,
+            bytes.len()
+        )));
+    }
+
+    let dimensions: Vec<usize> = bytes[4..header_size]
+        .chunks(4)
+        .map(|size| u32::from_be_bytes(size.try_into().unwrap()) as usize)
+        .collect();
+    // the sizes come from the file, a corrupted header must not overflow
+    let expected_size: usize = match dimensions
+        .iter()
+        .try_fold(data_type.size(), |size, dimension| {
+            size.checked_mul(*dimension)
+        }) {
+        Some(size) => size,
+        None => {
+            return Err(BriqueError::Format(format!(
+                "the idx dimensions {:?} are too large",
+                dimensions
+            )))
+        }
+    };
+    if bytes.len() - header_size != expected_size {
+        return Err(BriqueError::Format(format!(
+            "the idx dimensions {:?} need {} bytes of {:?} values, found {}",
+            dimensions,
+            expected_size,
+            data_type,
+            bytes.len() - header_size
+        )));
+    }
+
+    let data: Vec<f64> = bytes[header_size..]
+        .chunks(data_type.size())
+        .map(|value| data_type.decode(value))
+        .collect();
+
+    Ok(IdxArray {
+        data_type,
+        dimensions,
+        data,
+    })
+}
+
+pub fn encode_idx(array: &IdxArray) -> Result<Vec<u8>, BriqueError> {
+    if array.dimensions.iter().product::<usize>() != array.data.len() {
+        return Err(BriqueError::Shape(format!(
+            "{} values do not match the dimensions {:?}",
+            array.data.len(),
+            array.dimensions
+        )));
+    }
+    if array.dimensions.len() > u8::MAX as usize
+        || array.dimensions.iter().any(|d| *d > u32::MAX as usize)
+    {
+        return Err(BriqueError::Shape(format!(
+            "the dimensions {:?} can not be stored in an idx header",
+            array.dimensions
+        )));
+    }
+
+    let mut output: Vec<u8> = vec![0, 0, array.data_type.code(), array.dimensions.len() as u8];
+    for dimension in &array.dimensions {
+        output.extend_from_slice(&(*dimension as u32).to_be_bytes());
+    }
+    for value in &array.data {
+        array.data_type.encode(*value, &mut output)?;
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_idx, encode_idx, read_idx, write_idx, IdxArray, IdxType};
+    use crate::{error::BriqueError, matrix::Matrix};
+
+    #[test]
+    fn every_type_round_trip() {
+        let data: Vec<f64> = vec![0.0, 1.0, -2.0, 3.0, 100.0, -100.0];
+        for data_type in [
+            IdxType::I8,
+            IdxType::I16,
+            IdxType::I32,
+            IdxType::F32,
+            IdxType::F64,
+        ] {
+            let array = IdxArray {
+                data_type,
+                dimensions: vec![1, 2, 3],
+                data: data.clone(),
+            };
+            let bytes = encode_idx(&array).unwrap();
+
+            assert_eq!(bytes.len(), 4 + 3 * 4 + 6 * data_type.size());
+            assert_eq!(decode_idx(&bytes).unwrap(), array);
+        }
+
+        let array = IdxArray {
+            data_type: IdxType::U8,
+            dimensions: vec![2],
+            data: vec![-1.0, 2.0],
+        };
+        assert!(matches!(encode_idx(&array), Err(BriqueError::Config(_))));
+    }
+
+    #[test]
+    fn mnist_style_file() {
+        // 2 images of 2x2 pixels
+        let bytes: Vec<u8> = vec![
+            0, 0, 0x08, 3, 0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0, 2, 1, 2, 3, 4, 5, 6, 7, 255,
+        ];
+        let array = decode_idx(&bytes).unwrap();
+
+        assert_eq!(array.dimensions, vec![2, 2, 2]);
+        assert!(array.to_matrix().is_equal(
+            &Matrix::init(2, 4, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 255.0]),
+            15
+        ));
+
+        // truncated data, unknown type
+        assert!(matches!(
+            decode_idx(&bytes[..bytes.len() - 1]),
+            Err(BriqueError::Format(_))
+        ));
+        // 5 dimensions of u32::MAX values, the size overflows
+        let mut huge_dimensions: Vec<u8> = vec![0, 0, 0x08, 5];
+        huge_dimensions.extend([255; 20]);
+        assert!(matches!(
+            decode_idx(&huge_dimensions),
+            Err(BriqueError::Format(_))
+        ));
+        let mut unknown_type = bytes.clone();
+        unknown_type[2] = 0x0A;
+        assert!(matches!(
+            decode_idx(&unknown_type),
+            Err(BriqueError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn write_and_read_file() {
+        let file_path: String = std::env::temp_dir()
+            .join("brique_idx_round_trip")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let matrix = Matrix::init(2, 4, vec![0.5, 1.5, 2.5, 3.5, 4.5, 5.5, 6.5, 7.5]);
+        let array = IdxArray::from_matrix(&matrix, IdxType::F64)
+            .reshape(vec![2, 2, 2])
+            .unwrap();
+
+        write_idx(&file_path, &array).unwrap();
+        let read = read_idx(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+
+        assert_eq!(read.dimensions, vec![2, 2, 2]);
+        assert!(read.to_matrix().is_equal(&matrix, 15));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::matrix::Matrix;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use std::{fmt, sync::Arc};
+
+// user supplied initialization, receives the height, the width and the rng
+// of the model builder so the initialization stays seedable
+pub type InitializerFn = Arc<dyn Fn(usize, usize, &mut StdRng) -> Matrix + Send + Sync>;
+
+// weight initialization strategies
+// the fan in is the height of the matrix (number of inputs of the layer)
+// and the fan out its width (number of neurons)
+#[derive(Clone)]
+pub enum Initializer {
+    // normal, std dev = sqrt(2 / fan_in), used by Layer::init
+    HeNormal,
+    // uniform in [-sqrt(6 / fan_in), sqrt(6 / fan_in)]
+    HeUniform,
+    // normal, std dev = sqrt(2 / (fan_in + fan_out))
+    XavierNormal,
+    // uniform in [-sqrt(6 / (fan_in + fan_out)), sqrt(6 / (fan_in + fan_out))]
+    XavierUniform,
+    // normal, std dev = sqrt(1 / fan_in)
+    LeCunNormal,
+    // uniform in [-sqrt(3 / fan_in), sqrt(3 / fan_in)]
+    LeCunUniform,
+    // orthonormal rows or columns (whichever are fewer) scaled by gain
+    Orthogonal { gain: f64 },
+    Constant(f64),
+    Custom(InitializerFn),
+}
+
+impl fmt::Debug for Initializer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Initializer::HeNormal => write!(f, "HeNormal"),
+            Initializer::HeUniform => write!(f, "HeUniform"),
+            Initializer::XavierNormal => write!(f, "XavierNormal"),
+            Initializer::XavierUniform => write!(f, "XavierUniform"),
+            Initializer::LeCunNormal => write!(f, "LeCunNormal"),
+            Initializer::LeCunUniform => write!(f, "LeCunUniform"),
+            Initializer::Orthogonal { gain } => write!(f, "Orthogonal {{ gain: {} }}", gain),
+            Initializer::Constant(value) => write!(f, "Constant({})", value),
+            Initializer::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl Initializer {
+    pub fn init_matrix(&self, height: usize, width: usize, rng: &mut StdRng) -> Matrix {
+        let fan_in: f64 = height as f64;
+        let fan_out: f64 = width as f64;
+
+        match self {
+            Initializer::HeNormal => normal(height, width, (2.0 / fan_in).sqrt(), rng),
+            Initializer::HeUniform => uniform(height, width, (6.0 / fan_in).sqrt(), rng),
+            Initializer::XavierNormal => {
+                normal(height, width, (2.0 / (fan_in + fan_out)).sqrt(), rng)
+            }
+            Initializer::XavierUniform => {
+                uniform(height, width, (6.0 / (fan_in + fan_out)).sqrt(), rng)
+            }
+            Initializer::LeCunNormal => normal(height, width, (1.0 / fan_in).sqrt(), rng),
+            Initializer::LeCunUniform => uniform(height, width, (3.0 / fan_in).sqrt(), rng),
+            Initializer::Orthogonal { gain } => orthogonal(height, width, *gain, rng),
+            Initializer::Constant(value) => {
+                Matrix::init(height, width, vec![*value; height * width])
+            }
+            Initializer::Custom(init_fn) => {
+                let output: Matrix = init_fn(height, width, rng);
+                assert!(
+                    output.height == height && output.width == width,
+                    "Custom initializer returned a {}x{} matrix, expected {}x{}",
+                    output.height,
+                    output.width,
+                    height,
+                    width
+                );
+                output
+            }
+        }
+    }
+}
+
+fn normal(height: usize, width: usize, std_dev: f64, rng: &mut StdRng) -> Matrix {
+    let normal = Normal::new(0.0, std_dev).unwrap();
+    let data: Vec<f64> = (0..height * width).map(|_| normal.sample(rng)).collect();
+
+    Matrix::init(height, width, data)
+}
+
+fn uniform(height: usize, width: usize, limit: f64, rng: &mut StdRng) -> Matrix {
+    let data: Vec<f64> = (0..height * width)
+        .map(|_| (rng.random::<f64>() * 2.0 - 1.0) * limit)
+        .collect();
+
+    Matrix::init(height, width, data)
+}
+
+// modified Gram-Schmidt on a random normal matrix
+fn orthogonal(height: usize, width: usize, gain: f64, rng: &mut StdRng) -> Matrix {
+    // working on the shortest dimension, vectors are stored as the rows of tmp
+    let (number_of_vectors, length): (usize, usize) = if height < width {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let tmp: Matrix = normal(number_of_vectors, length, 1.0, rng);
+    let mut vectors: Vec<Vec<f64>> = (0..number_of_vectors).map(|r| tmp.get_row(r)).collect();
+
+    for i in 0..number_of_vectors {
+        for j in 0..i {
+            let projection: f64 = dot(&vectors[i], &vectors[j]);
+            let previous: Vec<f64> = vectors[j].clone();
+            vectors[i]
+                .iter_mut()
+                .zip(previous.iter())
+                .for_each(|(v, p)| *v -= projection * p);
+        }
+
+        let norm: f64 = dot(&vectors[i], &vectors[i]).sqrt();
+        vectors[i].iter_mut().for_each(|v| *v /= norm);
+    }
+
+    let mut output: Matrix = Matrix::init_zero(height, width);
+    for (i, vector) in vectors.iter().enumerate() {
+        for (j, value) in vector.iter().enumerate() {
+            if height < width {
+                output.set(value * gain, i, j);
+            } else {
+                output.set(value * gain, j, i);
+            }
+        }
+    }
+
+    output
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Initializer;
+    use crate::matrix::Matrix;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::sync::Arc;
+
+    #[test]
+    fn same_seed_same_weights() {
+        let a = Initializer::XavierNormal.init_matrix(10, 5, &mut StdRng::seed_from_u64(42));
+        let b = Initializer::XavierNormal.init_matrix(10, 5, &mut StdRng::seed_from_u64(42));
+        let c = Initializer::XavierNormal.init_matrix(10, 5, &mut StdRng::seed_from_u64(43));
+
+        assert!(a.is_equal(&b, 15));
+        assert!(!a.is_equal(&c, 15));
+    }
+
+    #[test]
+    fn uniform_initializers_stay_in_bounds() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let limit: f64 = (6.0 / (20.0 + 30.0_f64)).sqrt();
+        let weights = Initializer::XavierUniform.init_matrix(20, 30, &mut rng);
+
+        assert!(weights.max() <= limit && weights.min() >= -limit);
+
+        let limit: f64 = (6.0 / 20.0_f64).sqrt();
+        let weights = Initializer::HeUniform.init_matrix(20, 30, &mut rng);
+
+        assert!(weights.max() <= limit && weights.min() >= -limit);
+    }
+
+    #[test]
+    fn constant_initializer() {
+        let biases = Initializer::Constant(0.1).init_matrix(1, 4, &mut StdRng::seed_from_u64(0));
+
+        assert!(biases.is_equal(&Matrix::init(1, 4, vec![0.1; 4]), 15));
+    }
+
+    #[test]
+    fn orthogonal_initializer() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        // more rows than columns : orthonormal columns, W^T * W = I
+        let tall = Initializer::Orthogonal { gain: 1.0 }.init_matrix(6, 3, &mut rng);
+        let mut identity = Matrix::init_zero(3, 3);
+        (0..3).for_each(|i| identity.set(1.0, i, i));
+        assert!(tall.t().dot(&tall).is_equal(&identity, 10));
+
+        // more columns than rows : orthonormal rows, W * W^T = 2 * I
+        let wide = Initializer::Orthogonal {
+            gain: 2.0_f64.sqrt(),
+        }
+        .init_matrix(3, 6, &mut rng);
+        assert!(wide.dot(&wide.t()).is_equal(&identity.mult(2.0), 10));
+    }
+
+    #[test]
+    fn custom_initializer() {
+        let initializer = Initializer::Custom(Arc::new(|height, width, _| {
+            Matrix::init_zero(height, width)
+        }));
+
+        let weights = initializer.init_matrix(2, 3, &mut StdRng::seed_from_u64(0));
+
+        assert!(weights.is_equal(&Matrix::init_zero(2, 3), 15));
+    }
+}
@@ -1,8 +1,51 @@
 use crate::activation::*;
+use crate::initializer::Initializer;
 use crate::matrix::*;
 use crate::optimizer::Optimizer;
+use rand::rngs::StdRng;
 
 const EPSILON: f64 = 10E-8;
+
+// gradients of the loss relative to the parameters and the output of a layer
+#[derive(Clone)]
+pub struct LayerGradients {
+    pub d_weights: Matrix,
+    pub d_biases: Matrix,
+    pub d_output: Matrix,
+}
+
+impl LayerGradients {
+    // sum of the squared weights and biases gradients
+    pub fn squared_norm(&self) -> f64 {
+        self.d_weights.pow(2).sum() + self.d_biases.pow(2).sum()
+    }
+
+    pub fn clip_by_value(&mut self, max_value: f64) {
+        assert!(
+            max_value > 0.0,
+            "Error : the clipping value must be strictly positive, got {}",
+            max_value
+        );
+        self.d_weights.data = self
+            .d_weights
+            .data
+            .iter()
+            .map(|v| v.clamp(-max_value, max_value))
+            .collect();
+        self.d_biases.data = self
+            .d_biases
+            .data
+            .iter()
+            .map(|v| v.clamp(-max_value, max_value))
+            .collect();
+    }
+
+    pub fn scale(&mut self, factor: f64) {
+        self.d_weights.mult_inplace(factor);
+        self.d_biases.mult_inplace(factor);
+    }
+}
+
 // note : we have directly the transpose of weights (hence the _t)
 // height -> number of inputs
 // width -> number of neurons in the layer
@@ -12,6 +55,9 @@ pub struct Layer {
     pub biases: Matrix,
     pub relu: bool,
     pub output: Matrix,
+    // frozen layers still propagate the gradients but their parameters
+    // and optimizer state are not updated
+    pub trainable: bool,
 
     // for adam optimizer
     pub first_moment_weight: Option<Matrix>,
@@ -27,6 +73,7 @@ impl Layer {
             biases: Matrix::init_zero(1, size.try_into().unwrap()),
             relu,
             output: Matrix::init_zero(0, 0),
+            trainable: true,
             first_moment_weight: None,
             first_moment_biase: None,
             second_moment_weight: None,
@@ -34,12 +81,28 @@ impl Layer {
         }
     }
 
+    pub fn init_with_initializer(
+        input_size: u32,
+        size: u32,
+        relu: bool,
+        weights_initializer: &Initializer,
+        biases_initializer: &Initializer,
+        rng: &mut StdRng,
+    ) -> Layer {
+        let weights_t: Matrix =
+            weights_initializer.init_matrix(input_size as usize, size as usize, rng);
+        let biases: Matrix = biases_initializer.init_matrix(1, size as usize, rng);
+
+        Layer::init_with_data(weights_t, biases, relu)
+    }
+
     pub fn init_with_data(weights_t: Matrix, biases: Matrix, relu: bool) -> Layer {
         Layer {
             weights_t,
             biases,
             relu,
             output: Matrix::init_zero(0, 0),
+            trainable: true,
             first_moment_weight: None,
             first_moment_biase: None,
             second_moment_weight: None,
@@ -54,6 +117,7 @@ impl Layer {
             biases: Matrix::init_zero(1, size.try_into().unwrap()),
             relu,
             output: Matrix::init_zero(0, 0),
+            trainable: true,
             first_moment_weight: None,
             first_moment_biase: None,
             second_moment_weight: None,
@@ -62,12 +126,7 @@ impl Layer {
     }
 
     pub fn forward(&mut self, input: &Matrix, predict: bool) -> Matrix {
-        let mut tmp_output = input.dot(&self.weights_t);
-        tmp_output = tmp_output.add_1d_matrix_to_all_rows(&self.biases);
-
-        if self.relu {
-            tmp_output = self.relu(&tmp_output);
-        }
+        let tmp_output = self.predict(input);
 
         if !predict {
             self.output = tmp_output.clone();
@@ -76,6 +135,18 @@ impl Layer {
         tmp_output
     }
 
+    // same as forward without caching the output, usable through a shared reference
+    pub fn predict(&self, input: &Matrix) -> Matrix {
+        let mut output = input.dot(&self.weights_t);
+        output = output.add_1d_matrix_to_all_rows(&self.biases);
+
+        if self.relu {
+            output = self.relu(&output);
+        }
+
+        output
+    }
+
     // d_w(i) = input(i) * d_output(i)
     // can be rewritten
     // d_w(i) = output(i-1) * d_output(i) -> if i > 0
@@ -95,11 +166,8 @@ impl Layer {
         debug_array_d_biaises: &mut Option<Vec<Matrix>>,
         debug_array_d_outputs: &mut Option<Vec<Matrix>>,
     ) -> Matrix {
-        let d_w: Matrix = z_minus_1
-            .t()
-            .dot(d_z)
-            .add_two_matrices(&self.weights_t.mult(lambda));
-        let d_b: Matrix = d_z.sum_rows();
+        let (d_w, d_b, new_d_z) =
+            self.gradients(d_z, z_minus_1, previous_layer_relu, lambda, is_input_layer);
 
         if debug {
             debug_array_d_outputs
@@ -113,19 +181,38 @@ impl Layer {
                 .push(d_b.clone());
         }
 
-        let mut new_d_z = d_z.dot(&self.weights_t.t());
-        if !is_input_layer {
-            if previous_layer_relu {
-                new_d_z.compute_d_relu_inplace(z_minus_1);
-            }
+        if self.trainable {
+            self.update_weigths(d_w, optimizer, iteration);
+            self.update_biases(d_b, optimizer, iteration);
         }
 
-        self.update_weigths(d_w, optimizer, iteration);
-        self.update_biases(d_b, optimizer, iteration);
-
         new_d_z
     }
 
+    // same computation as backprop without updating the parameters
+    // returns (d_w, d_b, d_output of the previous layer)
+    pub fn gradients(
+        &self,
+        d_z: &Matrix,
+        z_minus_1: &Matrix,
+        previous_layer_relu: bool,
+        lambda: f64,
+        is_input_layer: bool,
+    ) -> (Matrix, Matrix, Matrix) {
+        let d_w: Matrix = z_minus_1
+            .t()
+            .dot(d_z)
+            .add_two_matrices(&self.weights_t.mult(lambda));
+        let d_b: Matrix = d_z.sum_rows();
+
+        let mut new_d_z = d_z.dot(&self.weights_t.t());
+        if !is_input_layer && previous_layer_relu {
+            new_d_z.compute_d_relu_inplace(z_minus_1);
+        }
+
+        (d_w, d_b, new_d_z)
+    }
+
     //implementing ReLu for this project
     fn relu(&self, input: &Matrix) -> Matrix {
         let mut output: Matrix = Matrix::init_zero(input.height, input.width);
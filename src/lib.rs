@@ -1,13 +1,31 @@
 pub mod activation;
+pub mod autograd;
+pub mod batch_predictor;
 pub mod benchmark;
 pub mod checkpoint;
+pub mod cifar;
+pub mod cross_validation;
+pub mod csv_dataset;
+pub mod dataset;
+pub mod error;
+pub mod gradient_check;
+pub mod history;
+pub mod hyperparameter_search;
+pub mod idx;
+pub mod initializer;
 pub mod layers;
 pub mod loss;
 pub mod matrix;
+pub mod metrics;
 pub mod model;
 pub mod model_builder;
+pub mod numerical_guard;
 pub mod optimizer;
 pub mod parse_test_csv;
+pub mod preprocessing;
 pub mod save_load;
 pub mod spiral;
+pub mod summary;
+pub mod synthetic;
 pub mod utils;
+pub mod validation;
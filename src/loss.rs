@@ -1,6 +1,56 @@
+use crate::activation::{log_sigmoid_scalar, log_softmax};
 use crate::layers::*;
 use crate::matrix::*;
 
+// variants of the classification loss, the default is the plain cross entropy
+#[derive(Clone, Debug, Default)]
+pub struct ClassificationLoss {
+    // one weight per class, multiplies the loss of the samples of that class
+    pub class_weights: Option<Vec<f64>>,
+    // focal loss : -(1 - p)^gamma * log(p), down-weights the well classified samples
+    pub focal_gamma: Option<f64>,
+    // the targets become (1 - label_smoothing) * one_hot + label_smoothing / number_of_classes
+    pub label_smoothing: f64,
+}
+
+// format of the labels used for the training
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum TargetMode {
+    // 1 x N matrix of class indexes, softmax outputs
+    #[default]
+    ClassIndex,
+    // N x C matrix, every row is a probability distribution over the classes
+    // (knowledge distillation, mixup), softmax outputs
+    Probabilities,
+    // N x C matrix of 0 and 1, the outputs are independent sigmoids
+    // a class is predicted when its probability is >= its threshold
+    MultiLabel {
+        thresholds: Vec<f64>,
+    },
+}
+
+impl TargetMode {
+    // labels of the samples at the given indexes, in the same format
+    pub fn select(&self, labels: &Matrix, indexes: &[usize]) -> Matrix {
+        match self {
+            TargetMode::ClassIndex => {
+                let mut output: Matrix = Matrix::init_zero(1, indexes.len());
+                for (i, index) in indexes.iter().enumerate() {
+                    output.set(labels.get(0, *index), 0, i);
+                }
+                output
+            }
+            TargetMode::Probabilities | TargetMode::MultiLabel { .. } => {
+                let mut output: Matrix = Matrix::init_zero(indexes.len(), labels.width);
+                for (i, index) in indexes.iter().enumerate() {
+                    output.set_row(&labels.get_row(*index), i);
+                }
+                output
+            }
+        }
+    }
+}
+
 pub fn one_hot_encoding(input: &Matrix, labels: &Matrix) -> Matrix {
     assert_eq!(
         input.height, labels.width,
@@ -28,6 +78,301 @@ pub fn cross_entropy(output: &Matrix, labels: &Matrix) -> f64 {
     output_loss
 }
 
+// mean of -log_probabilities[label], the input is the output of log_softmax
+pub fn negative_log_likelihood(log_probabilities: &Matrix, labels: &Matrix) -> f64 {
+    let log_probabilities_one_hot: Matrix = one_hot_encoding(log_probabilities, labels);
+
+    -log_probabilities_one_hot.sum() / log_probabilities_one_hot.width as f64
+}
+
+// fused log softmax and negative log likelihood, numerically stable
+// version of cross_entropy(&softmax(logits), labels)
+pub fn cross_entropy_from_logits(logits: &Matrix, labels: &Matrix) -> f64 {
+    negative_log_likelihood(&log_softmax(logits), labels)
+}
+
+// labels as a 1 x N matrix of class indexes -> N x number_of_classes one hot matrix
+pub fn one_hot_targets(labels: &Matrix, number_of_classes: usize) -> Matrix {
+    let mut output: Matrix = Matrix::init_zero(labels.width, number_of_classes);
+    for c in 0..labels.width {
+        output.set(1.0, c, labels.get(0, c) as usize);
+    }
+
+    output
+}
+
+// weight of every sample : sample weight * weight of its class
+// with soft targets the class weight is the mean of the class weights weighted by the targets
+fn samples_weight(
+    targets: &Matrix,
+    sample_weights: Option<&Matrix>,
+    loss: &ClassificationLoss,
+) -> Vec<f64> {
+    (0..targets.height)
+        .map(|r| {
+            let class_weight: f64 = match &loss.class_weights {
+                Some(class_weights) => {
+                    assert_eq!(
+                        class_weights.len(),
+                        targets.width,
+                        "There should be one class weight per class"
+                    );
+                    (0..targets.width)
+                        .map(|c| class_weights[c] * targets.get(r, c))
+                        .sum()
+                }
+                None => 1.0,
+            };
+
+            sample_weight(sample_weights, r) * class_weight
+        })
+        .collect()
+}
+
+fn sample_weight(sample_weights: Option<&Matrix>, index: usize) -> f64 {
+    match sample_weights {
+        Some(weights) => weights.get(0, index),
+        None => 1.0,
+    }
+}
+
+fn smooth(targets: &Matrix, label_smoothing: f64, number_of_values: usize) -> Matrix {
+    let mut output: Matrix = targets.mult(1.0 - label_smoothing);
+    output.add_inplace(label_smoothing / number_of_values as f64);
+
+    output
+}
+
+// mean over the samples of the weighted loss
+// labels as a 1 x N matrix of class indexes
+pub fn classification_loss(
+    logits: &Matrix,
+    labels: &Matrix,
+    sample_weights: Option<&Matrix>,
+    loss: &ClassificationLoss,
+) -> f64 {
+    soft_classification_loss(
+        logits,
+        &one_hot_targets(labels, logits.width),
+        sample_weights,
+        loss,
+    )
+}
+
+pub fn d_classification_loss(
+    logits: &Matrix,
+    labels: &Matrix,
+    sample_weights: Option<&Matrix>,
+    loss: &ClassificationLoss,
+) -> Matrix {
+    d_soft_classification_loss(
+        logits,
+        &one_hot_targets(labels, logits.width),
+        sample_weights,
+        loss,
+    )
+}
+
+// same as classification_loss with N x C targets, every row being a probability distribution
+pub fn soft_classification_loss(
+    logits: &Matrix,
+    targets: &Matrix,
+    sample_weights: Option<&Matrix>,
+    loss: &ClassificationLoss,
+) -> f64 {
+    let weights: Vec<f64> = samples_weight(targets, sample_weights, loss);
+    let targets: Matrix = smooth(targets, loss.label_smoothing, logits.width);
+    let log_probabilities: Matrix = log_softmax(logits);
+    let gamma: f64 = loss.focal_gamma.unwrap_or(0.0);
+
+    let mut output: f64 = 0.0;
+    for (r, weight) in weights.iter().enumerate() {
+        let mut sample_loss: f64 = 0.0;
+        for c in 0..logits.width {
+            let q: f64 = targets.get(r, c);
+            if q == 0.0 {
+                continue;
+            }
+            let log_p: f64 = log_probabilities.get(r, c);
+            sample_loss -= q * (1.0 - log_p.exp()).powf(gamma) * log_p;
+        }
+        output += weight * sample_loss;
+    }
+
+    output / logits.height as f64
+}
+
+// gradient of the loss summed over the samples relative to the logits
+// cross entropy : p - q
+// focal : d_logits(j) = g(j) - p(j) * sum_k g(k)
+// with g(k) = q(k) * (gamma * (1 - p(k))^(gamma - 1) * p(k) * log(p(k)) - (1 - p(k))^gamma)
+pub fn d_soft_classification_loss(
+    logits: &Matrix,
+    targets: &Matrix,
+    sample_weights: Option<&Matrix>,
+    loss: &ClassificationLoss,
+) -> Matrix {
+    let weights: Vec<f64> = samples_weight(targets, sample_weights, loss);
+    let targets: Matrix = smooth(targets, loss.label_smoothing, logits.width);
+    let log_probabilities: Matrix = log_softmax(logits);
+
+    let mut output: Matrix = Matrix::init_zero(logits.height, logits.width);
+    for (r, weight) in weights.iter().enumerate() {
+        match loss.focal_gamma {
+            None => {
+                for c in 0..logits.width {
+                    let p: f64 = log_probabilities.get(r, c).exp();
+                    output.set(weight * (p - targets.get(r, c)), r, c);
+                }
+            }
+            Some(gamma) => {
+                let g: Vec<f64> = (0..logits.width)
+                    .map(|c| {
+                        let log_p: f64 = log_probabilities.get(r, c);
+                        let p: f64 = log_p.exp();
+                        // (1 - p)^(gamma - 1) * log(p) tends to 0 when p tends to 1
+                        let log_term: f64 = if 1.0 - p == 0.0 {
+                            0.0
+                        } else {
+                            gamma * (1.0 - p).powf(gamma - 1.0) * p * log_p
+                        };
+                        targets.get(r, c) * (log_term - (1.0 - p).powf(gamma))
+                    })
+                    .collect();
+                let g_sum: f64 = g.iter().sum();
+
+                for (c, g_c) in g.iter().enumerate() {
+                    let p: f64 = log_probabilities.get(r, c).exp();
+                    output.set(weight * (g_c - p * g_sum), r, c);
+                }
+            }
+        }
+    }
+
+    output
+}
+
+// multi-label loss, every output is an independent sigmoid
+// loss of one output : -(t * (1 - p)^gamma * log(p) + (1 - t) * p^gamma * log(1 - p))
+// summed over the classes (weighted by the class weights) and averaged over the samples
+// label smoothing moves the targets towards 0.5
+pub fn binary_cross_entropy_from_logits(
+    logits: &Matrix,
+    targets: &Matrix,
+    sample_weights: Option<&Matrix>,
+    loss: &ClassificationLoss,
+) -> f64 {
+    let targets: Matrix = smooth(targets, loss.label_smoothing, 2);
+    let gamma: f64 = loss.focal_gamma.unwrap_or(0.0);
+
+    let mut output: f64 = 0.0;
+    for r in 0..logits.height {
+        let mut sample_loss: f64 = 0.0;
+        for c in 0..logits.width {
+            let t: f64 = targets.get(r, c);
+            let log_p: f64 = log_sigmoid_scalar(logits.get(r, c));
+            let log_1_minus_p: f64 = log_sigmoid_scalar(-logits.get(r, c));
+            let p: f64 = log_p.exp();
+
+            sample_loss -= class_weight(loss, c, logits.width)
+                * (t * (1.0 - p).powf(gamma) * log_p + (1.0 - t) * p.powf(gamma) * log_1_minus_p);
+        }
+        output += sample_weight(sample_weights, r) * sample_loss;
+    }
+
+    output / logits.height as f64
+}
+
+// gradient of the summed loss relative to the logits
+// cross entropy : p - t
+// focal : t * (gamma * p * (1 - p)^gamma * log(p) - (1 - p)^(gamma + 1))
+//       + (1 - t) * (p^(gamma + 1) - gamma * (1 - p) * p^gamma * log(1 - p))
+pub fn d_binary_cross_entropy_from_logits(
+    logits: &Matrix,
+    targets: &Matrix,
+    sample_weights: Option<&Matrix>,
+    loss: &ClassificationLoss,
+) -> Matrix {
+    let targets: Matrix = smooth(targets, loss.label_smoothing, 2);
+
+    let mut output: Matrix = Matrix::init_zero(logits.height, logits.width);
+    for r in 0..logits.height {
+        for c in 0..logits.width {
+            let t: f64 = targets.get(r, c);
+            let log_p: f64 = log_sigmoid_scalar(logits.get(r, c));
+            let log_1_minus_p: f64 = log_sigmoid_scalar(-logits.get(r, c));
+            let p: f64 = log_p.exp();
+            let q: f64 = log_1_minus_p.exp();
+
+            let gradient: f64 = match loss.focal_gamma {
+                None => p - t,
+                Some(gamma) => {
+                    t * (gamma * p * q.powf(gamma) * log_p - q.powf(gamma + 1.0))
+                        + (1.0 - t)
+                            * (p.powf(gamma + 1.0) - gamma * q * p.powf(gamma) * log_1_minus_p)
+                }
+            };
+
+            output.set(
+                sample_weight(sample_weights, r) * class_weight(loss, c, logits.width) * gradient,
+                r,
+                c,
+            );
+        }
+    }
+
+    output
+}
+
+fn class_weight(loss: &ClassificationLoss, class: usize, number_of_classes: usize) -> f64 {
+    match &loss.class_weights {
+        Some(class_weights) => {
+            assert_eq!(
+                class_weights.len(),
+                number_of_classes,
+                "There should be one class weight per class"
+            );
+            class_weights[class]
+        }
+        None => 1.0,
+    }
+}
+
+// loss of the model for the given target mode, see TargetMode
+pub fn data_loss(
+    logits: &Matrix,
+    labels: &Matrix,
+    sample_weights: Option<&Matrix>,
+    loss: &ClassificationLoss,
+    target_mode: &TargetMode,
+) -> f64 {
+    match target_mode {
+        TargetMode::ClassIndex => classification_loss(logits, labels, sample_weights, loss),
+        TargetMode::Probabilities => soft_classification_loss(logits, labels, sample_weights, loss),
+        TargetMode::MultiLabel { .. } => {
+            binary_cross_entropy_from_logits(logits, labels, sample_weights, loss)
+        }
+    }
+}
+
+pub fn d_data_loss(
+    logits: &Matrix,
+    labels: &Matrix,
+    sample_weights: Option<&Matrix>,
+    loss: &ClassificationLoss,
+    target_mode: &TargetMode,
+) -> Matrix {
+    match target_mode {
+        TargetMode::ClassIndex => d_classification_loss(logits, labels, sample_weights, loss),
+        TargetMode::Probabilities => {
+            d_soft_classification_loss(logits, labels, sample_weights, loss)
+        }
+        TargetMode::MultiLabel { .. } => {
+            d_binary_cross_entropy_from_logits(logits, labels, sample_weights, loss)
+        }
+    }
+}
+
 pub fn l2_reg(layers: &Vec<Layer>, lambda: f64) -> f64 {
     let mut l2: f64 = 0.0;
 
@@ -37,3 +382,230 @@ pub fn l2_reg(layers: &Vec<Layer>, lambda: f64) -> f64 {
 
     l2
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        binary_cross_entropy_from_logits, classification_loss, cross_entropy,
+        cross_entropy_from_logits, d_binary_cross_entropy_from_logits, d_classification_loss,
+        d_soft_classification_loss, one_hot_targets, soft_classification_loss, ClassificationLoss,
+        TargetMode,
+    };
+    use crate::{activation::softmax, matrix::Matrix};
+
+    fn get_test_data() -> (Matrix, Matrix) {
+        (
+            Matrix::init(3, 3, vec![0.1, 1.3, 0.5, 2.0, 1.01, -1.0, -0.3, 0.2, 0.9]),
+            Matrix::init(1, 3, vec![1.0, 2.0, 0.0]),
+        )
+    }
+
+    // compares the analytic gradient with central finite differences
+    fn check_gradient(loss: &ClassificationLoss, sample_weights: Option<&Matrix>) {
+        let (logits, labels) = get_test_data();
+        let epsilon: f64 = 1e-6;
+        let d_logits = d_classification_loss(&logits, &labels, sample_weights, loss);
+
+        for i in 0..logits.data.len() {
+            let mut plus = logits.clone();
+            plus.data[i] += epsilon;
+            let mut minus = logits.clone();
+            minus.data[i] -= epsilon;
+
+            // the gradient is the one of the summed loss
+            let numerical: f64 = (classification_loss(&plus, &labels, sample_weights, loss)
+                - classification_loss(&minus, &labels, sample_weights, loss))
+                * logits.height as f64
+                / (2.0 * epsilon);
+
+            assert!(
+                (numerical - d_logits.data[i]).abs() < 1e-6,
+                "{:?} : gradient {} expected {}",
+                loss,
+                d_logits.data[i],
+                numerical
+            );
+        }
+    }
+
+    #[test]
+    fn default_loss_is_cross_entropy() {
+        let (logits, labels) = get_test_data();
+        let loss = ClassificationLoss::default();
+
+        assert!(
+            (classification_loss(&logits, &labels, None, &loss)
+                - cross_entropy_from_logits(&logits, &labels))
+            .abs()
+                < 1e-12
+        );
+        check_gradient(&loss, None);
+    }
+
+    #[test]
+    fn weighted_loss_gradient() {
+        let loss = ClassificationLoss {
+            class_weights: Some(vec![0.5, 2.0, 1.0]),
+            ..Default::default()
+        };
+        let sample_weights = Matrix::init(1, 3, vec![1.0, 0.2, 3.0]);
+
+        check_gradient(&loss, Some(&sample_weights));
+    }
+
+    #[test]
+    fn focal_loss_gradient() {
+        for gamma in [0.5, 2.0] {
+            let loss = ClassificationLoss {
+                focal_gamma: Some(gamma),
+                ..Default::default()
+            };
+            check_gradient(&loss, None);
+        }
+    }
+
+    #[test]
+    fn label_smoothing_gradient() {
+        let loss = ClassificationLoss {
+            label_smoothing: 0.1,
+            focal_gamma: Some(2.0),
+            class_weights: Some(vec![0.5, 2.0, 1.0]),
+        };
+
+        check_gradient(&loss, None);
+    }
+
+    // same as check_gradient for the N x C targets losses
+    fn check_targets_gradient(
+        loss_fn: fn(&Matrix, &Matrix, Option<&Matrix>, &ClassificationLoss) -> f64,
+        d_loss_fn: fn(&Matrix, &Matrix, Option<&Matrix>, &ClassificationLoss) -> Matrix,
+        targets: &Matrix,
+        loss: &ClassificationLoss,
+    ) {
+        let (logits, _) = get_test_data();
+        let epsilon: f64 = 1e-6;
+        let d_logits = d_loss_fn(&logits, targets, None, loss);
+
+        for i in 0..logits.data.len() {
+            let mut plus = logits.clone();
+            plus.data[i] += epsilon;
+            let mut minus = logits.clone();
+            minus.data[i] -= epsilon;
+
+            let numerical: f64 = (loss_fn(&plus, targets, None, loss)
+                - loss_fn(&minus, targets, None, loss))
+                * logits.height as f64
+                / (2.0 * epsilon);
+
+            assert!(
+                (numerical - d_logits.data[i]).abs() < 1e-6,
+                "{:?} : gradient {} expected {}",
+                loss,
+                d_logits.data[i],
+                numerical
+            );
+        }
+    }
+
+    #[test]
+    fn soft_targets_loss() {
+        let (logits, labels) = get_test_data();
+        let loss = ClassificationLoss::default();
+
+        // one hot targets are equivalent to the class indexes
+        assert!(
+            (soft_classification_loss(&logits, &one_hot_targets(&labels, 3), None, &loss)
+                - classification_loss(&logits, &labels, None, &loss))
+            .abs()
+                < 1e-12
+        );
+
+        let targets = Matrix::init(3, 3, vec![0.2, 0.7, 0.1, 0.0, 0.5, 0.5, 1.0, 0.0, 0.0]);
+        check_targets_gradient(
+            soft_classification_loss,
+            d_soft_classification_loss,
+            &targets,
+            &loss,
+        );
+        check_targets_gradient(
+            soft_classification_loss,
+            d_soft_classification_loss,
+            &targets,
+            &ClassificationLoss {
+                class_weights: Some(vec![0.5, 2.0, 1.0]),
+                focal_gamma: Some(2.0),
+                label_smoothing: 0.1,
+            },
+        );
+    }
+
+    #[test]
+    fn binary_cross_entropy_loss() {
+        let logits = Matrix::init(1, 2, vec![0.0, 2.0]);
+        let targets = Matrix::init(1, 2, vec![1.0, 0.0]);
+        let expected: f64 = 2.0_f64.ln() + (1.0 + 2.0_f64.exp()).ln();
+
+        assert!(
+            (binary_cross_entropy_from_logits(
+                &logits,
+                &targets,
+                None,
+                &ClassificationLoss::default()
+            ) - expected)
+                .abs()
+                < 1e-12
+        );
+    }
+
+    #[test]
+    fn binary_cross_entropy_gradient() {
+        let targets = Matrix::init(3, 3, vec![1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 0.0]);
+
+        for loss in [
+            ClassificationLoss::default(),
+            ClassificationLoss {
+                class_weights: Some(vec![0.5, 2.0, 1.0]),
+                focal_gamma: Some(2.0),
+                label_smoothing: 0.1,
+            },
+        ] {
+            check_targets_gradient(
+                binary_cross_entropy_from_logits,
+                d_binary_cross_entropy_from_logits,
+                &targets,
+                &loss,
+            );
+        }
+    }
+
+    #[test]
+    fn select_targets() {
+        let labels = Matrix::init(1, 3, vec![2.0, 0.0, 1.0]);
+        let selected = TargetMode::ClassIndex.select(&labels, &[2, 0]);
+        assert!(selected.is_equal(&Matrix::init(1, 2, vec![1.0, 2.0]), 15));
+
+        let targets = Matrix::init(3, 2, vec![0.1, 0.9, 0.5, 0.5, 1.0, 0.0]);
+        let selected = TargetMode::Probabilities.select(&targets, &[2, 0]);
+        assert!(selected.is_equal(&Matrix::init(2, 2, vec![1.0, 0.0, 0.1, 0.9]), 15));
+    }
+
+    #[test]
+    fn cross_entropy_from_logits_matches_cross_entropy() {
+        let logits = Matrix::init(2, 3, vec![0.1, 1.3, 0.5, 12.0, 1.01, -10.0]);
+        let labels = Matrix::init(1, 2, vec![1.0, 2.0]);
+
+        let expected: f64 = cross_entropy(&softmax(&logits), &labels);
+
+        assert!((cross_entropy_from_logits(&logits, &labels) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn cross_entropy_from_logits_confident_prediction() {
+        // the softmax probability of the label is rounded to 0
+        let logits = Matrix::init(1, 2, vec![1000.0, 0.0]);
+        let labels = Matrix::init(1, 1, vec![1.0]);
+
+        assert!(cross_entropy(&softmax(&logits), &labels).is_infinite());
+        assert_eq!(cross_entropy_from_logits(&logits, &labels), 1000.0);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::BriqueError;
 use rand::rng;
 use rand_distr::{Distribution, Normal};
 
@@ -40,6 +41,19 @@ impl Matrix {
         }
     }
 
+    pub fn try_init(height: usize, width: usize, data: Vec<f64>) -> Result<Matrix, BriqueError> {
+        if height * width != data.len() {
+            return Err(BriqueError::Shape(format!(
+                "can not build a {}x{} matrix with {} values",
+                height,
+                width,
+                data.len()
+            )));
+        }
+
+        Ok(Matrix::init(height, width, data))
+    }
+
     pub fn init_rand(height: usize, width: usize) -> Matrix {
         let std_dev = (2.0 / height as f64).sqrt();
         let normal = Normal::new(0.0, std_dev).unwrap();
@@ -69,6 +83,23 @@ impl Matrix {
         }
     }
 
+    pub fn try_get(&self, row: usize, column: usize) -> Result<f64, BriqueError> {
+        self.check_bounds(row, column)?;
+
+        Ok(self.get(row, column))
+    }
+
+    fn check_bounds(&self, row: usize, column: usize) -> Result<(), BriqueError> {
+        if row >= self.height || column >= self.width {
+            return Err(BriqueError::Shape(format!(
+                "index ({}, {}) out of bound for a {}x{} matrix",
+                row, column, self.height, self.width
+            )));
+        }
+
+        Ok(())
+    }
+
     // access to underlying one dimensional Vec
     pub fn get_1d(&self, index: usize) -> f64 {
         assert!(
@@ -91,6 +122,12 @@ impl Matrix {
         output
     }
 
+    pub fn try_get_row(&self, row: usize) -> Result<Vec<f64>, BriqueError> {
+        self.check_bounds(row, 0)?;
+
+        Ok(self.get_row(row))
+    }
+
     pub fn set(&mut self, value: f64, row: usize, column: usize) {
         assert!(row < self.height, "Error while modifying matrix data : row greater or equal to height, out of bound index");
         assert!(column < self.width, "Error while modifying matrix data : column greater or equal to width, out of bound index");
@@ -102,6 +139,13 @@ impl Matrix {
         }
     }
 
+    pub fn try_set(&mut self, value: f64, row: usize, column: usize) -> Result<(), BriqueError> {
+        self.check_bounds(row, column)?;
+        self.set(value, row, column);
+
+        Ok(())
+    }
+
     // access to underlying one dimensional Vec
     pub fn set_1d(&mut self, value: f64, index: usize) {
         assert!(
@@ -135,6 +179,17 @@ impl Matrix {
         res
     }
 
+    pub fn try_dot(&self, m: &Matrix) -> Result<Matrix, BriqueError> {
+        if self.width != m.height {
+            return Err(BriqueError::Shape(format!(
+                "dot product of a {}x{} matrix with a {}x{} matrix",
+                self.height, self.width, m.height, m.width
+            )));
+        }
+
+        Ok(self.dot(m))
+    }
+
     // adds a matrix of X width and 1 height to a matrix of Y height and X width
     pub fn add_1d_matrix_to_all_rows(&self, m: &Matrix) -> Matrix {
         assert_eq!(m.height, 1, "The input matrix should have a height of 1");
@@ -522,6 +577,29 @@ mod tests {
         matrix.set(69.69, 3, 1);
     }
 
+    #[test]
+    fn fallible_access() {
+        let mut matrix = get_test_matrix();
+        matrix.transpose_inplace();
+
+        assert_eq!(matrix.try_get(1, 1).unwrap(), 1.01);
+        assert!(matrix.try_get(3, 1).is_err());
+        assert!(matrix.try_get_row(1).is_ok());
+        assert!(matrix.try_get_row(3).is_err());
+        assert!(matrix.try_set(69.69, 0, 2).is_err());
+        assert!(matrix.try_set(69.69, 2, 1).is_ok());
+        assert_eq!(matrix.get(2, 1), 69.69);
+    }
+
+    #[test]
+    fn fallible_init_and_dot() {
+        assert!(Matrix::try_init(2, 3, vec![0.0; 5]).is_err());
+
+        let matrix = Matrix::try_init(2, 3, vec![0.0; 6]).unwrap();
+        assert!(matrix.try_dot(&matrix).is_err());
+        assert!(matrix.try_dot(&matrix.t()).is_ok());
+    }
+
     #[test]
     fn valid_set_row() {
         let mut matrix = get_test_matrix();
@@ -0,0 +1,550 @@
+use crate::matrix::Matrix;
+use crate::model::Model;
+
+// the classification metrics take the output of Model::evaluate (N x C probabilities)
+// and the labels as a 1 x N matrix of class indexes
+// the number of classes is the width of the score
+
+// averaging of the per class metrics
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Average {
+    // unweighted mean of the classes
+    Macro,
+    // computed from the counts of all the classes together
+    Micro,
+    // mean of the classes weighted by their number of samples
+    Weighted,
+}
+
+// metric monitored by Checkpoint::ValMetric
+#[derive(Clone, Debug, PartialEq)]
+pub enum Metric {
+    Accuracy,
+    TopKAccuracy { k: usize },
+    LogLoss,
+    Precision(Average),
+    Recall(Average),
+    F1(Average),
+    RocAuc(Average),
+    PrAuc(Average),
+}
+
+impl Metric {
+    pub fn compute(&self, score: &Matrix, labels: &Matrix) -> f64 {
+        match self {
+            Metric::Accuracy => accuracy(score, labels),
+            Metric::TopKAccuracy { k } => top_k_accuracy(score, labels, *k),
+            Metric::LogLoss => log_loss(score, labels),
+            Metric::Precision(average) => precision(score, labels, *average),
+            Metric::Recall(average) => recall(score, labels, *average),
+            Metric::F1(average) => f1_score(score, labels, *average),
+            Metric::RocAuc(average) => roc_auc(score, labels, *average),
+            Metric::PrAuc(average) => pr_auc(score, labels, *average),
+        }
+    }
+
+    pub fn higher_is_better(&self) -> bool {
+        !matches!(self, Metric::LogLoss)
+    }
+}
+
+// fraction of the samples whose label is the prediction of Model::evaluation_output
+pub fn accuracy(score: &Matrix, labels: &Matrix) -> f64 {
+    assert_labels_shape(score, labels);
+
+    let predictions: Matrix = Model::evaluation_output(score);
+    let correct: usize = (0..score.height)
+        .filter(|r| predictions.get(0, *r) == labels.get(0, *r))
+        .count();
+
+    correct as f64 / score.height as f64
+}
+
+// fraction of the samples whose label is among the k most probable classes
+pub fn top_k_accuracy(score: &Matrix, labels: &Matrix, k: usize) -> f64 {
+    assert_labels_shape(score, labels);
+
+    let mut correct: usize = 0;
+    for r in 0..score.height {
+        let label: usize = labels.get(0, r) as usize;
+        let label_score: f64 = score.get(r, label);
+        // number of classes ranked before the label, like Model::evaluation_output
+        // a tie goes to the class with the highest index, so k = 1 gives the accuracy
+        let rank: usize = (0..score.width)
+            .filter(|c| {
+                let class_score: f64 = score.get(r, *c);
+                class_score > label_score || (class_score == label_score && *c > label)
+            })
+            .count();
+        if rank < k {
+            correct += 1;
+        }
+    }
+
+    correct as f64 / score.height as f64
+}
+
+// mean of -ln(probability of the label), the probabilities are clipped to avoid ln(0)
+pub fn log_loss(probabilities: &Matrix, labels: &Matrix) -> f64 {
+    assert_labels_shape(probabilities, labels);
+
+    let mut loss: f64 = 0.0;
+    for r in 0..probabilities.height {
+        let p: f64 = probabilities.get(r, labels.get(0, r) as usize);
+        loss -= p.clamp(1e-15, 1.0).ln();
+    }
+
+    loss / probabilities.height as f64
+}
+
+// C x C matrix, the rows are the true classes and the columns the predicted classes
+pub fn confusion_matrix(score: &Matrix, labels: &Matrix) -> Matrix {
+    assert_labels_shape(score, labels);
+
+    let predictions: Matrix = Model::evaluation_output(score);
+    let mut output: Matrix = Matrix::init_zero(score.width, score.width);
+    for r in 0..score.height {
+        let label: usize = labels.get(0, r) as usize;
+        let prediction: usize = predictions.get(0, r) as usize;
+        output.set(output.get(label, prediction) + 1.0, label, prediction);
+    }
+
+    output
+}
+
+// a class never predicted has a precision of 0
+pub fn precision_per_class(score: &Matrix, labels: &Matrix) -> Vec<f64> {
+    let counts: ClassCounts = ClassCounts::init(score, labels);
+
+    (0..counts.true_positives.len())
+        .map(|c| ratio(counts.true_positives[c], counts.predicted[c]))
+        .collect()
+}
+
+// a class without samples has a recall of 0
+pub fn recall_per_class(score: &Matrix, labels: &Matrix) -> Vec<f64> {
+    let counts: ClassCounts = ClassCounts::init(score, labels);
+
+    (0..counts.true_positives.len())
+        .map(|c| ratio(counts.true_positives[c], counts.support[c]))
+        .collect()
+}
+
+pub fn f1_per_class(score: &Matrix, labels: &Matrix) -> Vec<f64> {
+    precision_per_class(score, labels)
+        .iter()
+        .zip(recall_per_class(score, labels).iter())
+        .map(|(p, r)| f1(*p, *r))
+        .collect()
+}
+
+// with a single label per sample the micro precision, recall and F1 are the accuracy
+pub fn precision(score: &Matrix, labels: &Matrix, average: Average) -> f64 {
+    match average {
+        Average::Micro => accuracy(score, labels),
+        _ => average_per_class(&precision_per_class(score, labels), score, labels, average),
+    }
+}
+
+pub fn recall(score: &Matrix, labels: &Matrix, average: Average) -> f64 {
+    match average {
+        Average::Micro => accuracy(score, labels),
+        _ => average_per_class(&recall_per_class(score, labels), score, labels, average),
+    }
+}
+
+pub fn f1_score(score: &Matrix, labels: &Matrix, average: Average) -> f64 {
+    match average {
+        Average::Micro => accuracy(score, labels),
+        _ => average_per_class(&f1_per_class(score, labels), score, labels, average),
+    }
+}
+
+// one-vs-rest area under the ROC curve of every class
+// NaN for a class without positive or without negative samples
+pub fn roc_auc_per_class(score: &Matrix, labels: &Matrix) -> Vec<f64> {
+    assert_labels_shape(score, labels);
+
+    (0..score.width)
+        .map(|c| {
+            let (scores, positives): (Vec<f64>, Vec<bool>) = one_vs_rest(score, labels, c);
+            binary_roc_auc(&scores, &positives)
+        })
+        .collect()
+}
+
+// one-vs-rest area under the precision-recall curve (average precision) of every class
+// NaN for a class without positive samples
+pub fn pr_auc_per_class(score: &Matrix, labels: &Matrix) -> Vec<f64> {
+    assert_labels_shape(score, labels);
+
+    (0..score.width)
+        .map(|c| {
+            let (scores, positives): (Vec<f64>, Vec<bool>) = one_vs_rest(score, labels, c);
+            average_precision(&scores, &positives)
+        })
+        .collect()
+}
+
+// the micro average flattens the one-vs-rest problems of all the classes into one
+// the classes where the metric is not defined are ignored by the macro average
+pub fn roc_auc(score: &Matrix, labels: &Matrix, average: Average) -> f64 {
+    match average {
+        Average::Micro => {
+            let (scores, positives): (Vec<f64>, Vec<bool>) = flatten(score, labels);
+            binary_roc_auc(&scores, &positives)
+        }
+        _ => average_per_class(&roc_auc_per_class(score, labels), score, labels, average),
+    }
+}
+
+pub fn pr_auc(score: &Matrix, labels: &Matrix, average: Average) -> f64 {
+    match average {
+        Average::Micro => {
+            let (scores, positives): (Vec<f64>, Vec<bool>) = flatten(score, labels);
+            average_precision(&scores, &positives)
+        }
+        _ => average_per_class(&pr_auc_per_class(score, labels), score, labels, average),
+    }
+}
+
+// multi-label metrics, predictions and targets are N x C matrices of 0 and 1
+
+// fraction of the labels that are wrongly predicted
+pub fn hamming_loss(predictions: &Matrix, targets: &Matrix) -> f64 {
+    assert_same_shape(predictions, targets);
+
+    let mut errors: usize = 0;
+    for r in 0..targets.height {
+        for c in 0..targets.width {
+            if predictions.get(r, c) != targets.get(r, c) {
+                errors += 1;
+            }
+        }
+    }
+
+    errors as f64 / (targets.height * targets.width) as f64
+}
+
+// fraction of the samples for which every label is correctly predicted
+pub fn subset_accuracy(predictions: &Matrix, targets: &Matrix) -> f64 {
+    assert_same_shape(predictions, targets);
+
+    let correct: usize = (0..targets.height)
+        .filter(|r| predictions.get_row(*r) == targets.get_row(*r))
+        .count();
+
+    correct as f64 / targets.height as f64
+}
+
+fn assert_same_shape(predictions: &Matrix, targets: &Matrix) {
+    assert!(
+        predictions.height == targets.height && predictions.width == targets.width,
+        "Predictions and targets should have the same shape"
+    );
+}
+
+struct ClassCounts {
+    true_positives: Vec<f64>,
+    // number of samples predicted as the class
+    predicted: Vec<f64>,
+    // number of samples of the class
+    support: Vec<f64>,
+}
+
+impl ClassCounts {
+    fn init(score: &Matrix, labels: &Matrix) -> ClassCounts {
+        let confusion: Matrix = confusion_matrix(score, labels);
+        let number_of_classes: usize = confusion.width;
+
+        ClassCounts {
+            true_positives: (0..number_of_classes)
+                .map(|c| confusion.get(c, c))
+                .collect(),
+            predicted: (0..number_of_classes)
+                .map(|c| (0..number_of_classes).map(|r| confusion.get(r, c)).sum())
+                .collect(),
+            support: (0..number_of_classes)
+                .map(|r| confusion.get_row(r).iter().sum())
+                .collect(),
+        }
+    }
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator == 0.0 {
+        0.0
+    } else {
+        numerator / denominator
+    }
+}
+
+fn f1(precision: f64, recall: f64) -> f64 {
+    ratio(2.0 * precision * recall, precision + recall)
+}
+
+fn average_per_class(values: &[f64], score: &Matrix, labels: &Matrix, average: Average) -> f64 {
+    let support: Vec<f64> = ClassCounts::init(score, labels).support;
+    let defined: Vec<(f64, f64)> = values
+        .iter()
+        .zip(support.iter())
+        .filter(|(value, _)| !value.is_nan())
+        .map(|(value, support)| (*value, *support))
+        .collect();
+
+    match average {
+        Average::Weighted => ratio(
+            defined.iter().map(|(value, support)| value * support).sum(),
+            defined.iter().map(|(_, support)| support).sum(),
+        ),
+        _ => ratio(
+            defined.iter().map(|(value, _)| value).sum(),
+            defined.len() as f64,
+        ),
+    }
+}
+
+fn one_vs_rest(score: &Matrix, labels: &Matrix, class: usize) -> (Vec<f64>, Vec<bool>) {
+    (0..score.height)
+        .map(|r| (score.get(r, class), labels.get(0, r) as usize == class))
+        .unzip()
+}
+
+fn flatten(score: &Matrix, labels: &Matrix) -> (Vec<f64>, Vec<bool>) {
+    assert_labels_shape(score, labels);
+
+    (0..score.height)
+        .flat_map(|r| {
+            (0..score.width).map(move |c| (score.get(r, c), labels.get(0, r) as usize == c))
+        })
+        .unzip()
+}
+
+// indexes sorted by decreasing score
+fn sorted_by_score(scores: &[f64]) -> Vec<usize> {
+    let mut indexes: Vec<usize> = (0..scores.len()).collect();
+    indexes.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+
+    indexes
+}
+
+// walks the thresholds from the highest score to the lowest, the samples with
+// the same score are added together, the ROC curve is integrated with trapezoids
+fn binary_roc_auc(scores: &[f64], positives: &[bool]) -> f64 {
+    let total_positives: f64 = positives.iter().filter(|p| **p).count() as f64;
+    let total_negatives: f64 = positives.len() as f64 - total_positives;
+    if total_positives == 0.0 || total_negatives == 0.0 {
+        return f64::NAN;
+    }
+
+    let indexes: Vec<usize> = sorted_by_score(scores);
+    let (mut true_positives, mut false_positives): (f64, f64) = (0.0, 0.0);
+    let mut area: f64 = 0.0;
+    let mut i: usize = 0;
+    while i < indexes.len() {
+        let (previous_tp, previous_fp): (f64, f64) = (true_positives, false_positives);
+        let threshold: f64 = scores[indexes[i]];
+        while i < indexes.len() && scores[indexes[i]] == threshold {
+            if positives[indexes[i]] {
+                true_positives += 1.0;
+            } else {
+                false_positives += 1.0;
+            }
+            i += 1;
+        }
+        area += (false_positives - previous_fp) * (true_positives + previous_tp) / 2.0;
+    }
+
+    area / (total_positives * total_negatives)
+}
+
+// sum over the thresholds of (R(n) - R(n-1)) * P(n)
+fn average_precision(scores: &[f64], positives: &[bool]) -> f64 {
+    let total_positives: f64 = positives.iter().filter(|p| **p).count() as f64;
+    if total_positives == 0.0 {
+        return f64::NAN;
+    }
+
+    let indexes: Vec<usize> = sorted_by_score(scores);
+    let (mut true_positives, mut predicted): (f64, f64) = (0.0, 0.0);
+    let mut area: f64 = 0.0;
+    let mut i: usize = 0;
+    while i < indexes.len() {
+        let previous_tp: f64 = true_positives;
+        let threshold: f64 = scores[indexes[i]];
+        while i < indexes.len() && scores[indexes[i]] == threshold {
+            if positives[indexes[i]] {
+                true_positives += 1.0;
+            }
+            predicted += 1.0;
+            i += 1;
+        }
+        area += (true_positives - previous_tp) / total_positives * (true_positives / predicted);
+    }
+
+    area
+}
+
+fn assert_labels_shape(score: &Matrix, labels: &Matrix) {
+    assert_eq!(
+        score.height, labels.width,
+        "Score height and labels width should be equal"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        accuracy, confusion_matrix, f1_per_class, f1_score, hamming_loss, log_loss, pr_auc,
+        pr_auc_per_class, precision, precision_per_class, recall_per_class, roc_auc,
+        roc_auc_per_class, subset_accuracy, top_k_accuracy, Average, Metric,
+    };
+    use crate::matrix::Matrix;
+
+    // predictions : 0, 1, 1, 2, 0, 2
+    fn get_test_score() -> (Matrix, Matrix) {
+        (
+            Matrix::init(
+                6,
+                3,
+                vec![
+                    0.7, 0.2, 0.1, //
+                    0.3, 0.6, 0.1, //
+                    0.1, 0.5, 0.4, //
+                    0.2, 0.3, 0.5, //
+                    0.5, 0.1, 0.4, //
+                    0.1, 0.1, 0.8, //
+                ],
+            ),
+            Matrix::init(1, 6, vec![0.0, 1.0, 2.0, 2.0, 0.0, 1.0]),
+        )
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-12, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn confusion_matrix_test() {
+        let (score, labels) = get_test_score();
+        let expected = Matrix::init(3, 3, vec![2.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 1.0]);
+
+        assert!(confusion_matrix(&score, &labels).is_equal(&expected, 15));
+        assert_close(accuracy(&score, &labels), 4.0 / 6.0);
+    }
+
+    #[test]
+    fn precision_recall_f1() {
+        let (score, labels) = get_test_score();
+
+        assert_eq!(precision_per_class(&score, &labels), vec![1.0, 0.5, 0.5]);
+        assert_eq!(recall_per_class(&score, &labels), vec![1.0, 0.5, 0.5]);
+        assert_eq!(f1_per_class(&score, &labels), vec![1.0, 0.5, 0.5]);
+        assert_close(precision(&score, &labels, Average::Macro), 2.0 / 3.0);
+        assert_close(precision(&score, &labels, Average::Micro), 4.0 / 6.0);
+        assert_close(f1_score(&score, &labels, Average::Weighted), 2.0 / 3.0);
+
+        // class 1 is never predicted
+        let labels = Matrix::init(1, 6, vec![0.0, 0.0, 2.0, 2.0, 0.0, 1.0]);
+        assert_eq!(precision_per_class(&score, &labels)[1], 0.0);
+        assert_close(
+            precision(&score, &labels, Average::Weighted),
+            (3.0 * 1.0 + 1.0 * 0.0 + 2.0 * 0.5) / 6.0,
+        );
+    }
+
+    #[test]
+    fn top_k_accuracy_test() {
+        let (score, labels) = get_test_score();
+
+        assert_close(top_k_accuracy(&score, &labels, 1), 4.0 / 6.0);
+        // the label of the last sample is tied for the second place
+        assert_close(top_k_accuracy(&score, &labels, 2), 1.0);
+    }
+
+    #[test]
+    fn constant_score_accuracy() {
+        // every class is as probable, a tie is not a correct answer for every label
+        let score: Matrix = Matrix::init(3, 3, vec![1.0; 9]);
+        let labels: Matrix = Matrix::init(1, 3, vec![0.0, 1.0, 2.0]);
+
+        assert_close(accuracy(&score, &labels), 1.0 / 3.0);
+        assert_close(top_k_accuracy(&score, &labels, 1), 1.0 / 3.0);
+        assert_close(top_k_accuracy(&score, &labels, 2), 2.0 / 3.0);
+        assert_close(precision(&score, &labels, Average::Micro), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn log_loss_test() {
+        let (score, labels) = get_test_score();
+        let expected: f64 = -(0.7_f64.ln()
+            + 0.6_f64.ln()
+            + 0.4_f64.ln()
+            + 0.5_f64.ln()
+            + 0.5_f64.ln()
+            + 0.1_f64.ln())
+            / 6.0;
+
+        assert_close(log_loss(&score, &labels), expected);
+        assert!(!Metric::LogLoss.higher_is_better());
+    }
+
+    #[test]
+    fn roc_auc_test() {
+        let (score, labels) = get_test_score();
+        let per_class = roc_auc_per_class(&score, &labels);
+
+        // class 0 : positives 0.7, 0.5, negatives 0.3, 0.1, 0.2, 0.1
+        assert_close(per_class[0], 1.0);
+        // class 1 : positives 0.6, 0.1, negatives 0.2, 0.5, 0.3, 0.1
+        // pairs won : 4 + 0.5 (tie with 0.1), out of 8
+        assert_close(per_class[1], 4.5 / 8.0);
+        assert_close(
+            roc_auc(&score, &labels, Average::Macro),
+            per_class.iter().sum::<f64>() / 3.0,
+        );
+
+        // a class without positive sample is ignored by the macro average
+        let labels = Matrix::init(1, 6, vec![0.0, 0.0, 2.0, 2.0, 0.0, 2.0]);
+        assert!(roc_auc_per_class(&score, &labels)[1].is_nan());
+        assert!(!roc_auc(&score, &labels, Average::Macro).is_nan());
+    }
+
+    #[test]
+    fn pr_auc_test() {
+        let (score, labels) = get_test_score();
+        let per_class = pr_auc_per_class(&score, &labels);
+
+        assert_close(per_class[0], 1.0);
+        // class 2 : scores 0.8 (n), 0.5 (p), 0.4 (p), 0.4 (n), 0.1, 0.1
+        // (0.5 * 1 / 2) + (0.5 * 2 / 4)
+        assert_close(per_class[2], 0.5);
+        assert_close(
+            Metric::PrAuc(Average::Macro).compute(&score, &labels),
+            pr_auc(&score, &labels, Average::Macro),
+        );
+    }
+
+    fn get_test_data() -> (Matrix, Matrix) {
+        (
+            Matrix::init(3, 3, vec![1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0]),
+            Matrix::init(3, 3, vec![1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0]),
+        )
+    }
+
+    #[test]
+    fn hamming_loss_test() {
+        let (predictions, targets) = get_test_data();
+
+        assert!((hamming_loss(&predictions, &targets) - 3.0 / 9.0).abs() < 1e-12);
+        assert_eq!(hamming_loss(&targets, &targets), 0.0);
+    }
+
+    #[test]
+    fn subset_accuracy_test() {
+        let (predictions, targets) = get_test_data();
+
+        assert!((subset_accuracy(&predictions, &targets) - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(subset_accuracy(&targets, &targets), 1.0);
+    }
+}
@@ -1,17 +1,70 @@
 use crate::activation::*;
 use crate::checkpoint::Checkpoint;
+use crate::dataset::{Batch, DataLoader, Dataset, MatrixDataset, Sampler};
+use crate::error::{BriqueError, ModelBuildError};
+use crate::history::TrainingHistory;
 use crate::layers::*;
 use crate::loss::*;
 use crate::matrix::*;
+use crate::metrics::{accuracy, subset_accuracy};
+use crate::numerical_guard::*;
 use crate::optimizer::*;
+use crate::preprocessing::FittedPipeline;
 use crate::save_load::save_model;
-use crate::utils::*;
+use crate::summary::ModelSummary;
+use crate::validation::{sample_classes, ValidationSplit};
+
+// checks that every layer takes the output of the previous one as input
+pub fn check_layer_shapes(
+    layers: &[Layer],
+    input_size: Option<usize>,
+) -> Result<(), ModelBuildError> {
+    if layers.is_empty() {
+        return Err(ModelBuildError::NoLayers);
+    }
+
+    let mut expected_input_size: Option<usize> = input_size;
+    for (index, layer) in layers.iter().enumerate() {
+        if let Some(expected_input_size) = expected_input_size {
+            if layer.weights_t.height != expected_input_size {
+                return Err(ModelBuildError::LayerShapeMismatch {
+                    layer: index,
+                    expected_input_size,
+                    input_size: layer.weights_t.height,
+                });
+            }
+        }
+        if layer.biases.width != layer.weights_t.width {
+            return Err(ModelBuildError::BiasesShapeMismatch {
+                layer: index,
+                expected_width: layer.weights_t.width,
+                width: layer.biases.width,
+            });
+        }
+        expected_input_size = Some(layer.weights_t.width);
+    }
+
+    Ok(())
+}
 
 #[derive(Clone)]
 pub struct Model {
     pub layers: Vec<Layer>,
     pub lambda: f64,
     pub optimizer: Optimizer,
+    // gradient clipping, applied by value first then by global norm
+    pub clip_value: Option<f64>,
+    pub clip_norm: Option<f64>,
+    pub numerical_guard: Option<NumericalGuard>,
+    pub loss_function: ClassificationLoss,
+    pub target_mode: TargetMode,
+    // seeds the shuffle of the training data and the validation split, random if None
+    // set by ModelBuilder::seed, not saved in the .brq file
+    pub seed: Option<u64>,
+    pub history: TrainingHistory,
+    // fitted preprocessing applied before the first layer by the inference api (evaluate,
+    // predict, embed ...) and by the training api, which then expects raw data
+    pub preprocessing: Option<FittedPipeline>,
 
     // these elements are stored in the struct for debugging purposes
     // only if debug arg is true
@@ -38,6 +91,14 @@ impl Model {
             layers,
             lambda,
             optimizer,
+            clip_value: None,
+            clip_norm: None,
+            numerical_guard: None,
+            loss_function: ClassificationLoss::default(),
+            target_mode: TargetMode::ClassIndex,
+            seed: None,
+            history: TrainingHistory::new(),
+            preprocessing: None,
             layers_debug: None,
             input: None,
             input_label: None,
@@ -55,7 +116,31 @@ impl Model {
         output
     }
 
+    // the preprocessing pipeline, if any, is applied to the input first
     pub fn evaluate(&mut self, input: &Matrix, debug: bool) -> Matrix {
+        let transformed: Option<Matrix> = self.preprocessing.as_ref().map(|p| p.transform(input));
+        self.forward(transformed.as_ref().unwrap_or(input), debug)
+    }
+
+    // forward pass without the final softmax
+    pub fn evaluate_logits(&mut self, input: &Matrix, debug: bool) -> Matrix {
+        let transformed: Option<Matrix> = self.preprocessing.as_ref().map(|p| p.transform(input));
+        self.forward_logits(transformed.as_ref().unwrap_or(input), debug)
+    }
+
+    // same as evaluate on an input already transformed by the preprocessing
+    fn forward(&mut self, input: &Matrix, debug: bool) -> Matrix {
+        let logits: Matrix = self.forward_logits(input, debug);
+        let output = self.output_activation(&logits);
+
+        if debug {
+            self.softmax_output = Some(output.clone());
+        }
+
+        output
+    }
+
+    fn forward_logits(&mut self, input: &Matrix, debug: bool) -> Matrix {
         for index in 0..self.layers.len() {
             if index == 0 {
                 self.layers[0].forward(input, false);
@@ -71,94 +156,302 @@ impl Model {
             }
         }
 
-        let output = softmax(&self.layers[self.layers.len() - 1].output);
+        self.layers[self.layers.len() - 1].output.clone()
+    }
 
-        if debug {
-            self.softmax_output = Some(output.clone());
+    // softmax, or sigmoid for multi-label models
+    pub fn output_activation(&self, logits: &Matrix) -> Matrix {
+        match self.target_mode {
+            TargetMode::MultiLabel { .. } => sigmoid(logits),
+            _ => softmax(logits),
         }
+    }
 
-        output
+    // inference api, does not modify the model so it can be shared between threads
+    // every call allocates its own intermediate matrices
+
+    // output of the last layer (logits)
+    pub fn predict(&self, input: &Matrix) -> Matrix {
+        self.embed(input, self.layers.len() - 1)
     }
 
-    // implementing cross-entropy and L2 regulariztion
-    pub fn compute_loss(&mut self, output: &Matrix, labels: &Matrix, debug: bool) -> (f64, f64) {
+    // output of the layer layer_index (after its activation), e.g. the
+    // penultimate layer for embeddings, the following layers are not computed
+    pub fn embed(&self, input: &Matrix, layer_index: usize) -> Matrix {
+        self.embed_layers(input, &[layer_index]).remove(0)
+    }
+
+    // outputs of several layers in one forward pass, in the order of layer_indexes
+    pub fn embed_layers(&self, input: &Matrix, layer_indexes: &[usize]) -> Vec<Matrix> {
+        let last_index: usize = match layer_indexes.iter().max() {
+            Some(index) => *index,
+            None => return vec![],
+        };
+        assert!(
+            last_index < self.layers.len(),
+            "Error : layer index {} out of range, the model has {} layers",
+            last_index,
+            self.layers.len()
+        );
+
+        // only the requested outputs are kept
+        let mut outputs: Vec<Option<Matrix>> = vec![None; last_index + 1];
+        let mut output: Matrix = match &self.preprocessing {
+            Some(pipeline) => pipeline.transform(input),
+            None => input.clone(),
+        };
+        for (index, layer) in self.layers.iter().take(last_index + 1).enumerate() {
+            output = layer.predict(&output);
+            if layer_indexes.contains(&index) {
+                outputs[index] = Some(output.clone());
+            }
+        }
+
+        layer_indexes
+            .iter()
+            .map(|index| outputs[*index].clone().unwrap())
+            .collect()
+    }
+
+    // same as predict, returns an error if the width of the input does not match the model
+    pub fn try_predict(&self, input: &Matrix) -> Result<Matrix, BriqueError> {
+        let expected_width: usize = self.input_width();
+        if input.width != expected_width {
+            return Err(BriqueError::Shape(format!(
+                "the input has {} columns, the model expects {}",
+                input.width, expected_width
+            )));
+        }
+
+        Ok(self.predict(input))
+    }
+
+    // width of the raw inputs of the inference api, before the preprocessing
+    pub fn input_width(&self) -> usize {
+        match self.preprocessing.as_ref().and_then(|p| p.input_width()) {
+            Some(width) => width,
+            None => self.layers[0].weights_t.height,
+        }
+    }
+
+    // attaches a fitted pipeline, its output width must match the first layer
+    pub fn set_preprocessing(&mut self, pipeline: FittedPipeline) -> Result<(), BriqueError> {
+        let expected_width: usize = self.layers[0].weights_t.height;
+        if let Some(width) = pipeline.output_width() {
+            if width != expected_width {
+                return Err(BriqueError::Shape(format!(
+                    "the preprocessing outputs {} columns, the first layer expects {}",
+                    width, expected_width
+                )));
+            }
+        }
+        self.preprocessing = Some(pipeline);
+
+        Ok(())
+    }
+
+    // softmax probabilities, or sigmoid probabilities for multi-label models
+    pub fn predict_proba(&self, input: &Matrix) -> Matrix {
+        self.output_activation(&self.predict(input))
+    }
+
+    // 1 x N matrix of the most probable classes
+    // N x C matrix of 0 and 1 for multi-label models, see predict_multi_label
+    pub fn predict_classes(&self, input: &Matrix) -> Matrix {
+        match self.target_mode {
+            TargetMode::MultiLabel { .. } => self.predict_multi_label(input),
+            _ => Self::evaluation_output(&self.predict(input)),
+        }
+    }
+
+    pub fn predict_log_proba(&self, input: &Matrix) -> Matrix {
+        let logits: Matrix = self.predict(input);
+        match self.target_mode {
+            TargetMode::MultiLabel { .. } => log_sigmoid(&logits),
+            _ => log_softmax(&logits),
+        }
+    }
+
+    // N x C matrix of 0 and 1, only for the multi-label models
+    pub fn predict_multi_label(&self, input: &Matrix) -> Matrix {
+        let thresholds: Vec<f64> = match &self.target_mode {
+            TargetMode::MultiLabel { thresholds } => thresholds.clone(),
+            _ => panic!("Error : predict_multi_label needs a model in the multi-label mode"),
+        };
+        let probabilities: Matrix = self.predict_proba(input);
+
+        Self::multi_label_output(&probabilities, &thresholds)
+    }
+
+    // implementing the classification loss (cross-entropy by default) and L2 regulariztion
+    // the logits are the output of the last layer, before the softmax
+    pub fn compute_loss_from_logits(
+        &mut self,
+        logits: &Matrix,
+        labels: &Matrix,
+        sample_weights: Option<&Matrix>,
+        debug: bool,
+    ) -> (f64, f64) {
+        let data_loss: f64 = data_loss(
+            logits,
+            labels,
+            sample_weights,
+            &self.loss_function,
+            &self.target_mode,
+        );
+        let reg_loss: f64 = l2_reg(&self.layers, self.lambda);
+
         if debug {
-            self.data_loss = Some(cross_entropy(output, labels));
-            self.reg_loss = Some(l2_reg(&self.layers, self.lambda));
+            self.data_loss = Some(data_loss);
+            self.reg_loss = Some(reg_loss);
         }
 
-        (
-            cross_entropy(output, labels),
-            l2_reg(&self.layers, self.lambda),
-        )
+        (data_loss, reg_loss)
     }
 
+    // same losses from the output probabilities (softmax, or sigmoid for multi-label models)
+    // their log gives back the logits, the softmax ignores the missing constant
+    #[deprecated(note = "use compute_loss_from_logits, the probabilities lose precision")]
+    pub fn compute_loss(&mut self, output: &Matrix, labels: &Matrix, debug: bool) -> (f64, f64) {
+        let mut logits: Matrix = output.clone();
+        logits.data = match self.target_mode {
+            TargetMode::MultiLabel { .. } => {
+                output.data.iter().map(|p| (p / (1.0 - p)).ln()).collect()
+            }
+            _ => output.data.iter().map(|p| p.ln()).collect(),
+        };
+
+        self.compute_loss_from_logits(&logits, labels, None, debug)
+    }
+
+    // gradient of the cross entropy relative to the logits from the softmax probabilities
+    // and the class indexes : d_score = score - one_hot(labels)
+    #[deprecated(note = "use compute_d_logits, which supports every loss and target mode")]
     pub fn compute_d_score(score: &Matrix, labels: &Matrix) -> Matrix {
-        let mut output: Matrix = Matrix::init_zero(score.height, score.width);
-        for r in 0..score.height {
-            for c in 0..score.width {
-                //TODO make a choice, to divide or not to divide
-                if labels.get(0, r) == c as f64 {
-                    //output.data[r][c] = (score.data[r][c] - 1.0) / score.height as f64;
-                    let v: f64 = score.get(r, c) - 1.0;
-
-                    output.set(v, r, c);
-                } else {
-                    //output.data[r][c] = score.data[r][c] / score.height as f64;
-                    let v: f64 = score.get(r, c);
-                    output.set(v, r, c);
-                }
+        let mut logits: Matrix = score.clone();
+        logits.data = score.data.iter().map(|p| p.ln()).collect();
+
+        Self::compute_d_logits(
+            &logits,
+            labels,
+            None,
+            &ClassificationLoss::default(),
+            &TargetMode::ClassIndex,
+        )
+    }
+
+    // gradient of the loss relative to the logits, see loss::d_data_loss
+    // with the default cross entropy : d_score = softmax(logits) - one_hot(labels)
+    pub fn compute_d_logits(
+        logits: &Matrix,
+        labels: &Matrix,
+        sample_weights: Option<&Matrix>,
+        loss_function: &ClassificationLoss,
+        target_mode: &TargetMode,
+    ) -> Matrix {
+        d_data_loss(logits, labels, sample_weights, loss_function, target_mode)
+    }
+
+    pub fn update_params(&mut self, d_score: Matrix, input: Matrix, iteration: i32, debug: bool) {
+        let gradients: Vec<LayerGradients> = self.compute_gradients(d_score, &input);
+        self.apply_gradients(gradients, iteration, debug);
+    }
+
+    // clips the gradients if needed and updates the trainable layers
+    pub fn apply_gradients(
+        &mut self,
+        mut gradients: Vec<LayerGradients>,
+        iteration: i32,
+        debug: bool,
+    ) {
+        let gradient_norm: f64 = self.gradient_norm(&gradients);
+        self.history.gradient_norms.push(gradient_norm);
+
+        if let Some(max_value) = self.clip_value {
+            gradients
+                .iter_mut()
+                .for_each(|layer_gradients| layer_gradients.clip_by_value(max_value));
+        }
+
+        if let Some(max_norm) = self.clip_norm {
+            let norm: f64 = self.gradient_norm(&gradients);
+            if norm > max_norm {
+                gradients
+                    .iter_mut()
+                    .for_each(|layer_gradients| layer_gradients.scale(max_norm / norm));
             }
         }
 
-        output
+        if debug {
+            // stored from the last layer to the first, in the backprop order
+            for layer_gradients in gradients.iter().rev() {
+                self.d_zs
+                    .get_or_insert(Vec::new())
+                    .push(layer_gradients.d_output.clone());
+                self.d_ws
+                    .get_or_insert(Vec::new())
+                    .push(layer_gradients.d_weights.clone());
+                self.d_bs
+                    .get_or_insert(Vec::new())
+                    .push(layer_gradients.d_biases.clone());
+            }
+        }
+
+        for (layer, layer_gradients) in self.layers.iter_mut().zip(gradients) {
+            if !layer.trainable {
+                continue;
+            }
+
+            layer.update_weigths(layer_gradients.d_weights, &self.optimizer, iteration);
+            layer.update_biases(layer_gradients.d_biases, &self.optimizer, iteration);
+        }
     }
 
-    pub fn update_params(&mut self, d_score: Matrix, input: Matrix, iteration: i32, debug: bool) {
-        let mut index: usize = self.layers.len() - 1;
+    // global L2 norm of the gradients of the trainable layers
+    pub fn gradient_norm(&self, gradients: &[LayerGradients]) -> f64 {
+        gradients
+            .iter()
+            .zip(self.layers.iter())
+            .filter(|(_, layer)| layer.trainable)
+            .map(|(layer_gradients, _)| layer_gradients.squared_norm())
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    // backpropagation without applying the gradients, uses the outputs
+    // cached in the layers by the last call to evaluate
+    // the output is indexed like self.layers
+    pub fn compute_gradients(&self, d_score: Matrix, input: &Matrix) -> Vec<LayerGradients> {
+        let mut output: Vec<LayerGradients> = vec![];
         let mut d_z: Matrix = d_score;
 
-        loop {
-            if index > 0 {
-                let (l, r) = self.layers.split_at_mut(index);
-                d_z = r[0].backprop(
-                    &d_z,
-                    &l[index - 1].output,
-                    l[index - 1].relu,
-                    self.lambda,
-                    &self.optimizer,
-                    iteration,
-                    false,
-                    debug,
-                    &mut self.d_ws,
-                    &mut self.d_bs,
-                    &mut self.d_zs,
-                );
-            } else if index == 0 {
-                self.layers[index].backprop(
-                    &d_z,
-                    &input,
-                    false,
-                    self.lambda,
-                    &self.optimizer,
-                    iteration,
-                    true,
-                    debug,
-                    &mut self.d_ws,
-                    &mut self.d_bs,
-                    &mut self.d_zs,
-                );
-                break;
-            }
+        for index in (0..self.layers.len()).rev() {
+            let (z_minus_1, previous_layer_relu): (&Matrix, bool) = if index > 0 {
+                (&self.layers[index - 1].output, self.layers[index - 1].relu)
+            } else {
+                (input, false)
+            };
+
+            let (d_w, d_b, new_d_z) = self.layers[index].gradients(
+                &d_z,
+                z_minus_1,
+                previous_layer_relu,
+                self.lambda,
+                index == 0,
+            );
 
-            index -= 1;
+            output.push(LayerGradients {
+                d_weights: d_w,
+                d_biases: d_b,
+                d_output: d_z,
+            });
+            d_z = new_d_z;
         }
+
+        output.reverse();
+        output
     }
 
-    // the steps :
-    // before every epoch :
-    //  - shuffle dataset (use the algo of rand crate)
-    //  - generate batch from shuffled dataset
     pub fn train(
         &mut self,
         data: &Matrix,
@@ -171,56 +464,398 @@ impl Model {
         debug: bool,
         silent_mode: bool, // if true will not print anything
     ) -> Option<Vec<Model>> {
-        let mut network_history: Option<Vec<Model>> = None;
+        self.train_with_sample_weights(
+            data,
+            labels,
+            None,
+            batch_size,
+            epochs,
+            ValidationSplit::Random {
+                size: validation_dataset_size,
+            },
+            checkpoint,
+            print_frequency,
+            debug,
+            silent_mode,
+        )
+    }
 
-        let mut index_table: Vec<u32>;
-        let index_validation: Vec<u32>;
-        let mut validation_data: Matrix =
-            Matrix::init_zero(validation_dataset_size as usize, data.width);
-        let mut validation_label: Matrix = Matrix::init_zero(1, validation_dataset_size as usize);
+    // same as train, returns an error instead of panicking on invalid data or
+    // parameters and when a checkpoint can not be saved
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_train(
+        &mut self,
+        data: &Matrix,
+        labels: &Matrix,
+        batch_size: u32,
+        epochs: u32,
+        validation_dataset_size: usize,
+        checkpoint: Option<Checkpoint>,
+        print_frequency: usize,
+        debug: bool,
+        silent_mode: bool,
+    ) -> Result<Option<Vec<Model>>, BriqueError> {
+        self.try_train_with_sample_weights(
+            data,
+            labels,
+            None,
+            batch_size,
+            epochs,
+            ValidationSplit::Random {
+                size: validation_dataset_size,
+            },
+            checkpoint,
+            print_frequency,
+            debug,
+            silent_mode,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn train_with_sample_weights(
+        &mut self,
+        data: &Matrix,
+        labels: &Matrix,
+        sample_weights: Option<&Matrix>,
+        batch_size: u32,
+        epochs: u32,
+        validation: ValidationSplit,
+        checkpoint: Option<Checkpoint>,
+        print_frequency: usize,
+        debug: bool,
+        silent_mode: bool,
+    ) -> Option<Vec<Model>> {
+        match self.try_train_with_sample_weights(
+            data,
+            labels,
+            sample_weights,
+            batch_size,
+            epochs,
+            validation,
+            checkpoint,
+            print_frequency,
+            debug,
+            silent_mode,
+        ) {
+            Ok(output) => output,
+            Err(e) => panic!("Error : {}", e),
+        }
+    }
+
+    // the steps :
+    // before every epoch :
+    //  - shuffle dataset (use the algo of rand crate)
+    //  - generate batch from shuffled dataset
+    // labels : 1 x N class indexes or N x C targets, depending on self.target_mode
+    // sample_weights : 1 x N matrix, multiplies the loss of every sample
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_train_with_sample_weights(
+        &mut self,
+        data: &Matrix,
+        labels: &Matrix,
+        sample_weights: Option<&Matrix>,
+        batch_size: u32,
+        epochs: u32,
+        validation: ValidationSplit,
+        checkpoint: Option<Checkpoint>,
+        print_frequency: usize,
+        debug: bool,
+        silent_mode: bool, // if true will not print anything
+    ) -> Result<Option<Vec<Model>>, BriqueError> {
+        self.check_data_shape(data, labels)?;
+        if let Some(weights) = sample_weights {
+            if weights.height != 1 || weights.width != data.height {
+                return Err(BriqueError::Shape(format!(
+                    "the sample weights should be a 1x{} matrix, found {}x{}",
+                    data.height, weights.height, weights.width
+                )));
+            }
+        }
+        if let ValidationSplit::Explicit {
+            data: validation_data,
+            labels: validation_labels,
+        } = &validation
+        {
+            if validation_data.width != data.width {
+                return Err(BriqueError::Shape(format!(
+                    "the validation data should have {} columns, found {}",
+                    data.width, validation_data.width
+                )));
+            }
+            let validation_samples: usize = match self.target_mode {
+                TargetMode::ClassIndex => validation_labels.width,
+                _ => validation_labels.height,
+            };
+            if validation_samples != validation_data.height {
+                return Err(BriqueError::Shape(format!(
+                    "the validation dataset has {} samples but {} labels",
+                    validation_data.height, validation_samples
+                )));
+            }
+        }
 
         // first step is to randomize the input data
         // and to create the validation dataset
-        // if debugging mode is on, no validation and no randomization
-        if !debug {
-            index_table = generate_vec_rand_unique(data.height as u32);
-
-            index_validation = index_table[0..validation_dataset_size].to_vec();
-            index_table.drain(0..validation_dataset_size);
-
-            for i in 0..validation_dataset_size as usize {
-                let index: usize = index_validation[i] as usize;
-                // TODO write test for validation dataset creation
-                validation_data.set_row(&data.get_row(index), i);
-                validation_label.set(labels.get(0, index), 0, i);
+        // if debugging mode is on, no randomization
+        let (index_table, index_validation): (Vec<u32>, Vec<u32>) =
+            validation.split(labels, data.height, &self.target_mode, !debug, self.seed)?;
+
+        let validation_data: Matrix;
+        let validation_label: Matrix;
+        let mut validation_weights: Option<Matrix> = None;
+        match validation {
+            ValidationSplit::Explicit {
+                data: explicit_data,
+                labels: explicit_labels,
+            } => {
+                validation_data = explicit_data;
+                validation_label = explicit_labels;
+            }
+            _ => {
+                let validation_indexes: Vec<usize> = index_validation
+                    .iter()
+                    .map(|index| *index as usize)
+                    .collect();
+                validation_label = self.target_mode.select(labels, &validation_indexes);
+
+                let mut split_data: Matrix =
+                    Matrix::init_zero(validation_indexes.len(), data.width);
+                for (i, index) in validation_indexes.iter().enumerate() {
+                    split_data.set_row(&data.get_row(*index), i);
+                }
+                validation_data = split_data;
+
+                if let Some(weights) = sample_weights {
+                    let mut split_weights: Matrix = Matrix::init_zero(1, validation_indexes.len());
+                    for (i, index) in validation_indexes.iter().enumerate() {
+                        split_weights.set(weights.get(0, *index), 0, i);
+                    }
+                    validation_weights = Some(split_weights);
+                }
+            }
+        }
+
+        let mut dataset: MatrixDataset = MatrixDataset::init(data, labels);
+        if let Some(weights) = sample_weights {
+            dataset = dataset.with_sample_weights(weights);
+        }
+        // the order of the samples is drawn once, before the first epoch
+        let loader: DataLoader<MatrixDataset> = DataLoader::init(&dataset, batch_size as usize)
+            .shuffle(false)
+            .sampler(Sampler::Subset(
+                index_table.iter().map(|index| *index as usize).collect(),
+            ));
+
+        self.train_loop(
+            &loader,
+            epochs,
+            &validation_data,
+            &validation_label,
+            validation_weights.as_ref(),
+            checkpoint,
+            print_frequency,
+            debug,
+            silent_mode,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn train_on_loader<D: Dataset + ?Sized>(
+        &mut self,
+        loader: &DataLoader<D>,
+        validation: Option<&dyn Dataset>,
+        epochs: u32,
+        checkpoint: Option<Checkpoint>,
+        print_frequency: usize,
+        debug: bool,
+        silent_mode: bool,
+    ) -> Option<Vec<Model>> {
+        match self.try_train_on_loader(
+            loader,
+            validation,
+            epochs,
+            checkpoint,
+            print_frequency,
+            debug,
+            silent_mode,
+        ) {
+            Ok(output) => output,
+            Err(e) => panic!("Error : {}", e),
+        }
+    }
+
+    // trains on the batches of any dataset, the loader decides the order of the samples
+    // and the size of the batches
+    // validation : read once before the training, without it the validation loss and
+    // accuracy are not printed and a checkpoint is an error
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_train_on_loader<D: Dataset + ?Sized>(
+        &mut self,
+        loader: &DataLoader<D>,
+        validation: Option<&dyn Dataset>,
+        epochs: u32,
+        checkpoint: Option<Checkpoint>,
+        print_frequency: usize,
+        debug: bool,
+        silent_mode: bool,
+    ) -> Result<Option<Vec<Model>>, BriqueError> {
+        check_layer_shapes(&self.layers, None)?;
+        let expected_width: usize = self.input_width();
+        let widths: [Option<usize>; 2] = [
+            Some(loader.dataset().width()),
+            validation.map(|validation| validation.width()),
+        ];
+        if let Some(width) = widths
+            .iter()
+            .flatten()
+            .find(|width| **width != expected_width)
+        {
+            return Err(ModelBuildError::InputWidthMismatch {
+                expected_width,
+                width: *width,
             }
-        } else {
-            index_table = (0..data.height as u32).collect();
+            .into());
         }
 
+        let validation_batch: Batch = match validation {
+            Some(validation) => validation.get_batch(
+                &(0..validation.len()).collect::<Vec<usize>>(),
+                &self.target_mode,
+            ),
+            None => Batch {
+                data: Matrix::init_zero(0, expected_width),
+                labels: match self.target_mode {
+                    TargetMode::ClassIndex => Matrix::init_zero(1, 0),
+                    _ => Matrix::init_zero(0, self.layers[self.layers.len() - 1].weights_t.width),
+                },
+                weights: None,
+            },
+        };
+
+        self.train_loop(
+            loader,
+            epochs,
+            &validation_batch.data,
+            &validation_batch.labels,
+            validation_batch.weights.as_ref(),
+            checkpoint,
+            print_frequency,
+            debug,
+            silent_mode,
+        )
+    }
+
+    // the training loop shared by the matrix and the dataset apis
+    #[allow(clippy::too_many_arguments)]
+    fn train_loop<D: Dataset + ?Sized>(
+        &mut self,
+        loader: &DataLoader<D>,
+        epochs: u32,
+        validation_data: &Matrix,
+        validation_label: &Matrix,
+        validation_weights: Option<&Matrix>,
+        checkpoint: Option<Checkpoint>,
+        print_frequency: usize,
+        debug: bool,
+        silent_mode: bool,
+    ) -> Result<Option<Vec<Model>>, BriqueError> {
+        if print_frequency == 0 {
+            return Err(BriqueError::Config(
+                "the print frequency must be strictly positive".to_string(),
+            ));
+        }
+        // the builder checks the thresholds but the fields are public
+        for value in [self.clip_value, self.clip_norm].iter().flatten() {
+            if !value.is_finite() || *value <= 0.0 {
+                return Err(BriqueError::Config(format!(
+                    "the gradient clipping threshold is {}, expected a finite and strictly positive value",
+                    value
+                )));
+            }
+        }
+
+        // without validation data the checkpoint could never compare two models
+        let has_validation: bool = validation_data.height > 0;
+        if checkpoint.is_some() && !has_validation {
+            return Err(BriqueError::Config(
+                "a checkpoint needs validation data".to_string(),
+            ));
+        }
+
+        // the metrics expect class indexes, the probability targets go through an argmax
+        let metric_label: Option<Matrix> = match (&checkpoint, &self.target_mode) {
+            (Some(Checkpoint::ValMetric { .. }), TargetMode::MultiLabel { .. }) => {
+                return Err(BriqueError::Config(
+                    "the validation metric checkpoint is not available for multi-label targets"
+                        .to_string(),
+                ));
+            }
+            (Some(Checkpoint::ValMetric { .. }), target_mode) => {
+                Some(sample_classes(validation_label, target_mode)?)
+            }
+            _ => None,
+        };
+
+        // the batches are transformed one by one, the validation data once
+        let validation_data: &Matrix = &match &self.preprocessing {
+            Some(pipeline) if has_validation => pipeline.try_transform(validation_data)?,
+            _ => validation_data.clone(),
+        };
+
+        let mut network_history: Option<Vec<Model>> = None;
+        self.history = TrainingHistory::new();
+
         let mut iteration: i32 = 1;
         let mut best_val_acc: Option<f64> = None;
         let mut best_val_loss: Option<f64> = None;
+        let mut best_val_metric: Option<f64> = None;
+        let target_mode: TargetMode = self.target_mode.clone();
+        let mut stop_training: bool = false;
         for epoch in 0..epochs {
-            let index_matrix: Vec<Vec<f64>> = generate_batch_index(&index_table, batch_size);
+            loader.for_each_batch(epoch as usize, &target_mode, |batch_row, number_of_batches, batch| {
+                let batch_data: Matrix = match &self.preprocessing {
+                    Some(pipeline) => pipeline.try_transform(&batch.data)?,
+                    None => batch.data,
+                };
+                let batch_label: Matrix = batch.labels;
+                let batch_weights: Option<Matrix> = batch.weights;
 
-            for batch_row in 0..index_matrix.len() {
-                let batch_indexes: Vec<f64> = index_matrix[batch_row].clone();
-                let mut batch_data: Matrix = Matrix::init_zero(batch_indexes.len(), data.width);
-                let mut batch_label: Matrix = Matrix::init_zero(1, batch_indexes.len());
+                let score: Matrix = self.forward(&batch_data, debug);
+                let logits: Matrix = self.layers[self.layers.len() - 1].output.clone();
 
-                for i in 0..batch_indexes.len() as usize {
-                    let index: usize = batch_indexes[i] as usize;
-                    batch_data.set_row(&data.get_row(index), i);
-                    batch_label.set(labels.get(0, index), 0, i);
+                let mut numerical_issue: Option<NumericalIssueKind> = None;
+                if self.numerical_guard.is_some() {
+                    numerical_issue = check_activations(&self.layers);
+                    if numerical_issue.is_none()
+                        && !self
+                            .compute_loss_from_logits(
+                                &logits,
+                                &batch_label,
+                                batch_weights.as_ref(),
+                                false,
+                            )
+                            .0
+                            .is_finite()
+                    {
+                        numerical_issue = Some(NumericalIssueKind::Loss);
+                    }
                 }
 
-                let score: Matrix = self.evaluate(&batch_data, debug);
-                let d_score: Matrix = Model::compute_d_score(&score, &batch_label);
+                let d_score: Matrix = Model::compute_d_logits(
+                    &logits,
+                    &batch_label,
+                    batch_weights.as_ref(),
+                    &self.loss_function,
+                    &self.target_mode,
+                );
 
                 if debug {
-                    let (loss, l2_reg_penalty): (f64, f64) =
-                        self.compute_loss(&score, &batch_label, debug);
+                    let (loss, l2_reg_penalty): (f64, f64) = self.compute_loss_from_logits(
+                        &logits,
+                        &batch_label,
+                        batch_weights.as_ref(),
+                        debug,
+                    );
                     self.d_score = Some(d_score.clone());
                     self.input = Some(batch_data.clone());
                     self.input_label = Some(batch_label.clone());
@@ -228,7 +863,35 @@ impl Model {
                     self.layers_debug = Some(self.layers.clone());
                 }
 
-                self.update_params(d_score, batch_data, iteration, debug);
+                let gradients: Vec<LayerGradients> = self.compute_gradients(d_score, &batch_data);
+
+                if self.numerical_guard.is_some() && numerical_issue.is_none() {
+                    numerical_issue = check_gradients(&gradients);
+                }
+
+                if let Some(kind) = numerical_issue {
+                    let issue = NumericalIssue {
+                        kind,
+                        epoch: epoch + 1,
+                        batch: batch_row + 1,
+                        iteration,
+                    };
+                    let stop: bool = self.handle_numerical_issue(
+                        issue,
+                        &batch_data,
+                        &batch_label,
+                        &gradients,
+                        silent_mode,
+                    );
+
+                    if stop {
+                        stop_training = true;
+                        return Ok(false);
+                    }
+                    return Ok(true);
+                }
+
+                self.apply_gradients(gradients, iteration, debug);
 
                 if debug {
                     network_history.get_or_insert(Vec::new()).push(self.clone());
@@ -241,13 +904,13 @@ impl Model {
                 match &checkpoint {
                     Some(checkpoint) => match checkpoint {
                         Checkpoint::ValAcc { save_path } => {
-                            let score_validation: Matrix = self.evaluate(&validation_data, false);
+                            let score_validation: Matrix = self.forward(validation_data, false);
                             let acc_validation: f64 =
-                                self.accuracy(&score_validation, &validation_label);
+                                self.accuracy(&score_validation, validation_label);
                             match best_val_acc {
                                 Some(prev) => {
                                     if acc_validation > prev {
-                                        save_model(self, save_path.to_string()).unwrap();
+                                        save_model(self, save_path.to_string())?;
                                         best_val_acc = Some(acc_validation);
                                     }
                                 }
@@ -257,13 +920,18 @@ impl Model {
                             }
                         }
                         Checkpoint::ValLoss { save_path } => {
-                            let score_validation: Matrix = self.evaluate(&validation_data, false);
-                            let (loss_validation, _): (f64, f64) =
-                                self.compute_loss(&score_validation, &validation_label, debug);
+                            let score_validation: Matrix =
+                                self.forward_logits(validation_data, false);
+                            let (loss_validation, _): (f64, f64) = self.compute_loss_from_logits(
+                                &score_validation,
+                                validation_label,
+                                validation_weights,
+                                debug,
+                            );
                             match best_val_loss {
                                 Some(prev) => {
                                     if loss_validation < prev {
-                                        save_model(self, save_path.to_string()).unwrap();
+                                        save_model(self, save_path.to_string())?;
                                         best_val_loss = Some(loss_validation);
                                     }
                                 }
@@ -272,35 +940,84 @@ impl Model {
                                 }
                             }
                         }
+                        Checkpoint::ValMetric { save_path, metric } => {
+                            let score_validation: Matrix = self.forward(validation_data, false);
+                            let metric_validation: f64 = metric
+                                .compute(&score_validation, metric_label.as_ref().unwrap());
+                            match best_val_metric {
+                                Some(prev) => {
+                                    let improved: bool = if metric.higher_is_better() {
+                                        metric_validation > prev
+                                    } else {
+                                        metric_validation < prev
+                                    };
+                                    if improved {
+                                        save_model(self, save_path.to_string())?;
+                                        best_val_metric = Some(metric_validation);
+                                    }
+                                }
+                                None => {
+                                    best_val_metric = Some(metric_validation);
+                                }
+                            }
+                        }
                     },
                     None => (),
                 }
 
-                if ((batch_row + 1) % print_frequency == 0 || batch_row + 1 == index_matrix.len())
+                if ((batch_row + 1) % print_frequency == 0 || batch_row + 1 == number_of_batches)
                     && !debug
                     && !silent_mode
                 {
-                    let score_validation: Matrix = self.evaluate(&validation_data, false);
-                    let (loss_validation, _): (f64, f64) =
-                        self.compute_loss(&score_validation, &validation_label, debug);
-                    let (loss_training, l2_reg_penalty_training): (f64, f64) =
-                        self.compute_loss(&score, &batch_label, debug);
+                    let (loss_training, l2_reg_penalty_training): (f64, f64) = self
+                        .compute_loss_from_logits(
+                            &logits,
+                            &batch_label,
+                            batch_weights.as_ref(),
+                            debug,
+                        );
                     let acc_training: f64 = self.accuracy(&score, &batch_label);
-                    let acc_validation: f64 = self.accuracy(&score_validation, &validation_label);
+
+                    // the validation columns are only printed with validation data
+                    let validation_columns: String = if has_validation {
+                        let score_validation: Matrix =
+                            self.forward_logits(validation_data, false);
+                        let (loss_validation, _): (f64, f64) = self.compute_loss_from_logits(
+                            &score_validation,
+                            validation_label,
+                            validation_weights,
+                            debug,
+                        );
+                        let acc_validation: f64 = self.accuracy(
+                            &self.output_activation(&score_validation),
+                            validation_label,
+                        );
+                        format!(
+                            ", Val_loss : {}, Val_acc : {}",
+                            loss_validation, acc_validation
+                        )
+                    } else {
+                        String::new()
+                    };
 
                     println!(
-                        "Epoch : {}, Batch : {}, Loss : {}, L2 reg penalty {} , Acc {}, Val_loss : {}, Val_acc : {}",
+                        "Epoch : {}, Batch : {}, Loss : {}, L2 reg penalty {} , Acc {}{}, Grad_norm : {}",
                         epoch + 1,
                         batch_row + 1,
                         loss_training,
                         l2_reg_penalty_training,
                         acc_training,
-                        loss_validation,
-                        acc_validation
+                        validation_columns,
+                        self.history.gradient_norms.last().unwrap_or(&0.0)
                     );
                 }
 
                 iteration += 1;
+                Ok(true)
+            })?;
+
+            if stop_training {
+                break;
             }
         }
 
@@ -309,27 +1026,167 @@ impl Model {
                 Some(checkpoint) => {
                     match checkpoint {
                         Checkpoint::ValAcc { save_path } => println!("The best model has been saved at the path : {} it's validation accuracy is : {}", save_path, best_val_acc.unwrap_or(0.0)),
-                        Checkpoint::ValLoss { save_path } => println!("The best model has been saved at the path : {} it's validation loss is : {}", save_path, best_val_loss.unwrap_or(0.0))
+                        Checkpoint::ValLoss { save_path } => println!("The best model has been saved at the path : {} it's validation loss is : {}", save_path, best_val_loss.unwrap_or(0.0)),
+                        Checkpoint::ValMetric { save_path, metric } => println!("The best model has been saved at the path : {} it's validation {:?} is : {}", save_path, metric, best_val_metric.unwrap_or(0.0))
                     }
                 },
                 None => ()
             }
         }
 
-        network_history
+        Ok(network_history)
     }
 
-    pub fn accuracy(&mut self, score: &Matrix, labels: &Matrix) -> f64 {
-        let answer = Self::evaluation_output(&score);
+    // checks the layers, the width of the data and the number of labels
+    pub fn check_data_shape(&self, data: &Matrix, labels: &Matrix) -> Result<(), ModelBuildError> {
+        check_layer_shapes(&self.layers, None)?;
+
+        // raw data when the model has a preprocessing pipeline
+        let expected_width: usize = self.input_width();
+        if data.width != expected_width {
+            return Err(ModelBuildError::InputWidthMismatch {
+                expected_width,
+                width: data.width,
+            });
+        }
+
+        let number_of_labels: usize = match self.target_mode {
+            TargetMode::ClassIndex => labels.width,
+            _ => labels.height,
+        };
+        if number_of_labels != data.height {
+            return Err(ModelBuildError::LabelsMismatch {
+                samples: data.height,
+                labels: number_of_labels,
+            });
+        }
+
+        Ok(())
+    }
+
+    // records the issue, dumps the batch if requested and returns true
+    // if the training should stop
+    fn handle_numerical_issue(
+        &mut self,
+        issue: NumericalIssue,
+        batch_data: &Matrix,
+        batch_label: &Matrix,
+        gradients: &[LayerGradients],
+        silent_mode: bool,
+    ) -> bool {
+        let guard: NumericalGuard = match &self.numerical_guard {
+            Some(guard) => guard.clone(),
+            None => return false,
+        };
 
-        let mut sum = 0;
-        for index in 0..answer.width {
-            if answer.get(0, index) == labels.get(0, index) {
-                sum += 1;
+        if let Some(dump_path) = guard.dump_path() {
+            if let Err(e) = dump(
+                dump_path,
+                self,
+                &issue.kind,
+                batch_data,
+                batch_label,
+                gradients,
+            ) {
+                if !silent_mode {
+                    println!("Could not dump the offending batch : {}", e);
+                }
+            }
+        }
+
+        let stop: bool = matches!(guard, NumericalGuard::Stop { .. });
+        if !silent_mode {
+            if stop {
+                println!("{}, stopping the training", issue);
+            } else {
+                println!("{}, skipping the batch", issue);
             }
         }
 
-        sum as f64 / answer.width as f64
+        self.history.numerical_issues.push(issue);
+
+        stop
+    }
+
+    // the frozen layers stay frozen once saved and loaded again
+    pub fn set_trainable(&mut self, layer_index: usize, trainable: bool) {
+        assert!(
+            layer_index < self.layers.len(),
+            "Error : layer index {} out of bound, the model has {} layers",
+            layer_index,
+            self.layers.len()
+        );
+
+        self.layers[layer_index].trainable = trainable;
+    }
+
+    // for fine-tuning, only the output layer will be trained
+    pub fn freeze_all_but_output(&mut self) {
+        let last_index: usize = self.layers.len() - 1;
+        self.layers
+            .iter_mut()
+            .enumerate()
+            .for_each(|(index, layer)| layer.trainable = index == last_index);
+    }
+
+    pub fn replace_output_layer(&mut self, layer: Layer) {
+        let last_index: usize = self.layers.len() - 1;
+        let expected_input_size: usize = self.layers[last_index].weights_t.height;
+        assert_eq!(
+            layer.weights_t.height, expected_input_size,
+            "Error : the new output layer should have {} inputs",
+            expected_input_size
+        );
+
+        self.layers[last_index] = layer;
+    }
+
+    // replaces the output layer with a new randomly initialized one,
+    // e.g. to retrain a loaded model on a new label set
+    pub fn replace_output_head(&mut self, number_of_classes: u32) {
+        let last_index: usize = self.layers.len() - 1;
+        let input_size: u32 = self.layers[last_index].weights_t.height as u32;
+
+        self.replace_output_layer(Layer::init(input_size, number_of_classes, false));
+    }
+
+    // layers, shapes, parameter counts and memory, printable with println!("{}", summary)
+    pub fn summary(&self) -> ModelSummary {
+        ModelSummary::init(self)
+    }
+
+    // score : output of evaluate
+    // with soft targets the most probable class of the target is the expected answer
+    // in the multi-label mode this is the subset accuracy
+    pub fn accuracy(&self, score: &Matrix, labels: &Matrix) -> f64 {
+        let labels: Matrix = match &self.target_mode {
+            TargetMode::ClassIndex => labels.clone(),
+            TargetMode::Probabilities => Self::evaluation_output(labels),
+            TargetMode::MultiLabel { thresholds } => {
+                return subset_accuracy(&Self::multi_label_output(score, thresholds), labels);
+            }
+        };
+
+        accuracy(score, &labels)
+    }
+
+    // 1 where the probability is >= the threshold of the class, 0 elsewhere
+    pub fn multi_label_output(probabilities: &Matrix, thresholds: &[f64]) -> Matrix {
+        assert_eq!(
+            probabilities.width,
+            thresholds.len(),
+            "There should be one threshold per class"
+        );
+        let mut output: Matrix = Matrix::init_zero(probabilities.height, probabilities.width);
+        for r in 0..probabilities.height {
+            for (c, threshold) in thresholds.iter().enumerate() {
+                if probabilities.get(r, c) >= *threshold {
+                    output.set(1.0, r, c);
+                }
+            }
+        }
+
+        output
     }
 
     pub fn evaluation_output(score: &Matrix) -> Matrix {
@@ -353,6 +1210,17 @@ impl Model {
 #[cfg(test)]
 mod tests {
     use super::{Matrix, Model};
+    use crate::{
+        checkpoint::Checkpoint,
+        dataset::{DataLoader, MatrixDataset},
+        error::BriqueError,
+        layers::Layer,
+        loss::TargetMode,
+        metrics::Metric,
+        optimizer::Optimizer,
+        preprocessing::{Pipeline, Preprocessor},
+        validation::ValidationSplit,
+    };
 
     fn get_test_matrix() -> Matrix {
         let matrix = Matrix::init(2, 3, vec![0.1, 1.3, 0.5, 12.0, 1.01, -1000.0]);
@@ -367,4 +1235,444 @@ mod tests {
 
         assert!(expected_output.is_equal(&output, 10));
     }
+
+    #[test]
+    fn frozen_layers_are_not_updated() {
+        let mut model = Model::init(
+            vec![
+                Layer::init(3, 4, true),
+                Layer::init(4, 4, true),
+                Layer::init(4, 2, false),
+            ],
+            Optimizer::Adam {
+                learning_step: 0.01,
+                beta1: 0.9,
+                beta2: 0.999,
+            },
+            0.001,
+        );
+        model.replace_output_head(5);
+        model.freeze_all_but_output();
+        let initial_model = model.clone();
+
+        let data = Matrix::init(2, 3, vec![0.1, 0.5, -0.3, 1.0, -0.2, 0.4]);
+        let labels = Matrix::init(1, 2, vec![4.0, 1.0]);
+        model.train(&data, &labels, 2, 3, 0, None, 10, false, true);
+
+        for index in 0..2 {
+            assert!(model.layers[index]
+                .weights_t
+                .is_equal(&initial_model.layers[index].weights_t, 15));
+            assert!(model.layers[index]
+                .biases
+                .is_equal(&initial_model.layers[index].biases, 15));
+            assert!(model.layers[index].first_moment_weight.is_none());
+        }
+        assert_eq!(model.layers[2].weights_t.width, 5);
+        assert!(!model.layers[2]
+            .weights_t
+            .is_equal(&initial_model.layers[2].weights_t, 15));
+    }
+
+    #[test]
+    fn gradients_are_clipped_by_global_norm() {
+        // fixed weights, a random initialization can leave every relu inactive
+        // and give a gradient of norm 0
+        let layers = vec![
+            Layer::init_with_data(
+                Matrix::init(
+                    3,
+                    4,
+                    vec![
+                        0.5, -0.2, 0.3, 0.1, -0.4, 0.6, 0.2, -0.3, 0.1, 0.2, -0.5, 0.4,
+                    ],
+                ),
+                Matrix::init(1, 4, vec![0.1, 0.1, 0.1, 0.1]),
+                true,
+            ),
+            Layer::init_with_data(
+                Matrix::init(4, 2, vec![0.3, -0.3, -0.2, 0.4, 0.5, -0.1, 0.1, 0.2]),
+                Matrix::init_zero(1, 2),
+                false,
+            ),
+        ];
+        let mut model = Model::init(layers, Optimizer::SGD { learning_step: 1.0 }, 0.0);
+        model.clip_norm = Some(0.01);
+        let initial_model = model.clone();
+
+        let data = Matrix::init(2, 3, vec![0.1, 0.5, -0.3, 1.0, -0.2, 0.4]);
+        let labels = Matrix::init(1, 2, vec![0.0, 1.0]);
+        model.train(&data, &labels, 2, 1, 0, None, 10, false, true);
+
+        assert_eq!(model.history.gradient_norms.len(), 1);
+        assert!(model.history.gradient_norms[0] > 0.01);
+
+        // with a learning step of 1, the update is the clipped gradient
+        let update_norm: f64 = (0..2)
+            .map(|i| {
+                model.layers[i]
+                    .weights_t
+                    .add_two_matrices(&initial_model.layers[i].weights_t.mult(-1.0))
+                    .pow(2)
+                    .sum()
+                    + model.layers[i]
+                        .biases
+                        .add_two_matrices(&initial_model.layers[i].biases.mult(-1.0))
+                        .pow(2)
+                        .sum()
+            })
+            .sum::<f64>()
+            .sqrt();
+        assert!((update_norm - 0.01).abs() < 1e-10);
+    }
+
+    #[test]
+    fn multi_label_training() {
+        // label 0 : x > 0, label 1 : y > 0
+        let mut data = Matrix::init_zero(40, 2);
+        let mut targets = Matrix::init_zero(40, 2);
+        for i in 0..40 {
+            let x: f64 = (i % 4) as f64 - 1.5;
+            let y: f64 = (i / 4) as f64 / 4.5 - 1.0;
+            data.set_row(&vec![x, y], i);
+            targets.set_row(&vec![(x > 0.0) as u8 as f64, (y > 0.0) as u8 as f64], i);
+        }
+
+        let mut model = Model::init(
+            vec![Layer::init(2, 2, false)],
+            Optimizer::SGD { learning_step: 0.1 },
+            0.0,
+        );
+        model.target_mode = TargetMode::MultiLabel {
+            thresholds: vec![0.5, 0.5],
+        };
+        model.train(&data, &targets, 10, 200, 0, None, 10, false, true);
+
+        let predictions = model.predict_multi_label(&data);
+        assert!(predictions.is_equal(&targets, 15));
+
+        let probabilities = model.evaluate(&data, false);
+        assert!(probabilities.get(0, 0) < 0.5 && probabilities.get(0, 1) < 0.5);
+        assert_eq!(model.accuracy(&probabilities, &targets), 1.0);
+    }
+
+    #[test]
+    fn concurrent_predictions() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Model>();
+
+        let mut model = Model::init(
+            vec![Layer::init(3, 4, true), Layer::init(4, 2, false)],
+            Optimizer::SGD { learning_step: 0.1 },
+            0.0,
+        );
+        let data = Matrix::init(2, 3, vec![0.1, 0.5, -0.3, 1.0, -0.2, 0.4]);
+        let expected = model.evaluate(&data, false);
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| (model.predict_proba(&data), model.predict_classes(&data))))
+                .collect();
+
+            for handle in handles {
+                let (probabilities, classes) = handle.join().unwrap();
+                assert!(probabilities.is_equal(&expected, 15));
+                assert!(classes.is_equal(&Model::evaluation_output(&expected), 15));
+            }
+        });
+    }
+
+    #[test]
+    fn embeddings() {
+        let mut model = Model::init(
+            vec![
+                Layer::init(3, 4, true),
+                Layer::init(4, 5, true),
+                Layer::init(5, 2, false),
+            ],
+            Optimizer::SGD { learning_step: 0.1 },
+            0.0,
+        );
+        let data = Matrix::init(2, 3, vec![0.1, 0.5, -0.3, 1.0, -0.2, 0.4]);
+        model.evaluate(&data, false);
+
+        let embedding = model.embed(&data, 1);
+        assert_eq!((embedding.height, embedding.width), (2, 5));
+        assert!(embedding.is_equal(&model.layers[1].output, 15));
+
+        let embeddings = model.embed_layers(&data, &[2, 0]);
+        assert!(embeddings[0].is_equal(&model.layers[2].output, 15));
+        assert!(embeddings[1].is_equal(&model.layers[0].output, 15));
+    }
+
+    #[test]
+    fn training_errors_are_returned() {
+        let mut model = Model::init(
+            vec![Layer::init(3, 4, true), Layer::init(4, 2, false)],
+            Optimizer::SGD { learning_step: 0.1 },
+            0.0,
+        );
+        let data = Matrix::init(3, 3, vec![0.1, 0.5, -0.3, 1.0, -0.2, 0.4, 0.0, 0.3, 0.3]);
+        let labels = Matrix::init(1, 3, vec![0.0, 1.0, 1.0]);
+
+        let result = model.try_train(&data, &labels, 0, 1, 0, None, 10, false, true);
+        assert!(matches!(result, Err(BriqueError::Config(_))));
+
+        let result = model.try_train(&data, &labels, 2, 1, 3, None, 10, false, true);
+        assert!(matches!(result, Err(BriqueError::Config(_))));
+
+        // identical samples, the validation loss decreases at every step
+        let data = Matrix::init(3, 3, vec![0.1, 0.5, -0.3, 0.1, 0.5, -0.3, 0.1, 0.5, -0.3]);
+        let labels = Matrix::init(1, 3, vec![1.0, 1.0, 1.0]);
+        let checkpoint = Checkpoint::ValLoss {
+            save_path: "directory_that_does_not_exist/model".to_string(),
+        };
+        let result = model.try_train(&data, &labels, 1, 3, 1, Some(checkpoint), 10, false, true);
+        assert!(matches!(result, Err(BriqueError::Io(_))));
+
+        let input = Matrix::init(1, 2, vec![0.1, 0.5]);
+        assert!(matches!(
+            model.try_predict(&input),
+            Err(BriqueError::Shape(_))
+        ));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn loss_from_probabilities() {
+        let mut model = Model::init(
+            vec![Layer::init(3, 3, false)],
+            Optimizer::SGD { learning_step: 0.1 },
+            0.01,
+        );
+        let logits = Matrix::init(2, 3, vec![1.0, -2.0, 0.5, 0.0, 3.0, -1.0]);
+        let labels = Matrix::init(1, 2, vec![2.0, 1.0]);
+
+        let expected = model.compute_loss_from_logits(&logits, &labels, None, false);
+        let loss = model.compute_loss(&model.output_activation(&logits), &labels, false);
+        assert!((loss.0 - expected.0).abs() < 1e-12);
+        assert_eq!(loss.1, expected.1);
+
+        // the gradient from the probabilities is softmax - one_hot
+        let probabilities = model.output_activation(&logits);
+        let expected = Model::compute_d_logits(
+            &logits,
+            &labels,
+            None,
+            &model.loss_function,
+            &model.target_mode,
+        );
+        assert!(Model::compute_d_score(&probabilities, &labels).is_equal(&expected, 12));
+        assert!((expected.get(0, 2) - (probabilities.get(0, 2) - 1.0)).abs() < 1e-12);
+
+        model.target_mode = TargetMode::MultiLabel {
+            thresholds: vec![0.5; 3],
+        };
+        let labels = Matrix::init(2, 3, vec![1.0, 0.0, 1.0, 0.0, 1.0, 0.0]);
+        let expected = model.compute_loss_from_logits(&logits, &labels, None, false);
+        let loss = model.compute_loss(&model.output_activation(&logits), &labels, false);
+        assert!((loss.0 - expected.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn metric_checkpoint_with_probability_targets() {
+        let mut model = Model::init(
+            vec![Layer::init(3, 4, true), Layer::init(4, 2, false)],
+            Optimizer::SGD { learning_step: 0.1 },
+            0.0,
+        );
+        model.target_mode = TargetMode::Probabilities;
+        let data = Matrix::init(
+            6,
+            3,
+            vec![
+                0.1, 0.5, -0.3, 1.0, -0.2, 0.4, 0.0, 0.3, 0.3, 0.2, 0.1, 0.0, -0.5, 0.2, 0.1, 0.3,
+                0.3, -0.1,
+            ],
+        );
+        let labels = Matrix::init(
+            6,
+            2,
+            vec![0.9, 0.1, 0.2, 0.8, 0.0, 1.0, 0.6, 0.4, 1.0, 0.0, 0.3, 0.7],
+        );
+        let save_path: String = std::env::temp_dir()
+            .join("brique_metric_checkpoint")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        // 3 validation samples and 2 classes
+        let checkpoint = Checkpoint::ValMetric {
+            save_path: save_path.clone(),
+            metric: Metric::Accuracy,
+        };
+        let result = model.try_train(&data, &labels, 2, 5, 3, Some(checkpoint), 10, false, true);
+        let _ = std::fs::remove_file(save_path.clone() + ".brq");
+        assert!(result.is_ok());
+
+        model.target_mode = TargetMode::MultiLabel {
+            thresholds: vec![0.5, 0.5],
+        };
+        let checkpoint = Checkpoint::ValMetric {
+            save_path,
+            metric: Metric::Accuracy,
+        };
+        let result = model.try_train(&data, &labels, 2, 5, 3, Some(checkpoint), 10, false, true);
+        assert!(matches!(result, Err(BriqueError::Config(_))));
+    }
+
+    #[test]
+    fn explicit_validation_dataset() {
+        let mut model = Model::init(
+            vec![Layer::init(3, 4, true), Layer::init(4, 2, false)],
+            Optimizer::SGD { learning_step: 0.1 },
+            0.0,
+        );
+        let data = Matrix::init(3, 3, vec![0.1, 0.5, -0.3, 1.0, -0.2, 0.4, 0.0, 0.3, 0.3]);
+        let labels = Matrix::init(1, 3, vec![0.0, 1.0, 1.0]);
+
+        let validation = ValidationSplit::Explicit {
+            data: Matrix::init(1, 3, vec![0.2, 0.1, 0.0]),
+            labels: Matrix::init(1, 1, vec![1.0]),
+        };
+        let result = model.try_train_with_sample_weights(
+            &data, &labels, None, 3, 2, validation, None, 10, false, true,
+        );
+        assert!(result.is_ok());
+
+        let validation = ValidationSplit::Explicit {
+            data: Matrix::init(1, 2, vec![0.2, 0.1]),
+            labels: Matrix::init(1, 1, vec![1.0]),
+        };
+        let result = model.try_train_with_sample_weights(
+            &data, &labels, None, 3, 2, validation, None, 10, false, true,
+        );
+        assert!(matches!(result, Err(BriqueError::Shape(_))));
+    }
+
+    #[test]
+    fn preprocessing_is_applied_by_evaluate_and_training() {
+        // the second column is one-hot encoded, the first layer expects 4 columns
+        let raw = Matrix::init(4, 2, vec![0.5, 0.0, -1.0, 2.0, 1.5, 1.0, 0.0, 2.0]);
+        let labels = Matrix::init(1, 4, vec![0.0, 1.0, 1.0, 0.0]);
+        let (pipeline, transformed) = Pipeline::new()
+            .add_step(Preprocessor::OneHotEncoder { columns: vec![1] })
+            .add_step(Preprocessor::StandardScaler)
+            .fit_transform(&raw)
+            .unwrap();
+
+        let layers = vec![Layer::init(4, 3, true), Layer::init(3, 2, false)];
+        let mut model = Model::init(layers.clone(), Optimizer::SGD { learning_step: 0.1 }, 0.0);
+        model.set_preprocessing(pipeline).unwrap();
+        assert!(model
+            .evaluate(&raw, false)
+            .is_equal(&model.predict_proba(&raw), 12));
+
+        // training on the raw data is training on the transformed data
+        let mut reference = Model::init(layers, Optimizer::SGD { learning_step: 0.1 }, 0.0);
+        let raw_dataset = MatrixDataset::init(&raw, &labels);
+        let transformed_dataset = MatrixDataset::init(&transformed, &labels);
+        model
+            .try_train_on_loader(
+                &DataLoader::init(&raw_dataset, 2).seed(3),
+                Some(&raw_dataset),
+                2,
+                None,
+                10,
+                false,
+                true,
+            )
+            .unwrap();
+        reference
+            .try_train_on_loader(
+                &DataLoader::init(&transformed_dataset, 2).seed(3),
+                Some(&transformed_dataset),
+                2,
+                None,
+                10,
+                false,
+                true,
+            )
+            .unwrap();
+        for (a, b) in model.layers.iter().zip(reference.layers.iter()) {
+            assert!(a.weights_t.is_equal(&b.weights_t, 12));
+            assert!(a.biases.is_equal(&b.biases, 12));
+        }
+
+        // the matrix api also takes the raw data
+        model
+            .try_train(&raw, &labels, 2, 1, 1, None, 10, false, true)
+            .unwrap();
+    }
+
+    #[test]
+    fn training_on_a_data_loader() {
+        let layers = vec![Layer::init(3, 4, true), Layer::init(4, 2, false)];
+        let data = Matrix::init(3, 3, vec![0.1, 0.5, -0.3, 1.0, -0.2, 0.4, 0.0, 0.3, 0.3]);
+        let labels = Matrix::init(1, 3, vec![0.0, 1.0, 1.0]);
+        let dataset = MatrixDataset::init(&data, &labels);
+
+        // the same batches give the same parameters, with or without prefetching
+        let mut model = Model::init(layers.clone(), Optimizer::SGD { learning_step: 0.1 }, 0.0);
+        let loader = DataLoader::init(&dataset, 2).seed(1);
+        model
+            .try_train_on_loader(&loader, Some(&dataset), 3, None, 10, false, true)
+            .unwrap();
+
+        let mut prefetched = Model::init(layers, Optimizer::SGD { learning_step: 0.1 }, 0.0);
+        let loader = DataLoader::init(&dataset, 2).seed(1).prefetch(2);
+        prefetched
+            .try_train_on_loader(&loader, None, 3, None, 10, false, true)
+            .unwrap();
+
+        for (a, b) in model.layers.iter().zip(prefetched.layers.iter()) {
+            assert!(a.weights_t.is_equal(&b.weights_t, 15));
+            assert!(a.biases.is_equal(&b.biases, 15));
+        }
+
+        let narrow = Matrix::init(3, 2, vec![0.1, 0.5, 1.0, -0.2, 0.0, 0.3]);
+        let narrow_dataset = MatrixDataset::init(&narrow, &labels);
+        let result = model.try_train_on_loader(
+            &DataLoader::init(&narrow_dataset, 2),
+            None,
+            1,
+            None,
+            10,
+            false,
+            true,
+        );
+        assert!(matches!(result, Err(BriqueError::Build(_))));
+
+        // a clipping threshold set after the build
+        model.clip_norm = Some(-1.0);
+        let result = model.try_train(&data, &labels, 2, 1, 0, None, 10, false, true);
+        assert!(matches!(result, Err(BriqueError::Config(_))));
+        model.clip_norm = None;
+        model.clip_value = Some(0.0);
+        let result = model.try_train_on_loader(
+            &DataLoader::init(&dataset, 2),
+            None,
+            1,
+            None,
+            10,
+            false,
+            true,
+        );
+        assert!(matches!(result, Err(BriqueError::Config(_))));
+        model.clip_value = None;
+
+        // a checkpoint without validation data
+        let result = model.try_train_on_loader(
+            &DataLoader::init(&dataset, 2),
+            None,
+            1,
+            Some(Checkpoint::ValLoss {
+                save_path: "test_loader_checkpoint".to_string(),
+            }),
+            10,
+            false,
+            true,
+        );
+        assert!(matches!(result, Err(BriqueError::Config(_))));
+    }
 }
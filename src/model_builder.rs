@@ -1,6 +1,16 @@
 use crate::{
-    checkpoint::Checkpoint, layers::Layer, matrix::Matrix, model::Model, optimizer::Optimizer,
+    checkpoint::Checkpoint,
+    error::{BriqueError, ModelBuildError},
+    initializer::Initializer,
+    layers::Layer,
+    loss::{ClassificationLoss, TargetMode},
+    matrix::Matrix,
+    model::{check_layer_shapes, Model},
+    numerical_guard::NumericalGuard,
+    optimizer::Optimizer,
+    validation::ValidationSplit,
 };
+use rand::{rngs::StdRng, SeedableRng};
 
 const DEFAULT_LAMBDA: f64 = 0.001;
 const DEFAULT_OPTIMIZER: Optimizer = Optimizer::SGD {
@@ -10,11 +20,33 @@ const DEFAULT_PRINT_FREQUENCY: usize = 100;
 const DEFAULT_SILENT_MODE: bool = false;
 const DEFAULT_DEBUG: bool = false;
 
+#[derive(Clone)]
+enum LayerDefinition {
+    // layer added already initialized
+    Layer(Box<Layer>),
+    // initialized when the model is built, with the rng seeded by the seed option
+    // the input size is the output size of the previous layer when None
+    Uninitialized {
+        input_size: Option<u32>,
+        size: u32,
+        relu: bool,
+        weights_initializer: Initializer,
+        biases_initializer: Initializer,
+    },
+}
+
 #[derive(Clone)]
 pub struct ModelBuilder {
-    layers: Vec<Layer>,
+    layers: Vec<LayerDefinition>,
+    user_defined_input_size: Option<u32>,
+    user_defined_seed: Option<u64>,
     user_defined_lambda: Option<f64>,
     user_defined_optimizer: Option<Optimizer>,
+    user_defined_clip_value: Option<f64>,
+    user_defined_clip_norm: Option<f64>,
+    numerical_guard: Option<NumericalGuard>,
+    loss_function: ClassificationLoss,
+    target_mode: TargetMode,
     checkpoint: Option<Checkpoint>,
     user_defined_print_frequency: Option<usize>,
     user_defined_debug: Option<bool>,
@@ -25,17 +57,86 @@ impl ModelBuilder {
     pub fn new() -> ModelBuilder {
         ModelBuilder {
             layers: vec![],
+            user_defined_input_size: None,
+            user_defined_seed: None,
             user_defined_debug: None,
             user_defined_silent_mode: None,
             user_defined_print_frequency: None,
             user_defined_optimizer: None,
             user_defined_lambda: None,
+            user_defined_clip_value: None,
+            user_defined_clip_norm: None,
+            numerical_guard: None,
+            loss_function: ClassificationLoss::default(),
+            target_mode: TargetMode::ClassIndex,
             checkpoint: None,
         }
     }
 
     pub fn add_layer(mut self, layer: Layer) -> ModelBuilder {
-        self.layers.push(layer);
+        self.layers.push(LayerDefinition::Layer(Box::new(layer)));
+        self
+    }
+
+    // number of features of the data, checked against the first layer
+    // and used as the input size of a first layer added with dense
+    pub fn input_size(mut self, input_size: u32) -> ModelBuilder {
+        self.user_defined_input_size = Some(input_size);
+        self
+    }
+
+    // the input size is the size of the previous layer (or the model input size),
+    // the weights are initialized with He normal and the biases with zeros
+    pub fn dense(self, size: u32, relu: bool) -> ModelBuilder {
+        self.dense_with_initializer(
+            size,
+            relu,
+            Initializer::HeNormal,
+            Initializer::Constant(0.0),
+        )
+    }
+
+    pub fn dense_with_initializer(
+        mut self,
+        size: u32,
+        relu: bool,
+        weights_initializer: Initializer,
+        biases_initializer: Initializer,
+    ) -> ModelBuilder {
+        self.layers.push(LayerDefinition::Uninitialized {
+            input_size: None,
+            size,
+            relu,
+            weights_initializer,
+            biases_initializer,
+        });
+        self
+    }
+
+    // the parameters are initialized when the model is built
+    // with the rng seeded by the seed option
+    pub fn add_layer_with_initializer(
+        mut self,
+        input_size: u32,
+        size: u32,
+        relu: bool,
+        weights_initializer: Initializer,
+        biases_initializer: Initializer,
+    ) -> ModelBuilder {
+        self.layers.push(LayerDefinition::Uninitialized {
+            input_size: Some(input_size),
+            size,
+            relu,
+            weights_initializer,
+            biases_initializer,
+        });
+        self
+    }
+
+    // seed of the initializers, layers added with add_layer are not affected
+    // also seeds the shuffles of the training, the validation split and the cross-validation
+    pub fn seed(mut self, seed: u64) -> ModelBuilder {
+        self.user_defined_seed = Some(seed);
         self
     }
 
@@ -49,6 +150,49 @@ impl ModelBuilder {
         self
     }
 
+    // every element of the gradients is clamped in [-max_value, max_value]
+    pub fn clip_gradients_by_value(mut self, max_value: f64) -> ModelBuilder {
+        self.user_defined_clip_value = Some(max_value);
+        self
+    }
+
+    // the gradients of all the layers are scaled down together when their
+    // global L2 norm is greater than max_norm
+    pub fn clip_gradients_by_norm(mut self, max_norm: f64) -> ModelBuilder {
+        self.user_defined_clip_norm = Some(max_norm);
+        self
+    }
+
+    // the loss of every sample is multiplied by the weight of its class
+    pub fn class_weights(mut self, class_weights: Vec<f64>) -> ModelBuilder {
+        self.loss_function.class_weights = Some(class_weights);
+        self
+    }
+
+    // focal loss, down weights the well classified samples
+    // gamma = 0 is equivalent to the cross entropy
+    pub fn focal_loss(mut self, gamma: f64) -> ModelBuilder {
+        self.loss_function.focal_gamma = Some(gamma);
+        self
+    }
+
+    // the one hot targets become (1 - epsilon) * one_hot + epsilon / number_of_classes
+    pub fn label_smoothing(mut self, epsilon: f64) -> ModelBuilder {
+        self.loss_function.label_smoothing = epsilon;
+        self
+    }
+
+    // format of the labels, class indexes by default
+    pub fn target_mode(mut self, target_mode: TargetMode) -> ModelBuilder {
+        self.target_mode = target_mode;
+        self
+    }
+
+    pub fn numerical_guard(mut self, numerical_guard: NumericalGuard) -> ModelBuilder {
+        self.numerical_guard = Some(numerical_guard);
+        self
+    }
+
     pub fn checkpoint(mut self, checkpoint: Checkpoint) -> ModelBuilder {
         self.checkpoint = Some(checkpoint);
         self
@@ -65,12 +209,39 @@ impl ModelBuilder {
         self
     }
 
+    // panics if the model is not valid, see try_build
+    // used by the hyperparameter search, which adds the layers of every trial itself
+    pub(crate) fn has_layers(&self) -> bool {
+        !self.layers.is_empty()
+    }
+
+    // the trials of a hyperparameter search would all write to the same checkpoint
+    pub(crate) fn without_checkpoint(mut self) -> ModelBuilder {
+        self.checkpoint = None;
+        self
+    }
+
     pub fn build(self) -> Model {
-        assert_ne!(
-            self.layers.len(),
-            0,
-            "Error : No layers have been added to the model"
-        );
+        match self.try_build() {
+            Ok(model) => model,
+            Err(e) => panic!("Error : {}", e),
+        }
+    }
+
+    pub fn try_build(self) -> Result<Model, BriqueError> {
+        if self.layers.is_empty() {
+            return Err(ModelBuildError::NoLayers.into());
+        }
+
+        // a negative or NaN threshold would make the clamp panic or flip the gradients
+        for value in [self.user_defined_clip_value, self.user_defined_clip_norm]
+            .iter()
+            .flatten()
+        {
+            if !value.is_finite() || *value <= 0.0 {
+                return Err(ModelBuildError::InvalidClipThreshold { value: *value }.into());
+            }
+        }
 
         let optimizer: Optimizer = match &self.user_defined_optimizer {
             Some(optimizer) => optimizer.clone(),
@@ -82,7 +253,57 @@ impl ModelBuilder {
             None => DEFAULT_LAMBDA,
         };
 
-        Model::init(self.layers.clone(), optimizer, lambda)
+        let mut rng: StdRng = match self.user_defined_seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+
+        let mut layers: Vec<Layer> = vec![];
+        for (index, definition) in self.layers.iter().enumerate() {
+            let layer: Layer = match definition {
+                LayerDefinition::Layer(layer) => (**layer).clone(),
+                LayerDefinition::Uninitialized {
+                    input_size,
+                    size,
+                    relu,
+                    weights_initializer,
+                    biases_initializer,
+                } => {
+                    let previous_size: Option<u32> = match layers.last() {
+                        Some(previous) => Some(previous.weights_t.width as u32),
+                        None => self.user_defined_input_size,
+                    };
+                    let input_size: u32 = match input_size.or(previous_size) {
+                        Some(input_size) => input_size,
+                        None => {
+                            return Err(ModelBuildError::MissingInputSize { layer: index }.into())
+                        }
+                    };
+
+                    Layer::init_with_initializer(
+                        input_size,
+                        *size,
+                        *relu,
+                        weights_initializer,
+                        biases_initializer,
+                        &mut rng,
+                    )
+                }
+            };
+            layers.push(layer);
+        }
+
+        check_layer_shapes(&layers, self.user_defined_input_size.map(|v| v as usize))?;
+
+        let mut model: Model = Model::init(layers, optimizer, lambda);
+        model.clip_value = self.user_defined_clip_value;
+        model.clip_norm = self.user_defined_clip_norm;
+        model.numerical_guard = self.numerical_guard.clone();
+        model.loss_function = self.loss_function.clone();
+        model.target_mode = self.target_mode.clone();
+        model.seed = self.user_defined_seed;
+
+        Ok(model)
     }
 
     pub fn build_and_train(
@@ -93,6 +314,29 @@ impl ModelBuilder {
         epochs: u32,
         validation_dataset_size: usize,
     ) {
+        if let Err(e) = self.try_build_and_train(
+            data,
+            labels,
+            batch_size,
+            epochs,
+            ValidationSplit::Random {
+                size: validation_dataset_size,
+            },
+        ) {
+            panic!("Error : {}", e);
+        }
+    }
+
+    // checks the model and the shape of the data before training, returns the trained model
+    // validation : how the validation dataset is built, see ValidationSplit
+    pub fn try_build_and_train(
+        self,
+        data: &Matrix,
+        labels: &Matrix,
+        batch_size: u32,
+        epochs: u32,
+        validation: ValidationSplit,
+    ) -> Result<Model, BriqueError> {
         let print_frequency: usize = match &self.user_defined_print_frequency {
             Some(v) => *v,
             None => DEFAULT_PRINT_FREQUENCY,
@@ -110,17 +354,170 @@ impl ModelBuilder {
 
         let checkpoint = self.checkpoint.clone();
 
-        let mut model: Model = self.build();
-        model.train(
+        let mut model: Model = self.try_build()?;
+        model.try_train_with_sample_weights(
             data,
             labels,
+            None,
             batch_size,
             epochs,
-            validation_dataset_size,
+            validation,
             checkpoint,
             print_frequency,
             debug,
             silent_mode,
+        )?;
+
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModelBuilder;
+    use crate::{
+        error::{BriqueError, ModelBuildError},
+        initializer::Initializer,
+        layers::Layer,
+        matrix::Matrix,
+        validation::ValidationSplit,
+    };
+
+    #[test]
+    fn mismatched_layers_are_rejected() {
+        let result = ModelBuilder::new()
+            .add_layer(Layer::init(3, 4, true))
+            .add_layer(Layer::init(5, 2, false))
+            .try_build();
+
+        assert_eq!(
+            result.err(),
+            Some(BriqueError::Build(ModelBuildError::LayerShapeMismatch {
+                layer: 1,
+                expected_input_size: 4,
+                input_size: 5,
+            }))
+        );
+
+        let result = ModelBuilder::new()
+            .input_size(2)
+            .add_layer(Layer::init(3, 4, true))
+            .try_build();
+
+        assert!(matches!(
+            result,
+            Err(BriqueError::Build(ModelBuildError::LayerShapeMismatch {
+                layer: 0,
+                ..
+            }))
+        ));
+        assert_eq!(
+            ModelBuilder::new().try_build().err(),
+            Some(BriqueError::Build(ModelBuildError::NoLayers))
+        );
+    }
+
+    #[test]
+    fn invalid_clip_thresholds_are_rejected() {
+        let result = ModelBuilder::new()
+            .add_layer(Layer::init(3, 2, false))
+            .clip_gradients_by_value(-1.0)
+            .try_build();
+        assert_eq!(
+            result.err(),
+            Some(BriqueError::Build(ModelBuildError::InvalidClipThreshold {
+                value: -1.0
+            }))
         );
+
+        let result = ModelBuilder::new()
+            .add_layer(Layer::init(3, 2, false))
+            .clip_gradients_by_norm(f64::NAN)
+            .try_build();
+        assert!(matches!(
+            result,
+            Err(BriqueError::Build(
+                ModelBuildError::InvalidClipThreshold { .. }
+            ))
+        ));
+
+        let result = ModelBuilder::new()
+            .add_layer(Layer::init(3, 2, false))
+            .clip_gradients_by_value(0.5)
+            .clip_gradients_by_norm(1.0)
+            .try_build();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn input_sizes_are_inferred() {
+        let model = ModelBuilder::new()
+            .input_size(3)
+            .dense(4, true)
+            .dense_with_initializer(
+                5,
+                true,
+                Initializer::XavierNormal,
+                Initializer::Constant(0.1),
+            )
+            .add_layer(Layer::init(5, 6, true))
+            .dense(2, false)
+            .seed(1)
+            .try_build()
+            .unwrap();
+
+        let shapes: Vec<(usize, usize)> = model
+            .layers
+            .iter()
+            .map(|layer| (layer.weights_t.height, layer.weights_t.width))
+            .collect();
+        assert_eq!(shapes, vec![(3, 4), (4, 5), (5, 6), (6, 2)]);
+
+        let result = ModelBuilder::new().dense(4, true).try_build();
+        assert_eq!(
+            result.err(),
+            Some(BriqueError::Build(ModelBuildError::MissingInputSize {
+                layer: 0
+            }))
+        );
+    }
+
+    #[test]
+    fn data_shape_is_checked_before_training() {
+        let data = Matrix::init(2, 3, vec![0.1, 0.5, -0.3, 1.0, -0.2, 0.4]);
+        let labels = Matrix::init(1, 2, vec![0.0, 1.0]);
+        let builder = ModelBuilder::new()
+            .input_size(4)
+            .dense(2, false)
+            .verbose(10, true);
+
+        assert_eq!(
+            builder
+                .try_build_and_train(&data, &labels, 2, 1, ValidationSplit::Random { size: 0 })
+                .err(),
+            Some(BriqueError::Build(ModelBuildError::InputWidthMismatch {
+                expected_width: 4,
+                width: 3,
+            }))
+        );
+
+        let result = ModelBuilder::new()
+            .input_size(3)
+            .dense(2, false)
+            .verbose(10, true)
+            .try_build_and_train(
+                &data,
+                &Matrix::init(1, 1, vec![0.0]),
+                2,
+                1,
+                ValidationSplit::Random { size: 0 },
+            );
+        assert!(matches!(
+            result,
+            Err(BriqueError::Build(ModelBuildError::LabelsMismatch {
+                samples: 2,
+                labels: 1
+            }))
+        ));
     }
 }
@@ -0,0 +1,216 @@
+use crate::layers::{Layer, LayerGradients};
+use crate::matrix::Matrix;
+use crate::model::Model;
+use crate::save_load::{save_model, ModelManagementError};
+use std::{fmt, fs};
+
+// checks the losses, activations and gradients during the training
+// when a NaN or an infinite value is found the offending batch can be dumped to
+// dump_path.brq (model state), dump_path_batch.csv, dump_path_labels.csv and
+// dump_path_layer_<index>.csv (the offending activation or gradient)
+#[derive(Clone, Debug)]
+pub enum NumericalGuard {
+    // stops the training cleanly at the first non finite value
+    Stop { dump_path: Option<String> },
+    // the parameters are not updated with the offending batch, the training continues
+    SkipBatch { dump_path: Option<String> },
+}
+
+impl NumericalGuard {
+    pub fn dump_path(&self) -> Option<&String> {
+        match self {
+            NumericalGuard::Stop { dump_path } => dump_path.as_ref(),
+            NumericalGuard::SkipBatch { dump_path } => dump_path.as_ref(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NumericalIssueKind {
+    Activation { layer: usize },
+    Loss,
+    WeightsGradient { layer: usize },
+    BiasesGradient { layer: usize },
+}
+
+// epoch and batch start at 1, like in the training logs
+#[derive(Clone, Debug)]
+pub struct NumericalIssue {
+    pub kind: NumericalIssueKind,
+    pub epoch: u32,
+    pub batch: usize,
+    pub iteration: i32,
+}
+
+impl fmt::Display for NumericalIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location: String = match &self.kind {
+            NumericalIssueKind::Activation { layer } => format!("activation of layer {}", layer),
+            NumericalIssueKind::Loss => "loss".to_string(),
+            NumericalIssueKind::WeightsGradient { layer } => {
+                format!("weights gradient of layer {}", layer)
+            }
+            NumericalIssueKind::BiasesGradient { layer } => {
+                format!("biases gradient of layer {}", layer)
+            }
+        };
+
+        write!(
+            f,
+            "Non finite value in the {}, epoch : {}, batch : {}, iteration : {}",
+            location, self.epoch, self.batch, self.iteration
+        )
+    }
+}
+
+pub fn is_finite(input: &Matrix) -> bool {
+    input.data.iter().all(|v| v.is_finite())
+}
+
+// checks the outputs cached in the layers by the last forward pass
+pub fn check_activations(layers: &[Layer]) -> Option<NumericalIssueKind> {
+    layers
+        .iter()
+        .position(|layer| !is_finite(&layer.output))
+        .map(|layer| NumericalIssueKind::Activation { layer })
+}
+
+pub fn check_gradients(gradients: &[LayerGradients]) -> Option<NumericalIssueKind> {
+    for (layer, layer_gradients) in gradients.iter().enumerate() {
+        if !is_finite(&layer_gradients.d_weights) {
+            return Some(NumericalIssueKind::WeightsGradient { layer });
+        }
+        if !is_finite(&layer_gradients.d_biases) {
+            return Some(NumericalIssueKind::BiasesGradient { layer });
+        }
+    }
+
+    None
+}
+
+pub fn dump(
+    dump_path: &str,
+    model: &Model,
+    kind: &NumericalIssueKind,
+    batch_data: &Matrix,
+    batch_labels: &Matrix,
+    gradients: &[LayerGradients],
+) -> Result<(), ModelManagementError> {
+    save_model(model, dump_path.to_string())?;
+    write_csv(&format!("{}_batch.csv", dump_path), batch_data)?;
+    write_csv(&format!("{}_labels.csv", dump_path), batch_labels)?;
+
+    let last_layer: usize = model.layers.len() - 1;
+    let (layer, offending_matrix): (usize, &Matrix) = match kind {
+        NumericalIssueKind::Activation { layer } => (*layer, &model.layers[*layer].output),
+        NumericalIssueKind::Loss => (last_layer, &model.layers[last_layer].output),
+        NumericalIssueKind::WeightsGradient { layer } => (*layer, &gradients[*layer].d_weights),
+        NumericalIssueKind::BiasesGradient { layer } => (*layer, &gradients[*layer].d_biases),
+    };
+
+    write_csv(
+        &format!("{}_layer_{}.csv", dump_path, layer),
+        offending_matrix,
+    )
+}
+
+fn write_csv(file_path: &str, input: &Matrix) -> Result<(), ModelManagementError> {
+    fs::write(file_path, input.convert_to_csv())
+        .map_err(|e| ModelManagementError::CouldNotSaveModel(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_activations, NumericalGuard, NumericalIssueKind};
+    use crate::{layers::Layer, matrix::Matrix, model::Model, optimizer::Optimizer};
+    use std::fs;
+
+    // the first layer overflows to infinity
+    fn get_exploding_model() -> Model {
+        Model::init(
+            vec![
+                Layer::init_with_data(
+                    Matrix::init(2, 2, vec![1e308, 1e308, 1e308, 1e308]),
+                    Matrix::init_zero(1, 2),
+                    true,
+                ),
+                Layer::init(2, 2, false),
+            ],
+            Optimizer::SGD { learning_step: 0.1 },
+            0.0,
+        )
+    }
+
+    fn get_data() -> (Matrix, Matrix) {
+        (
+            Matrix::init(4, 2, vec![1.0, 1.0, 2.0, 2.0, 3.0, 3.0, 4.0, 4.0]),
+            Matrix::init(1, 4, vec![0.0, 1.0, 0.0, 1.0]),
+        )
+    }
+
+    #[test]
+    fn stop_policy() {
+        let mut model = get_exploding_model();
+        model.numerical_guard = Some(NumericalGuard::Stop { dump_path: None });
+        let initial_model = model.clone();
+        let (data, labels) = get_data();
+
+        model.train(&data, &labels, 2, 3, 0, None, 10, false, true);
+
+        assert_eq!(model.history.numerical_issues.len(), 1);
+        let issue = &model.history.numerical_issues[0];
+        assert_eq!(issue.kind, NumericalIssueKind::Activation { layer: 0 });
+        assert_eq!((issue.epoch, issue.batch, issue.iteration), (1, 1, 1));
+        assert!(model.layers[1]
+            .weights_t
+            .is_equal(&initial_model.layers[1].weights_t, 15));
+    }
+
+    #[test]
+    fn skip_batch_policy() {
+        let mut model = get_exploding_model();
+        model.numerical_guard = Some(NumericalGuard::SkipBatch { dump_path: None });
+        let (data, labels) = get_data();
+
+        model.train(&data, &labels, 2, 3, 0, None, 10, false, true);
+
+        // every batch of every epoch is skipped
+        assert_eq!(model.history.numerical_issues.len(), 6);
+        assert_eq!(model.history.gradient_norms.len(), 0);
+    }
+
+    #[test]
+    fn dump_offending_batch() {
+        let dump_path: String = std::env::temp_dir()
+            .join("brique_numerical_guard_dump")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let mut model = get_exploding_model();
+        model.numerical_guard = Some(NumericalGuard::Stop {
+            dump_path: Some(dump_path.clone()),
+        });
+        let (data, labels) = get_data();
+
+        model.train(&data, &labels, 4, 1, 0, None, 10, false, true);
+
+        for suffix in [".brq", "_batch.csv", "_labels.csv", "_layer_0.csv"] {
+            let file_path: String = format!("{}{}", dump_path, suffix);
+            assert!(fs::metadata(&file_path).is_ok(), "{} not found", file_path);
+            fs::remove_file(file_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn finite_activations() {
+        let mut model = Model::init(
+            vec![Layer::init(2, 2, true)],
+            Optimizer::SGD { learning_step: 0.1 },
+            0.0,
+        );
+        let (data, _) = get_data();
+        model.evaluate(&data, false);
+
+        assert!(check_activations(&model.layers).is_none());
+    }
+}
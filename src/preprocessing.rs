@@ -0,0 +1,572 @@
+use crate::error::BriqueError;
+use crate::matrix::Matrix;
+
+// jacobi eigenvalue algorithm, stops after this number of sweeps at the latest
+const MAX_JACOBI_SWEEPS: usize = 100;
+
+// preprocessing steps, fitted on the training data then applied the same way to
+// every other input, unlike Matrix::normalize which scales in place with the global min and max
+#[derive(Clone, Debug, PartialEq)]
+pub enum Preprocessor {
+    // (x - mean) / standard deviation, per column
+    StandardScaler,
+    // (x - min) / (max - min), per column, in [0, 1] on the training data
+    MinMaxScaler,
+    // (x - median) / interquartile range, per column, less sensitive to outliers
+    RobustScaler,
+    // replaces each listed column of categories by one column per category seen during the fit,
+    // in ascending order, at the place of the column, unknown categories only give zeros
+    OneHotEncoder {
+        columns: Vec<usize>,
+    },
+    // centers the data and projects it on the principal components, scaled to unit variance
+    // keeps all the components if components is None
+    // epsilon is added to the eigenvalues before the scaling, to avoid dividing by 0
+    PcaWhitening {
+        components: Option<usize>,
+        epsilon: f64,
+    },
+}
+
+// parameters learned by Preprocessor::fit
+// the scalers compute (x - center) / scale, a constant column has a scale of 1
+#[derive(Clone)]
+pub enum FittedPreprocessor {
+    StandardScaler {
+        center: Vec<f64>,
+        scale: Vec<f64>,
+    },
+    MinMaxScaler {
+        center: Vec<f64>,
+        scale: Vec<f64>,
+    },
+    RobustScaler {
+        center: Vec<f64>,
+        scale: Vec<f64>,
+    },
+    OneHotEncoder {
+        input_width: usize,
+        columns: Vec<usize>,
+        categories: Vec<Vec<f64>>,
+    },
+    // projection : input width x number of components
+    PcaWhitening {
+        mean: Vec<f64>,
+        projection: Matrix,
+    },
+}
+
+impl Preprocessor {
+    pub fn fit(&self, data: &Matrix) -> Result<FittedPreprocessor, BriqueError> {
+        if data.height == 0 || data.width == 0 {
+            return Err(BriqueError::Config(
+                "a preprocessor can not be fitted on an empty matrix".to_string(),
+            ));
+        }
+        let columns: Vec<Vec<f64>> = (0..data.width)
+            .map(|column| (0..data.height).map(|row| data.get(row, column)).collect())
+            .collect();
+
+        let fitted: FittedPreprocessor = match self {
+            Preprocessor::StandardScaler => {
+                let center: Vec<f64> = columns.iter().map(|c| mean(c)).collect();
+                let scale: Vec<f64> = columns
+                    .iter()
+                    .zip(&center)
+                    .map(|(c, m)| {
+                        let variance: f64 =
+                            c.iter().map(|v| (v - m).powi(2)).sum::<f64>() / c.len() as f64;
+                        non_zero_scale(variance.sqrt())
+                    })
+                    .collect();
+
+                FittedPreprocessor::StandardScaler { center, scale }
+            }
+            Preprocessor::MinMaxScaler => {
+                let center: Vec<f64> = columns
+                    .iter()
+                    .map(|c| c.iter().cloned().fold(f64::INFINITY, f64::min))
+                    .collect();
+                let scale: Vec<f64> = columns
+                    .iter()
+                    .zip(&center)
+                    .map(|(c, min)| {
+                        non_zero_scale(c.iter().cloned().fold(f64::NEG_INFINITY, f64::max) - min)
+                    })
+                    .collect();
+
+                FittedPreprocessor::MinMaxScaler { center, scale }
+            }
+            Preprocessor::RobustScaler => {
+                let mut center: Vec<f64> = vec![];
+                let mut scale: Vec<f64> = vec![];
+                for column in &columns {
+                    let mut sorted: Vec<f64> = column.clone();
+                    sorted.sort_by(|a, b| a.total_cmp(b));
+                    center.push(quantile(&sorted, 0.5));
+                    scale.push(non_zero_scale(
+                        quantile(&sorted, 0.75) - quantile(&sorted, 0.25),
+                    ));
+                }
+
+                FittedPreprocessor::RobustScaler { center, scale }
+            }
+            Preprocessor::OneHotEncoder {
+                columns: encoded_columns,
+            } => {
+                let mut categories: Vec<Vec<f64>> = vec![];
+                for (index, column) in encoded_columns.iter().enumerate() {
+                    if *column >= data.width {
+                        return Err(BriqueError::Config(format!(
+                            "one-hot encoder : column {} out of range, the data has {} columns",
+                            column, data.width
+                        )));
+                    }
+                    if encoded_columns[..index].contains(column) {
+                        return Err(BriqueError::Config(format!(
+                            "one-hot encoder : column {} is listed twice",
+                            column
+                        )));
+                    }
+                    let mut values: Vec<f64> = columns[*column].clone();
+                    values.sort_by(|a, b| a.total_cmp(b));
+                    values.dedup();
+                    categories.push(values);
+                }
+
+                FittedPreprocessor::OneHotEncoder {
+                    input_width: data.width,
+                    columns: encoded_columns.clone(),
+                    categories,
+                }
+            }
+            Preprocessor::PcaWhitening {
+                components,
+                epsilon,
+            } => {
+                let width: usize = data.width;
+                let number_of_components: usize = components.unwrap_or(width);
+                if number_of_components == 0 || number_of_components > width {
+                    return Err(BriqueError::Config(format!(
+                        "pca whitening : {} components requested, expected between 1 and {}",
+                        number_of_components, width
+                    )));
+                }
+                if !epsilon.is_finite() || *epsilon < 0.0 {
+                    return Err(BriqueError::Config(format!(
+                        "pca whitening : epsilon must be finite and positive, got {}",
+                        epsilon
+                    )));
+                }
+
+                let mean: Vec<f64> = columns.iter().map(|c| mean(c)).collect();
+                let mut covariance: Vec<f64> = vec![0.0; width * width];
+                for i in 0..width {
+                    for j in i..width {
+                        let value: f64 = columns[i]
+                            .iter()
+                            .zip(&columns[j])
+                            .map(|(a, b)| (a - mean[i]) * (b - mean[j]))
+                            .sum::<f64>()
+                            / data.height as f64;
+                        covariance[i * width + j] = value;
+                        covariance[j * width + i] = value;
+                    }
+                }
+
+                let (eigenvalues, eigenvectors) = symmetric_eigen(covariance, width);
+                let mut order: Vec<usize> = (0..width).collect();
+                order.sort_by(|a, b| eigenvalues[*b].total_cmp(&eigenvalues[*a]));
+
+                let mut projection: Matrix = Matrix::init_zero(width, number_of_components);
+                for (component, index) in order.iter().take(number_of_components).enumerate() {
+                    // rounding errors can give slightly negative eigenvalues
+                    let scale: f64 =
+                        non_zero_scale((eigenvalues[*index].max(0.0) + epsilon).sqrt());
+                    for row in 0..width {
+                        projection.set(eigenvectors[row * width + index] / scale, row, component);
+                    }
+                }
+
+                FittedPreprocessor::PcaWhitening { mean, projection }
+            }
+        };
+
+        Ok(fitted)
+    }
+}
+
+impl FittedPreprocessor {
+    pub fn input_width(&self) -> usize {
+        match self {
+            FittedPreprocessor::StandardScaler { center, .. }
+            | FittedPreprocessor::MinMaxScaler { center, .. }
+            | FittedPreprocessor::RobustScaler { center, .. } => center.len(),
+            FittedPreprocessor::OneHotEncoder { input_width, .. } => *input_width,
+            FittedPreprocessor::PcaWhitening { mean, .. } => mean.len(),
+        }
+    }
+
+    pub fn output_width(&self) -> usize {
+        match self {
+            FittedPreprocessor::OneHotEncoder {
+                input_width,
+                categories,
+                ..
+            } => input_width - categories.len() + categories.iter().map(|c| c.len()).sum::<usize>(),
+            FittedPreprocessor::PcaWhitening { projection, .. } => projection.width,
+            _ => self.input_width(),
+        }
+    }
+
+    pub fn transform(&self, data: &Matrix) -> Result<Matrix, BriqueError> {
+        if data.width != self.input_width() {
+            return Err(BriqueError::Shape(format!(
+                "the preprocessor expects {} columns, got {}",
+                self.input_width(),
+                data.width
+            )));
+        }
+
+        let output: Matrix = match self {
+            FittedPreprocessor::StandardScaler { center, scale }
+            | FittedPreprocessor::MinMaxScaler { center, scale }
+            | FittedPreprocessor::RobustScaler { center, scale } => {
+                let mut output: Matrix = Matrix::init_zero(data.height, data.width);
+                for row in 0..data.height {
+                    for column in 0..data.width {
+                        output.set(
+                            (data.get(row, column) - center[column]) / scale[column],
+                            row,
+                            column,
+                        );
+                    }
+                }
+                output
+            }
+            FittedPreprocessor::OneHotEncoder {
+                columns,
+                categories,
+                ..
+            } => {
+                let output_width: usize = self.output_width();
+                let mut values: Vec<f64> = Vec::with_capacity(data.height * output_width);
+                for row in 0..data.height {
+                    for column in 0..data.width {
+                        let value: f64 = data.get(row, column);
+                        match columns.iter().position(|c| *c == column) {
+                            Some(index) => values.extend(
+                                categories[index].iter().map(|c| (*c == value) as u8 as f64),
+                            ),
+                            None => values.push(value),
+                        }
+                    }
+                }
+                Matrix::init(data.height, output_width, values)
+            }
+            FittedPreprocessor::PcaWhitening { mean, projection } => {
+                let mut centered: Matrix = Matrix::init_zero(data.height, data.width);
+                for row in 0..data.height {
+                    for (column, m) in mean.iter().enumerate() {
+                        centered.set(data.get(row, column) - m, row, column);
+                    }
+                }
+                centered.dot(projection)
+            }
+        };
+
+        Ok(output)
+    }
+}
+
+// ordered list of preprocessors, each one is fitted on the output of the previous one
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pipeline {
+    pub steps: Vec<Preprocessor>,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline { steps: vec![] }
+    }
+
+    pub fn add_step(mut self, step: Preprocessor) -> Pipeline {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn fit(&self, data: &Matrix) -> Result<FittedPipeline, BriqueError> {
+        Ok(self.fit_transform(data)?.0)
+    }
+
+    // the fitted pipeline and the transformed training data
+    pub fn fit_transform(&self, data: &Matrix) -> Result<(FittedPipeline, Matrix), BriqueError> {
+        let mut steps: Vec<FittedPreprocessor> = vec![];
+        let mut output: Matrix = data.clone();
+        for step in &self.steps {
+            let fitted: FittedPreprocessor = step.fit(&output)?;
+            output = fitted.transform(&output)?;
+            steps.push(fitted);
+        }
+
+        Ok((FittedPipeline { steps }, output))
+    }
+}
+
+// a fitted pipeline can be attached to a model, see Model::set_preprocessing,
+// it is then saved in the .brq file and applied by the inference api
+#[derive(Clone, Default)]
+pub struct FittedPipeline {
+    pub steps: Vec<FittedPreprocessor>,
+}
+
+impl FittedPipeline {
+    // None for an empty pipeline, which accepts any width
+    pub fn input_width(&self) -> Option<usize> {
+        self.steps.first().map(|step| step.input_width())
+    }
+
+    pub fn output_width(&self) -> Option<usize> {
+        self.steps.last().map(|step| step.output_width())
+    }
+
+    pub fn transform(&self, data: &Matrix) -> Matrix {
+        match self.try_transform(data) {
+            Ok(output) => output,
+            Err(e) => panic!("Error : {}", e),
+        }
+    }
+
+    pub fn try_transform(&self, data: &Matrix) -> Result<Matrix, BriqueError> {
+        let mut output: Matrix = data.clone();
+        for step in &self.steps {
+            output = step.transform(&output)?;
+        }
+
+        Ok(output)
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn non_zero_scale(scale: f64) -> f64 {
+    if scale == 0.0 {
+        1.0
+    } else {
+        scale
+    }
+}
+
+// linear interpolation between the closest ranks, values must be sorted
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position: f64 = q * (sorted.len() - 1) as f64;
+    let low: usize = position.floor() as usize;
+    let high: usize = position.ceil() as usize;
+
+    sorted[low] + (position - low as f64) * (sorted[high] - sorted[low])
+}
+
+// eigen decomposition of a symmetric size x size matrix (row major) with the cyclic jacobi method
+// returns the eigenvalues and the eigenvectors, the eigenvector i is the column i
+fn symmetric_eigen(matrix: Vec<f64>, size: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut a: Vec<f64> = matrix;
+    let mut v: Vec<f64> = vec![0.0; size * size];
+    for i in 0..size {
+        v[i * size + i] = 1.0;
+    }
+
+    let norm: f64 = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    for _ in 0..MAX_JACOBI_SWEEPS {
+        let mut off_diagonal: f64 = 0.0;
+        for p in 0..size {
+            for q in p + 1..size {
+                off_diagonal += a[p * size + q].powi(2);
+            }
+        }
+        if off_diagonal.sqrt() <= 1e-15 * norm {
+            break;
+        }
+
+        for p in 0..size {
+            for q in p + 1..size {
+                let apq: f64 = a[p * size + q];
+                if apq == 0.0 {
+                    continue;
+                }
+                // rotation zeroing a[p][q] : a = rt * a * r, v = v * r
+                let theta: f64 = (a[q * size + q] - a[p * size + p]) / (2.0 * apq);
+                let t: f64 = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c: f64 = 1.0 / (t * t + 1.0).sqrt();
+                let s: f64 = t * c;
+
+                for k in 0..size {
+                    let (akp, akq): (f64, f64) = (a[k * size + p], a[k * size + q]);
+                    a[k * size + p] = c * akp - s * akq;
+                    a[k * size + q] = s * akp + c * akq;
+                }
+                for k in 0..size {
+                    let (apk, aqk): (f64, f64) = (a[p * size + k], a[q * size + k]);
+                    a[p * size + k] = c * apk - s * aqk;
+                    a[q * size + k] = s * apk + c * aqk;
+                }
+                for k in 0..size {
+                    let (vkp, vkq): (f64, f64) = (v[k * size + p], v[k * size + q]);
+                    v[k * size + p] = c * vkp - s * vkq;
+                    v[k * size + q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+
+    ((0..size).map(|i| a[i * size + i]).collect(), v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{symmetric_eigen, FittedPreprocessor, Pipeline, Preprocessor};
+    use crate::{error::BriqueError, matrix::Matrix};
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    fn column(matrix: &Matrix, column: usize) -> Vec<f64> {
+        (0..matrix.height)
+            .map(|row| matrix.get(row, column))
+            .collect()
+    }
+
+    #[test]
+    fn scalers() {
+        // the second column is constant
+        let data = Matrix::init(4, 2, vec![1.0, 5.0, 2.0, 5.0, 3.0, 5.0, 10.0, 5.0]);
+
+        let standard = Preprocessor::StandardScaler.fit(&data).unwrap();
+        let output = standard.transform(&data).unwrap();
+        let values: Vec<f64> = column(&output, 0);
+        assert!(values.iter().sum::<f64>().abs() < 1e-12);
+        assert!((values.iter().map(|v| v * v).sum::<f64>() / 4.0 - 1.0).abs() < 1e-12);
+        assert_close(&column(&output, 1), &[0.0; 4]);
+
+        let min_max = Preprocessor::MinMaxScaler.fit(&data).unwrap();
+        let output = min_max.transform(&data).unwrap();
+        assert_close(&column(&output, 0), &[0.0, 1.0 / 9.0, 2.0 / 9.0, 1.0]);
+
+        // median 2.5, quartiles 1.75 and 4.75
+        let robust = Preprocessor::RobustScaler.fit(&data).unwrap();
+        let output = robust.transform(&data).unwrap();
+        assert_close(&column(&output, 0), &[-0.5, -1.0 / 6.0, 1.0 / 6.0, 2.5]);
+
+        // the test data is scaled with the training parameters
+        let test_data = Matrix::init(1, 2, vec![19.0, 7.0]);
+        assert_close(&min_max.transform(&test_data).unwrap().data, &[2.0, 2.0]);
+        assert!(matches!(
+            min_max.transform(&Matrix::init(1, 3, vec![0.0; 3])),
+            Err(BriqueError::Shape(_))
+        ));
+    }
+
+    #[test]
+    fn one_hot_encoder() {
+        let data = Matrix::init(3, 3, vec![0.5, 2.0, 1.0, 1.5, 0.0, 3.0, 2.5, 2.0, 1.0]);
+        let encoder = Preprocessor::OneHotEncoder {
+            columns: vec![1, 2],
+        }
+        .fit(&data)
+        .unwrap();
+
+        assert_eq!(encoder.output_width(), 5);
+        let output = encoder.transform(&data).unwrap();
+        assert_close(&output.get_row(1), &[1.5, 1.0, 0.0, 0.0, 1.0]);
+        // unknown category
+        let output = encoder
+            .transform(&Matrix::init(1, 3, vec![0.0, 7.0, 3.0]))
+            .unwrap();
+        assert_close(&output.data, &[0.0, 0.0, 0.0, 0.0, 1.0]);
+
+        assert!(matches!(
+            Preprocessor::OneHotEncoder { columns: vec![3] }.fit(&data),
+            Err(BriqueError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn pca_whitening() {
+        let (eigenvalues, _) = symmetric_eigen(vec![2.0, 1.0, 1.0, 2.0], 2);
+        let mut eigenvalues: Vec<f64> = eigenvalues;
+        eigenvalues.sort_by(|a, b| a.total_cmp(b));
+        assert_close(&eigenvalues, &[1.0, 3.0]);
+
+        // correlated columns
+        let data = Matrix::init(
+            5,
+            3,
+            vec![
+                1.0, 2.0, 0.5, 2.0, 4.5, 1.0, 3.0, 5.5, -1.0, 4.0, 8.0, 0.0, 5.0, 10.5, 2.0,
+            ],
+        );
+        let pca = Preprocessor::PcaWhitening {
+            components: None,
+            epsilon: 0.0,
+        }
+        .fit(&data)
+        .unwrap();
+        let output = pca.transform(&data).unwrap();
+
+        // identity covariance
+        for i in 0..3 {
+            for j in 0..3 {
+                let covariance: f64 = column(&output, i)
+                    .iter()
+                    .zip(column(&output, j))
+                    .map(|(a, b)| a * b)
+                    .sum::<f64>()
+                    / 5.0;
+                let expected: f64 = if i == j { 1.0 } else { 0.0 };
+                assert!((covariance - expected).abs() < 1e-9);
+            }
+        }
+
+        let reduced = Preprocessor::PcaWhitening {
+            components: Some(1),
+            epsilon: 1e-5,
+        }
+        .fit(&data)
+        .unwrap();
+        assert_eq!(reduced.output_width(), 1);
+
+        for epsilon in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                Preprocessor::PcaWhitening {
+                    components: None,
+                    epsilon,
+                }
+                .fit(&data),
+                Err(BriqueError::Config(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn pipeline_steps_are_chained() {
+        let data = Matrix::init(3, 2, vec![0.0, 10.0, 1.0, 20.0, 2.0, 10.0]);
+        let pipeline = Pipeline::new()
+            .add_step(Preprocessor::OneHotEncoder { columns: vec![1] })
+            .add_step(Preprocessor::MinMaxScaler);
+        let (fitted, output) = pipeline.fit_transform(&data).unwrap();
+
+        assert_eq!(fitted.input_width(), Some(2));
+        assert_eq!(fitted.output_width(), Some(3));
+        assert!(matches!(
+            fitted.steps[1],
+            FittedPreprocessor::MinMaxScaler { .. }
+        ));
+        assert_close(&output.get_row(1), &[0.5, 0.0, 1.0]);
+        assert!(fitted.transform(&data).is_equal(&output, 12));
+    }
+}
@@ -1,9 +1,19 @@
-use crate::{layers::Layer, matrix::Matrix, model::Model, optimizer::Optimizer};
+use crate::{
+    layers::Layer,
+    loss::TargetMode,
+    matrix::Matrix,
+    model::Model,
+    optimizer::Optimizer,
+    preprocessing::{FittedPipeline, FittedPreprocessor},
+    summary::ModelSummary,
+};
 use core::panic;
 use std::{collections::HashMap, fmt, fs};
 
 const FILE_EXTENSION: &str = ".brq";
-const VERSION: u8 = 2;
+const VERSION: u8 = 3;
+// the version 2 files have no preprocessing pipeline, they are still read
+const OLDEST_SUPPORTED_VERSION: u8 = 2;
 const HEADER_SIZE: u64 = 15;
 // CAT
 const START_OF_OBJECT_MAGIC_NUMBER: [u8; 3] = [67, 65, 84];
@@ -23,6 +33,13 @@ impl LookupStructBinaryId {
         lookup_table.lookup_table.insert("Matrix".to_string(), 0);
         lookup_table.lookup_table.insert("Layer".to_string(), 1);
         lookup_table.lookup_table.insert("Model".to_string(), 2);
+        lookup_table.lookup_table.insert("Pipeline".to_string(), 3);
+        lookup_table
+            .lookup_table
+            .insert("Preprocessor".to_string(), 4);
+        lookup_table
+            .lookup_table
+            .insert("TargetMode".to_string(), 5);
 
         lookup_table
     }
@@ -91,6 +108,11 @@ pub fn load_model(file_path: String) -> Result<Model, ModelManagementError> {
     binary_to_model(&byte_stream, HEADER_SIZE as usize)
 }
 
+// summary of a saved model, see Model::summary
+pub fn summarize_model_file(file_path: String) -> Result<ModelSummary, ModelManagementError> {
+    Ok(load_model(file_path)?.summary())
+}
+
 pub fn load_model_from_byte_stream(byte_stream: &Vec<u8>) -> Result<Model, ModelManagementError> {
     match check_header(&byte_stream) {
         Ok(()) => (),
@@ -102,7 +124,7 @@ pub fn load_model_from_byte_stream(byte_stream: &Vec<u8>) -> Result<Model, Model
 
 // header (size 15 bytes)
 // magic number : 6 bytes
-// version, would match the version of the release of the lib, i.e, 0.2 => 2, 0.3 => 3 .... 1.0 => 10, 1.1 => 11 : 1 byte
+// version of the file format, incremented when the layout changes (3 adds the frozen layers, the target mode and the preprocessing pipeline) : 1 byte
 // length of the binary (data and header combined) in bytes : 8 bytes
 pub fn add_header(data_size: u64) -> Vec<u8> {
     let mut header: Vec<u8> = vec![];
@@ -132,7 +154,7 @@ pub fn check_header(byte_stream: &Vec<u8>) -> Result<(), ModelManagementError> {
         ));
     }
 
-    if byte_stream[offset] != VERSION {
+    if byte_stream[offset] < OLDEST_SUPPORTED_VERSION || byte_stream[offset] > VERSION {
         return Err(ModelManagementError::CouldNotDecodeBinary(
             "while attempting to decode the header : wrong file version".to_string(),
         ));
@@ -261,9 +283,10 @@ pub fn binary_to_matrix(
     Ok((output_matrix, offset))
 }
 
+// flags : u8, bit 0 the activation (relu), bit 1 frozen (since the version 3,
+// so the layers of the older files are trainable)
 // weights : matrix
 // biases : matrix
-// activation : bool
 pub fn layer_to_binary(input_layer: &Layer) -> Vec<u8> {
     let mut output: Vec<u8> = vec![];
 
@@ -271,7 +294,7 @@ pub fn layer_to_binary(input_layer: &Layer) -> Vec<u8> {
 
     output.append(&mut START_OF_OBJECT_MAGIC_NUMBER.to_vec());
     output.push(id_lookup_table.lookup("Layer"));
-    output.push(input_layer.relu as u8);
+    output.push(input_layer.relu as u8 | (!input_layer.trainable as u8) << 1);
     output.append(&mut matrix_to_binary(&input_layer.weights_t));
     output.append(&mut matrix_to_binary(&input_layer.biases));
 
@@ -295,7 +318,8 @@ pub fn binary_to_layer(
     }
     offset += 1;
 
-    let activation: bool = byte_stream[offset] != 0;
+    let activation: bool = byte_stream[offset] & 1 != 0;
+    let trainable: bool = byte_stream[offset] & 2 == 0;
     offset += 1;
 
     let (weights_t, offset) = match binary_to_matrix(byte_stream, offset) {
@@ -308,15 +332,284 @@ pub fn binary_to_layer(
         Err(e) => return Err(e),
     };
 
-    let output_layer = Layer::init_with_data(weights_t, biases, activation);
+    let mut output_layer = Layer::init_with_data(weights_t, biases, activation);
+    output_layer.trainable = trainable;
 
     Ok((output_layer, offset))
 }
 
+// kind u8 : 0 class indexes, 1 probabilities, 2 multi-label
+// thresholds : 1 x C matrix, only for the multi-label targets
+pub fn target_mode_to_binary(input: &TargetMode) -> Vec<u8> {
+    let mut output: Vec<u8> = vec![];
+
+    let id_lookup_table = LookupStructBinaryId::init();
+
+    output.append(&mut START_OF_OBJECT_MAGIC_NUMBER.to_vec());
+    output.push(id_lookup_table.lookup("TargetMode"));
+    match input {
+        TargetMode::ClassIndex => output.push(0),
+        TargetMode::Probabilities => output.push(1),
+        TargetMode::MultiLabel { thresholds } => {
+            output.push(2);
+            output.append(&mut matrix_to_binary(&Matrix::init(
+                1,
+                thresholds.len(),
+                thresholds.clone(),
+            )));
+        }
+    }
+
+    output
+}
+
+pub fn binary_to_target_mode(
+    byte_stream: &Vec<u8>,
+    input_offset: usize,
+) -> Result<(TargetMode, usize), ModelManagementError> {
+    let mut offset = input_offset;
+
+    if offset + 5 > byte_stream.len() {
+        return Err(ModelManagementError::CouldNotDecodeBinary(
+            "while attempting to decode the target mode : Unexpected EOF".to_string(),
+        ));
+    }
+    if byte_stream[offset..offset + 3] != START_OF_OBJECT_MAGIC_NUMBER {
+        return Err(ModelManagementError::CouldNotDecodeBinary("while attempting to decode the target mode : Binary start of object code not found, file may be corrupted".to_string()));
+    }
+    offset += 3;
+    let id_lookup_table = LookupStructBinaryId::init();
+
+    if byte_stream[offset] != id_lookup_table.lookup("TargetMode") {
+        return Err(ModelManagementError::CouldNotDecodeBinary("while attempting to decode the target mode : Binary id code does not match the lookup table for the TargetMode entry, file may be corrupted".to_string()));
+    }
+    offset += 1;
+
+    let kind: u8 = byte_stream[offset];
+    offset += 1;
+
+    match kind {
+        0 => Ok((TargetMode::ClassIndex, offset)),
+        1 => Ok((TargetMode::Probabilities, offset)),
+        2 => {
+            let (thresholds, offset) = binary_to_matrix(byte_stream, offset)?;
+            Ok((
+                TargetMode::MultiLabel {
+                    thresholds: thresholds.data,
+                },
+                offset,
+            ))
+        }
+        _ => Err(ModelManagementError::CouldNotDecodeBinary(format!(
+            "while attempting to decode the target mode : unknown kind {}, file may be corrupted",
+            kind
+        ))),
+    }
+}
+
+// kind of preprocessor u8
+// input width u64
+// number of matrices u64
+// parameters Vec<Matrix>, vectors are saved as 1 x n matrices
+//  - scalers : center, scale
+//  - one-hot encoder : encoded columns, then the categories of every encoded column
+//  - pca whitening : mean, projection
+pub fn preprocessor_to_binary(input: &FittedPreprocessor) -> Vec<u8> {
+    let mut output: Vec<u8> = vec![];
+
+    let id_lookup_table = LookupStructBinaryId::init();
+    let row = |values: &Vec<f64>| Matrix::init(1, values.len(), values.clone());
+
+    let (kind, parameters): (u8, Vec<Matrix>) = match input {
+        FittedPreprocessor::StandardScaler { center, scale } => (0, vec![row(center), row(scale)]),
+        FittedPreprocessor::MinMaxScaler { center, scale } => (1, vec![row(center), row(scale)]),
+        FittedPreprocessor::RobustScaler { center, scale } => (2, vec![row(center), row(scale)]),
+        FittedPreprocessor::OneHotEncoder {
+            columns,
+            categories,
+            ..
+        } => {
+            let mut parameters: Vec<Matrix> =
+                vec![row(&columns.iter().map(|c| *c as f64).collect())];
+            parameters.extend(categories.iter().map(row));
+            (3, parameters)
+        }
+        FittedPreprocessor::PcaWhitening { mean, projection } => {
+            (4, vec![row(mean), projection.clone()])
+        }
+    };
+
+    output.append(&mut START_OF_OBJECT_MAGIC_NUMBER.to_vec());
+    output.push(id_lookup_table.lookup("Preprocessor"));
+    output.push(kind);
+    output.append(&mut (input.input_width() as u64).to_be_bytes().to_vec());
+    output.append(&mut (parameters.len() as u64).to_be_bytes().to_vec());
+    parameters
+        .iter()
+        .for_each(|matrix| output.append(&mut matrix_to_binary(matrix)));
+
+    output
+}
+
+pub fn binary_to_preprocessor(
+    byte_stream: &Vec<u8>,
+    input_offset: usize,
+) -> Result<(FittedPreprocessor, usize), ModelManagementError> {
+    let mut offset = input_offset;
+
+    if offset + 21 > byte_stream.len() {
+        return Err(ModelManagementError::CouldNotDecodeBinary(
+            "while attempting to decode a preprocessor : Unexpected EOF".to_string(),
+        ));
+    }
+    if byte_stream[offset..offset + 3] != START_OF_OBJECT_MAGIC_NUMBER {
+        return Err(ModelManagementError::CouldNotDecodeBinary("while attempting to decode a preprocessor : Binary start of object code not found, file may be corrupted".to_string()));
+    }
+    offset += 3;
+    let id_lookup_table = LookupStructBinaryId::init();
+
+    if byte_stream[offset] != id_lookup_table.lookup("Preprocessor") {
+        return Err(ModelManagementError::CouldNotDecodeBinary("while attempting to decode a preprocessor : Binary id code does not match the lookup table for the Preprocessor entry, file may be corrupted".to_string()));
+    }
+    offset += 1;
+
+    let kind: u8 = byte_stream[offset];
+    offset += 1;
+
+    let input_width: usize =
+        u64::from_be_bytes(byte_stream[offset..offset + 8].try_into().unwrap()) as usize;
+    offset += 8;
+
+    let number_of_matrices: usize =
+        u64::from_be_bytes(byte_stream[offset..offset + 8].try_into().unwrap()) as usize;
+    offset += 8;
+
+    let mut parameters: Vec<Matrix> = vec![];
+    for _ in 0..number_of_matrices {
+        let (matrix, new_offset) = binary_to_matrix(byte_stream, offset)?;
+        offset = new_offset;
+        parameters.push(matrix);
+    }
+
+    let invalid_parameters = || {
+        ModelManagementError::CouldNotDecodeBinary(format!(
+            "while attempting to decode a preprocessor : invalid parameters for the preprocessor kind {}, file may be corrupted",
+            kind
+        ))
+    };
+    let expected_matrices: bool = match kind {
+        0..=2 | 4 => number_of_matrices == 2,
+        3 => number_of_matrices >= 1 && number_of_matrices == parameters[0].width + 1,
+        _ => false,
+    };
+    if !expected_matrices {
+        return Err(invalid_parameters());
+    }
+
+    let mut parameters = parameters.into_iter();
+    let mut next = || parameters.next().unwrap();
+    let preprocessor: FittedPreprocessor = match kind {
+        0..=2 => {
+            let center: Vec<f64> = next().data;
+            let scale: Vec<f64> = next().data;
+            if center.len() != input_width || scale.len() != input_width {
+                return Err(invalid_parameters());
+            }
+            match kind {
+                0 => FittedPreprocessor::StandardScaler { center, scale },
+                1 => FittedPreprocessor::MinMaxScaler { center, scale },
+                _ => FittedPreprocessor::RobustScaler { center, scale },
+            }
+        }
+        3 => {
+            let columns: Vec<usize> = next().data.iter().map(|c| *c as usize).collect();
+            let duplicate: bool = columns
+                .iter()
+                .enumerate()
+                .any(|(i, c)| columns[..i].contains(c));
+            if duplicate || columns.iter().any(|c| *c >= input_width) {
+                return Err(invalid_parameters());
+            }
+            let categories: Vec<Vec<f64>> = columns.iter().map(|_| next().data).collect();
+            FittedPreprocessor::OneHotEncoder {
+                input_width,
+                columns,
+                categories,
+            }
+        }
+        _ => {
+            let mean: Vec<f64> = next().data;
+            let projection: Matrix = next();
+            if mean.len() != input_width || projection.height != input_width {
+                return Err(invalid_parameters());
+            }
+            FittedPreprocessor::PcaWhitening { mean, projection }
+        }
+    };
+
+    Ok((preprocessor, offset))
+}
+
+// number of steps u64
+// steps Vec<Preprocessor>
+pub fn pipeline_to_binary(input: &FittedPipeline) -> Vec<u8> {
+    let mut output: Vec<u8> = vec![];
+
+    let id_lookup_table = LookupStructBinaryId::init();
+
+    output.append(&mut START_OF_OBJECT_MAGIC_NUMBER.to_vec());
+    output.push(id_lookup_table.lookup("Pipeline"));
+    output.append(&mut (input.steps.len() as u64).to_be_bytes().to_vec());
+    input
+        .steps
+        .iter()
+        .for_each(|step| output.append(&mut preprocessor_to_binary(step)));
+
+    output
+}
+
+pub fn binary_to_pipeline(
+    byte_stream: &Vec<u8>,
+    input_offset: usize,
+) -> Result<(FittedPipeline, usize), ModelManagementError> {
+    let mut offset = input_offset;
+
+    if offset + 12 > byte_stream.len() {
+        return Err(ModelManagementError::CouldNotDecodeBinary(
+            "while attempting to decode the pipeline : Unexpected EOF".to_string(),
+        ));
+    }
+    if byte_stream[offset..offset + 3] != START_OF_OBJECT_MAGIC_NUMBER {
+        return Err(ModelManagementError::CouldNotDecodeBinary("while attempting to decode the pipeline : Binary start of object code not found, file may be corrupted".to_string()));
+    }
+    offset += 3;
+    let id_lookup_table = LookupStructBinaryId::init();
+
+    if byte_stream[offset] != id_lookup_table.lookup("Pipeline") {
+        return Err(ModelManagementError::CouldNotDecodeBinary("while attempting to decode the pipeline : Binary id code does not match the lookup table for the Pipeline entry, file may be corrupted".to_string()));
+    }
+    offset += 1;
+
+    let number_of_steps: usize =
+        u64::from_be_bytes(byte_stream[offset..offset + 8].try_into().unwrap()) as usize;
+    offset += 8;
+
+    let mut steps: Vec<FittedPreprocessor> = vec![];
+    for _ in 0..number_of_steps {
+        let (step, new_offset) = binary_to_preprocessor(byte_stream, offset)?;
+        offset = new_offset;
+        steps.push(step);
+    }
+
+    Ok((FittedPipeline { steps }, offset))
+}
+
 // learning step f64
 // lambda f64
 // number of layers
 // layres Vec<Layer>
+// target mode, only for the probability and multi-label targets (since the version 3)
+// pipeline, only if the model has a preprocessing pipeline (since the version 3)
 pub fn model_to_binary(input_model: &Model) -> Vec<u8> {
     let mut output: Vec<u8> = vec![];
 
@@ -332,6 +625,14 @@ pub fn model_to_binary(input_model: &Model) -> Vec<u8> {
         .iter()
         .for_each(|layer| output.append(&mut layer_to_binary(&layer)));
 
+    if !matches!(input_model.target_mode, TargetMode::ClassIndex) {
+        output.append(&mut target_mode_to_binary(&input_model.target_mode));
+    }
+
+    if let Some(pipeline) = &input_model.preprocessing {
+        output.append(&mut pipeline_to_binary(pipeline));
+    }
+
     output
 }
 
@@ -371,13 +672,48 @@ pub fn binary_to_model(
         layers.push(layer);
     }
 
-    Ok(Model::init(
+    let mut model: Model = Model::init(
         layers,
         Optimizer::SGD {
             learning_step: 0.01,
         },
         lambda,
-    ))
+    );
+
+    if offset + 4 <= byte_stream.len()
+        && byte_stream[offset + 3] == LookupStructBinaryId::init().lookup("TargetMode")
+    {
+        let (target_mode, new_offset) = binary_to_target_mode(byte_stream, offset)?;
+        offset = new_offset;
+        if let TargetMode::MultiLabel { thresholds } = &target_mode {
+            let number_of_classes: usize = model.layers[model.layers.len() - 1].weights_t.width;
+            if thresholds.len() != number_of_classes {
+                return Err(ModelManagementError::CouldNotDecodeBinary(format!(
+                    "while attempting to decode the model : {} multi-label thresholds for {} classes, file may be corrupted",
+                    thresholds.len(),
+                    number_of_classes
+                )));
+            }
+        }
+        model.target_mode = target_mode;
+    }
+
+    if offset < byte_stream.len() {
+        let (pipeline, new_offset) = binary_to_pipeline(byte_stream, offset)?;
+        if new_offset != byte_stream.len() {
+            return Err(ModelManagementError::CouldNotDecodeBinary(
+                "while attempting to decode the model : unexpected data after the pipeline, file may be corrupted".to_string(),
+            ));
+        }
+        if let Err(e) = model.set_preprocessing(pipeline) {
+            return Err(ModelManagementError::CouldNotDecodeBinary(format!(
+                "while attempting to decode the model : {}",
+                e
+            )));
+        }
+    }
+
+    Ok(model)
 }
 
 //unit test
@@ -386,9 +722,20 @@ mod tests {
     use core::panic;
     use std::fs;
 
-    use crate::{layers::Layer, model::Model, optimizer::Optimizer, save_load::FILE_EXTENSION};
+    use crate::{
+        layers::Layer,
+        loss::TargetMode,
+        matrix::Matrix,
+        model::Model,
+        optimizer::Optimizer,
+        preprocessing::{FittedPipeline, FittedPreprocessor, Pipeline, Preprocessor},
+        save_load::FILE_EXTENSION,
+    };
 
-    use super::{load_model, save_model};
+    use super::{
+        add_header, load_model, load_model_from_byte_stream, model_to_binary, pipeline_to_binary,
+        save_model, summarize_model_file, ModelManagementError,
+    };
 
     #[test]
     fn succesful_model_save_and_load() {
@@ -400,13 +747,14 @@ mod tests {
         let lambda: f64 = 0.012;
 
         let file_path: String = "test_model_save".to_string();
-        let model = Model::init(
+        let mut model = Model::init(
             vec![layer1, layer2, layer3, layer4],
             Optimizer::SGD {
                 learning_step: 0.01,
             },
             lambda,
         );
+        model.freeze_all_but_output();
         save_model(&model, file_path.clone()).unwrap();
 
         let loaded_model = match load_model(file_path.clone()) {
@@ -443,6 +791,154 @@ mod tests {
                 "Layer {} biases are different in the two models",
                 i
             );
+            assert_eq!(model.layers[i].relu, loaded_model.layers[i].relu);
+            assert_eq!(model.layers[i].trainable, loaded_model.layers[i].trainable);
+        }
+        assert!(!loaded_model.layers[0].trainable && loaded_model.layers[3].trainable);
+    }
+
+    #[test]
+    fn summary_of_a_saved_model() {
+        let file_path: String = "test_model_summary".to_string();
+        let model = Model::init(
+            vec![Layer::init(10, 20, true), Layer::init(20, 3, false)],
+            Optimizer::SGD {
+                learning_step: 0.01,
+            },
+            0.0,
+        );
+        save_model(&model, file_path.clone()).unwrap();
+
+        let summary = summarize_model_file(file_path.clone());
+        fs::remove_file(file_path + FILE_EXTENSION).unwrap();
+
+        assert_eq!(summary.unwrap(), model.summary());
+    }
+
+    #[test]
+    fn preprocessing_pipeline_is_saved_with_the_model() {
+        let data = Matrix::init(
+            4,
+            3,
+            vec![
+                1.0, 0.0, 10.0, 2.0, 1.0, 30.0, 4.0, 2.0, 20.0, 8.0, 1.0, 50.0,
+            ],
+        );
+        let (pipeline, _) = Pipeline::new()
+            .add_step(Preprocessor::OneHotEncoder { columns: vec![1] })
+            .add_step(Preprocessor::RobustScaler)
+            .add_step(Preprocessor::PcaWhitening {
+                components: Some(4),
+                epsilon: 1e-5,
+            })
+            .fit_transform(&data)
+            .unwrap();
+
+        let mut model = Model::init(
+            vec![Layer::init(4, 6, true), Layer::init(6, 2, false)],
+            Optimizer::SGD {
+                learning_step: 0.01,
+            },
+            0.0,
+        );
+        model.set_preprocessing(pipeline).unwrap();
+        assert_eq!(model.input_width(), 3);
+
+        let file_path: String = "test_model_pipeline".to_string();
+        save_model(&model, file_path.clone()).unwrap();
+        let loaded_model = load_model(file_path.clone());
+        fs::remove_file(file_path + FILE_EXTENSION).unwrap();
+        let loaded_model = loaded_model.unwrap();
+
+        assert_eq!(loaded_model.preprocessing.as_ref().unwrap().steps.len(), 3);
+        assert!(loaded_model
+            .predict_proba(&data)
+            .is_equal(&model.predict_proba(&data), 10));
+    }
+
+    #[test]
+    fn target_mode_is_saved_with_the_model() {
+        let mut model = Model::init(
+            vec![Layer::init(3, 2, false)],
+            Optimizer::SGD {
+                learning_step: 0.01,
+            },
+            0.0,
+        );
+        model.target_mode = TargetMode::MultiLabel {
+            thresholds: vec![0.3, 0.7],
+        };
+        let (pipeline, _) = Pipeline::new()
+            .add_step(Preprocessor::StandardScaler)
+            .fit_transform(&Matrix::init(2, 3, vec![1.0, 2.0, 3.0, 4.0, 5.0, 7.0]))
+            .unwrap();
+        model.set_preprocessing(pipeline).unwrap();
+
+        let mut byte_stream: Vec<u8> = model_to_binary(&model);
+        byte_stream.splice(0..0, add_header(byte_stream.len() as u64));
+        let loaded_model = load_model_from_byte_stream(&byte_stream).unwrap();
+
+        match loaded_model.target_mode {
+            TargetMode::MultiLabel { thresholds } => assert_eq!(thresholds, vec![0.3, 0.7]),
+            _ => panic!("the target mode was not saved"),
         }
+        assert!(loaded_model.preprocessing.is_some());
+
+        model.target_mode = TargetMode::Probabilities;
+        model.preprocessing = None;
+        let mut byte_stream: Vec<u8> = model_to_binary(&model);
+        byte_stream.splice(0..0, add_header(byte_stream.len() as u64));
+        let loaded_model = load_model_from_byte_stream(&byte_stream).unwrap();
+        assert!(matches!(
+            loaded_model.target_mode,
+            TargetMode::Probabilities
+        ));
+    }
+
+    #[test]
+    fn corrupted_one_hot_columns_are_rejected() {
+        let model = Model::init(
+            vec![Layer::init(1, 2, false)],
+            Optimizer::SGD {
+                learning_step: 0.01,
+            },
+            0.0,
+        );
+        // the column 0 is encoded twice
+        let pipeline = FittedPipeline {
+            steps: vec![FittedPreprocessor::OneHotEncoder {
+                input_width: 1,
+                columns: vec![0, 0],
+                categories: vec![vec![0.0], vec![1.0]],
+            }],
+        };
+        let mut byte_stream: Vec<u8> = model_to_binary(&model);
+        byte_stream.append(&mut pipeline_to_binary(&pipeline));
+        byte_stream.splice(0..0, add_header(byte_stream.len() as u64));
+
+        assert!(matches!(
+            load_model_from_byte_stream(&byte_stream),
+            Err(ModelManagementError::CouldNotDecodeBinary(_))
+        ));
+    }
+
+    #[test]
+    fn version_2_files_are_still_read() {
+        let model = Model::init(
+            vec![Layer::init(3, 2, false)],
+            Optimizer::SGD {
+                learning_step: 0.01,
+            },
+            0.0,
+        );
+        let mut byte_stream: Vec<u8> = model_to_binary(&model);
+        byte_stream.splice(0..0, add_header(byte_stream.len() as u64));
+        byte_stream[6] = 2;
+
+        let loaded_model = load_model_from_byte_stream(&byte_stream).unwrap();
+        assert!(loaded_model.preprocessing.is_none());
+
+        byte_stream[6] = 1;
+        assert!(load_model_from_byte_stream(&byte_stream).is_err());
     }
 }
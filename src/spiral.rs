@@ -1,7 +1,20 @@
 use crate::matrix::Matrix;
-use rand::{rng, Rng};
+use rand::{rng, rngs::StdRng, Rng, SeedableRng};
 
 pub fn generate_spiral_dataset(number_of_points: u32, number_of_classes: u32) -> (Matrix, Matrix) {
+    generate_spiral_dataset_with_seed(number_of_points, number_of_classes, 0.2, rng().random())
+}
+
+// number_of_points per class, the angles get a uniform noise in [0, noise)
+// see also the synthetic module for other toy datasets
+pub fn generate_spiral_dataset_with_seed(
+    number_of_points: u32,
+    number_of_classes: u32,
+    noise: f64,
+    seed: u64,
+) -> (Matrix, Matrix) {
+    assert!(noise >= 0.0, "Error : the noise level must be positive");
+    let mut rng: StdRng = StdRng::seed_from_u64(seed);
     let height: usize = (number_of_points * number_of_classes) as usize;
     let mut data: Matrix = Matrix::init_zero(height, 2);
     let mut labels: Matrix = Matrix::init_zero(1, height);
@@ -14,7 +27,7 @@ pub fn generate_spiral_dataset(number_of_points: u32, number_of_classes: u32) ->
             (class + 1) as f64 * 4.0,
             number_of_points,
         );
-        let t: Vec<f64> = add_rand_to_vec(&a);
+        let t: Vec<f64> = add_rand_to_vec(&a, noise, &mut rng);
 
         populate_data(&mut data, &mut labels, &r, &t, class, number_of_points);
     }
@@ -39,12 +52,11 @@ pub fn linspace(start: f64, stop: f64, number: u32) -> Vec<f64> {
     output
 }
 
-fn add_rand_to_vec(input_vec: &Vec<f64>) -> Vec<f64> {
+fn add_rand_to_vec(input_vec: &Vec<f64>, noise: f64, rng: &mut StdRng) -> Vec<f64> {
     let mut output: Vec<f64> = Vec::new();
     for index in 0..input_vec.len() {
-        let mut rng = rng();
         let mut r: f64 = rng.random::<f64>();
-        r *= 0.2;
+        r *= noise;
         output.push(input_vec[index] + r);
     }
 
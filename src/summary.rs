@@ -0,0 +1,188 @@
+use crate::loss::TargetMode;
+use crate::model::Model;
+use crate::optimizer::Optimizer;
+use std::fmt;
+
+const BYTES_PER_VALUE: usize = std::mem::size_of::<f64>();
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerSummary {
+    pub layer_type: String,
+    pub input_size: usize,
+    pub output_size: usize,
+    pub activation: String,
+    pub parameters: usize,
+    pub bytes: usize,
+    pub trainable: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelSummary {
+    pub layers: Vec<LayerSummary>,
+    pub total_parameters: usize,
+    pub trainable_parameters: usize,
+    pub total_bytes: usize,
+    // number of values stored by the optimizer for every trainable parameter
+    // 0 for SGD, 2 for Adam (first and second moments)
+    pub optimizer_values_per_parameter: usize,
+    // if set, the training memory estimate is displayed for this batch size
+    pub batch_size: Option<usize>,
+}
+
+impl ModelSummary {
+    pub fn init(model: &Model) -> ModelSummary {
+        let last_index: usize = model.layers.len() - 1;
+        let layers: Vec<LayerSummary> = model
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(index, layer)| {
+                let parameters: usize = layer.weights_t.data.len() + layer.biases.data.len();
+                let activation: &str = if layer.relu {
+                    "ReLU"
+                } else if index == last_index {
+                    match model.target_mode {
+                        TargetMode::MultiLabel { .. } => "Sigmoid",
+                        _ => "Softmax",
+                    }
+                } else {
+                    "None"
+                };
+
+                LayerSummary {
+                    layer_type: "Dense".to_string(),
+                    input_size: layer.weights_t.height,
+                    output_size: layer.weights_t.width,
+                    activation: activation.to_string(),
+                    parameters,
+                    bytes: parameters * BYTES_PER_VALUE,
+                    trainable: layer.trainable,
+                }
+            })
+            .collect();
+
+        ModelSummary {
+            total_parameters: layers.iter().map(|layer| layer.parameters).sum(),
+            trainable_parameters: layers
+                .iter()
+                .filter(|layer| layer.trainable)
+                .map(|layer| layer.parameters)
+                .sum(),
+            total_bytes: layers.iter().map(|layer| layer.bytes).sum(),
+            optimizer_values_per_parameter: match model.optimizer {
+                Optimizer::SGD { .. } => 0,
+                Optimizer::Adam { .. } => 2,
+            },
+            batch_size: None,
+            layers,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> ModelSummary {
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    // rough estimate of the memory used by one training step, in bytes :
+    //  - the parameters and their gradients
+    //  - the optimizer state of the trainable parameters
+    //  - the batch, the cached output and the output gradient of every layer
+    pub fn training_memory_bytes(&self, batch_size: usize) -> usize {
+        let input_size: usize = self.layers.first().map_or(0, |layer| layer.input_size);
+        let outputs_size: usize = self.layers.iter().map(|layer| layer.output_size).sum();
+
+        let parameters: usize = 2 * self.total_parameters
+            + self.optimizer_values_per_parameter * self.trainable_parameters;
+        let activations: usize = batch_size * (input_size + 2 * outputs_size);
+
+        (parameters + activations) * BYTES_PER_VALUE
+    }
+}
+
+impl fmt::Display for ModelSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<6} {:<8} {:>8} {:>8} {:<10} {:>12} {:>12} {:<9}",
+            "Layer", "Type", "Input", "Output", "Activation", "Parameters", "Bytes", "Trainable"
+        )?;
+        for (index, layer) in self.layers.iter().enumerate() {
+            writeln!(
+                f,
+                "{:<6} {:<8} {:>8} {:>8} {:<10} {:>12} {:>12} {:<9}",
+                index,
+                layer.layer_type,
+                layer.input_size,
+                layer.output_size,
+                layer.activation,
+                layer.parameters,
+                layer.bytes,
+                layer.trainable
+            )?;
+        }
+        writeln!(f, "Total parameters : {}", self.total_parameters)?;
+        writeln!(f, "Trainable parameters : {}", self.trainable_parameters)?;
+        writeln!(
+            f,
+            "Non-trainable parameters : {}",
+            self.total_parameters - self.trainable_parameters
+        )?;
+        write!(f, "Parameters size : {} bytes", self.total_bytes)?;
+
+        if let Some(batch_size) = self.batch_size {
+            write!(
+                f,
+                "\nEstimated training memory (batch size {}) : {} bytes",
+                batch_size,
+                self.training_memory_bytes(batch_size)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{layers::Layer, model::Model, optimizer::Optimizer};
+
+    fn get_test_model() -> Model {
+        Model::init(
+            vec![Layer::init(4, 3, true), Layer::init(3, 2, false)],
+            Optimizer::Adam {
+                learning_step: 0.01,
+                beta1: 0.9,
+                beta2: 0.999,
+            },
+            0.0,
+        )
+    }
+
+    #[test]
+    fn parameter_counts() {
+        let mut model = get_test_model();
+        model.set_trainable(0, false);
+        let summary = model.summary();
+
+        assert_eq!(summary.layers[0].parameters, 4 * 3 + 3);
+        assert_eq!(summary.layers[1].parameters, 3 * 2 + 2);
+        assert_eq!(summary.layers[0].activation, "ReLU");
+        assert_eq!(summary.layers[1].activation, "Softmax");
+        assert_eq!(summary.total_parameters, 23);
+        assert_eq!(summary.trainable_parameters, 8);
+        assert_eq!(summary.total_bytes, 23 * 8);
+    }
+
+    #[test]
+    fn training_memory_estimate() {
+        let summary = get_test_model().summary();
+
+        // parameters and gradients, adam moments, batch, outputs and their gradients
+        let expected: usize = (2 * 23 + 2 * 23 + 10 * (4 + 2 * (3 + 2))) * 8;
+        assert_eq!(summary.training_memory_bytes(10), expected);
+
+        let display: String = summary.with_batch_size(10).to_string();
+        assert!(display.contains("Total parameters : 23"));
+        assert!(display.contains(&format!("{} bytes", expected)));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::matrix::Matrix;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+use std::f64::consts::PI;
+
+// toy datasets for tests and demos, see also spiral.rs
+// every generator is seeded and returns the data (one sample per row) and the 1 x N labels
+// like generate_spiral_dataset, the classes are stored one after the other
+// noise is the standard deviation of a gaussian noise added to every coordinate
+
+// two interleaving half circles, number_of_points per class
+pub fn generate_moons(number_of_points: u32, noise: f64, seed: u64) -> (Matrix, Matrix) {
+    let (mut rng, normal) = init_generator(noise, seed);
+    let mut data: Vec<f64> = vec![];
+    let mut labels: Vec<f64> = vec![];
+
+    for class in 0..2 {
+        for t in linspace(0.0, PI, number_of_points) {
+            let (x, y): (f64, f64) = if class == 0 {
+                (t.cos(), t.sin())
+            } else {
+                (1.0 - t.cos(), 0.5 - t.sin())
+            };
+            data.push(x + normal.sample(&mut rng));
+            data.push(y + normal.sample(&mut rng));
+            labels.push(class as f64);
+        }
+    }
+
+    into_matrices(data, labels, 2)
+}
+
+// a circle of radius 1 (class 0) around a circle of radius factor (class 1), number_of_points per class
+pub fn generate_circles(
+    number_of_points: u32,
+    factor: f64,
+    noise: f64,
+    seed: u64,
+) -> (Matrix, Matrix) {
+    assert!(
+        factor > 0.0 && factor < 1.0,
+        "Error : the factor between the two circles must be in (0, 1)"
+    );
+    let (mut rng, normal) = init_generator(noise, seed);
+    let mut data: Vec<f64> = vec![];
+    let mut labels: Vec<f64> = vec![];
+
+    for (class, radius) in [1.0, factor].iter().enumerate() {
+        for index in 0..number_of_points {
+            let t: f64 = 2.0 * PI * index as f64 / number_of_points as f64;
+            data.push(radius * t.cos() + normal.sample(&mut rng));
+            data.push(radius * t.sin() + normal.sample(&mut rng));
+            labels.push(class as f64);
+        }
+    }
+
+    into_matrices(data, labels, 2)
+}
+
+// gaussian clusters in width dimensions, number_of_points per class
+// the centers are drawn uniformly in [-10, 10], noise is the standard deviation of the clusters
+pub fn generate_blobs(
+    number_of_points: u32,
+    number_of_classes: u32,
+    width: usize,
+    noise: f64,
+    seed: u64,
+) -> (Matrix, Matrix) {
+    assert!(width > 0, "Error : the blobs need at least one dimension");
+    let (mut rng, normal) = init_generator(noise, seed);
+    let mut data: Vec<f64> = vec![];
+    let mut labels: Vec<f64> = vec![];
+
+    for class in 0..number_of_classes {
+        let center: Vec<f64> = (0..width).map(|_| rng.random_range(-10.0..10.0)).collect();
+        for _ in 0..number_of_points {
+            center
+                .iter()
+                .for_each(|c| data.push(c + normal.sample(&mut rng)));
+            labels.push(class as f64);
+        }
+    }
+
+    into_matrices(data, labels, width)
+}
+
+// points drawn uniformly in [-1, 1]^2, class 1 when exactly one coordinate is positive
+// the noise is added after the labelling
+pub fn generate_xor(number_of_points: u32, noise: f64, seed: u64) -> (Matrix, Matrix) {
+    let (mut rng, normal) = init_generator(noise, seed);
+    let mut data: Vec<f64> = vec![];
+    let mut labels: Vec<f64> = vec![];
+
+    for _ in 0..number_of_points {
+        let x: f64 = rng.random_range(-1.0..1.0);
+        let y: f64 = rng.random_range(-1.0..1.0);
+        data.push(x + normal.sample(&mut rng));
+        data.push(y + normal.sample(&mut rng));
+        labels.push(((x > 0.0) != (y > 0.0)) as u8 as f64);
+    }
+
+    into_matrices(data, labels, 2)
+}
+
+// points drawn uniformly in [0, 1]^2 split in cells x cells squares, alternating classes
+// the noise is added after the labelling
+pub fn generate_checkerboard(
+    number_of_points: u32,
+    cells: u32,
+    noise: f64,
+    seed: u64,
+) -> (Matrix, Matrix) {
+    assert!(
+        cells > 0,
+        "Error : the checkerboard needs at least one cell"
+    );
+    let (mut rng, normal) = init_generator(noise, seed);
+    let mut data: Vec<f64> = vec![];
+    let mut labels: Vec<f64> = vec![];
+
+    for _ in 0..number_of_points {
+        let x: f64 = rng.random::<f64>();
+        let y: f64 = rng.random::<f64>();
+        let cell: u32 = (x * cells as f64) as u32 + (y * cells as f64) as u32;
+        data.push(x + normal.sample(&mut rng));
+        data.push(y + normal.sample(&mut rng));
+        labels.push((cell % 2) as f64);
+    }
+
+    into_matrices(data, labels, 2)
+}
+
+// regression : x drawn uniformly in [-pi, pi], the labels are sin(x) plus the noise
+pub fn generate_sine(number_of_points: u32, noise: f64, seed: u64) -> (Matrix, Matrix) {
+    let (mut rng, normal) = init_generator(noise, seed);
+    let mut data: Vec<f64> = vec![];
+    let mut labels: Vec<f64> = vec![];
+
+    for _ in 0..number_of_points {
+        let x: f64 = rng.random_range(-PI..PI);
+        data.push(x);
+        labels.push(x.sin() + normal.sample(&mut rng));
+    }
+
+    into_matrices(data, labels, 1)
+}
+
+// standard normal points in width dimensions, labelled by the side of a random hyperplane
+// through the origin, the noise is added after the labelling so the classes may overlap
+pub fn generate_linearly_separable(
+    number_of_points: u32,
+    width: usize,
+    noise: f64,
+    seed: u64,
+) -> (Matrix, Matrix) {
+    assert!(width > 0, "Error : the data needs at least one dimension");
+    let (mut rng, normal) = init_generator(noise, seed);
+    let standard_normal: Normal<f64> = Normal::new(0.0, 1.0).unwrap();
+    let mut data: Vec<f64> = vec![];
+    let mut labels: Vec<f64> = vec![];
+
+    let normal_vector: Vec<f64> = (0..width)
+        .map(|_| standard_normal.sample(&mut rng))
+        .collect();
+    for _ in 0..number_of_points {
+        let point: Vec<f64> = (0..width)
+            .map(|_| standard_normal.sample(&mut rng))
+            .collect();
+        let side: f64 = point.iter().zip(&normal_vector).map(|(p, n)| p * n).sum();
+        point
+            .iter()
+            .for_each(|p| data.push(p + normal.sample(&mut rng)));
+        labels.push((side > 0.0) as u8 as f64);
+    }
+
+    into_matrices(data, labels, width)
+}
+
+fn init_generator(noise: f64, seed: u64) -> (StdRng, Normal<f64>) {
+    assert!(
+        noise >= 0.0 && noise.is_finite(),
+        "Error : the noise level must be positive"
+    );
+
+    (
+        StdRng::seed_from_u64(seed),
+        Normal::new(0.0, noise).unwrap(),
+    )
+}
+
+// number values evenly spaced from start to stop, both included
+fn linspace(start: f64, stop: f64, number: u32) -> Vec<f64> {
+    if number == 1 {
+        return vec![start];
+    }
+
+    (0..number)
+        .map(|i| start + (stop - start) * i as f64 / (number - 1) as f64)
+        .collect()
+}
+
+fn into_matrices(data: Vec<f64>, labels: Vec<f64>, width: usize) -> (Matrix, Matrix) {
+    (
+        Matrix::init(labels.len(), width, data),
+        Matrix::init(1, labels.len(), labels),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        generate_blobs, generate_checkerboard, generate_circles, generate_linearly_separable,
+        generate_moons, generate_sine, generate_xor,
+    };
+    use crate::{matrix::Matrix, spiral::generate_spiral_dataset_with_seed};
+
+    type Generator = Box<dyn Fn(u64) -> (Matrix, Matrix)>;
+
+    fn class_count(labels: &Matrix, class: f64) -> usize {
+        labels.data.iter().filter(|l| **l == class).count()
+    }
+
+    #[test]
+    fn generators_are_seeded() {
+        let generators: Vec<Generator> = vec![
+            Box::new(|seed| generate_moons(20, 0.1, seed)),
+            Box::new(|seed| generate_circles(20, 0.5, 0.1, seed)),
+            Box::new(|seed| generate_blobs(20, 3, 4, 1.0, seed)),
+            Box::new(|seed| generate_xor(40, 0.1, seed)),
+            Box::new(|seed| generate_checkerboard(40, 4, 0.1, seed)),
+            Box::new(|seed| generate_sine(40, 0.1, seed)),
+            Box::new(|seed| generate_linearly_separable(40, 3, 0.1, seed)),
+            Box::new(|seed| generate_spiral_dataset_with_seed(20, 2, 0.2, seed)),
+        ];
+
+        for generator in generators {
+            let (data, labels) = generator(7);
+            let (same_data, same_labels) = generator(7);
+            let (other_data, _) = generator(8);
+
+            assert_eq!(data.height, labels.width);
+            assert_eq!(labels.height, 1);
+            assert!(data.is_equal(&same_data, 15));
+            assert!(labels.is_equal(&same_labels, 15));
+            assert!(!data.is_equal(&other_data, 15));
+        }
+    }
+
+    #[test]
+    fn classification_shapes() {
+        let (data, labels) = generate_moons(50, 0.0, 0);
+        assert_eq!((data.height, data.width), (100, 2));
+        assert_eq!(class_count(&labels, 0.0), 50);
+        // the first moon is on the unit circle
+        assert!((data.get(10, 0).powi(2) + data.get(10, 1).powi(2) - 1.0).abs() < 1e-12);
+
+        let (data, labels) = generate_circles(30, 0.3, 0.0, 0);
+        assert_eq!(class_count(&labels, 1.0), 30);
+        assert!((data.get(40, 0).powi(2) + data.get(40, 1).powi(2) - 0.09).abs() < 1e-12);
+
+        // no noise, all the points of a blob are its center
+        let (data, labels) = generate_blobs(5, 3, 4, 0.0, 0);
+        assert_eq!((data.height, data.width), (15, 4));
+        assert_eq!(labels.get(0, 14), 2.0);
+        assert_eq!(data.get_row(5), data.get_row(9));
+        assert_ne!(data.get_row(4), data.get_row(5));
+
+        let (data, labels) = generate_xor(100, 0.0, 0);
+        for row in 0..100 {
+            let expected: bool = (data.get(row, 0) > 0.0) != (data.get(row, 1) > 0.0);
+            assert_eq!(labels.get(0, row), expected as u8 as f64);
+        }
+
+        let (data, labels) = generate_checkerboard(100, 2, 0.0, 0);
+        for row in 0..100 {
+            let cell: usize =
+                (data.get(row, 0) >= 0.5) as usize + (data.get(row, 1) >= 0.5) as usize;
+            assert_eq!(labels.get(0, row), (cell % 2) as f64);
+        }
+
+        let (data, labels) = generate_linearly_separable(200, 5, 0.0, 0);
+        assert_eq!(data.width, 5);
+        assert!(class_count(&labels, 0.0) > 0 && class_count(&labels, 1.0) > 0);
+    }
+
+    #[test]
+    fn sine_regression() {
+        let (data, labels) = generate_sine(50, 0.0, 3);
+        assert_eq!((data.height, data.width, labels.width), (50, 1, 50));
+        for row in 0..50 {
+            assert!((labels.get(0, row) - data.get(row, 0).sin()).abs() < 1e-12);
+        }
+
+        let (_, noisy_labels) = generate_sine(50, 0.5, 3);
+        assert!(!noisy_labels.is_equal(&labels, 5));
+    }
+}
@@ -1,6 +1,7 @@
+use crate::error::BriqueError;
+use crate::idx::{read_idx, IdxArray};
 use crate::matrix::*;
 use rand::seq::SliceRandom;
-use std::fs::read;
 
 pub fn generate_vec_rand_unique(size: u32) -> Vec<u32> {
     let mut rng = rand::rng();
@@ -13,11 +14,26 @@ pub fn generate_vec_rand_unique(size: u32) -> Vec<u32> {
 // not the optimal way to return Matrix with f64s. can be optimised with matrix that accepts
 // generic type
 pub fn generate_batch_index(index_table: &Vec<u32>, batch_size: u32) -> Vec<Vec<f64>> {
-    assert!(
-        index_table.len() as u32 >= batch_size,
-        "Batch size cannot be bigger than training dataset size"
-    );
-    assert!(batch_size > 0, "Batch size must be strictly positive");
+    match try_generate_batch_index(index_table, batch_size) {
+        Ok(output) => output,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+pub fn try_generate_batch_index(
+    index_table: &[u32],
+    batch_size: u32,
+) -> Result<Vec<Vec<f64>>, BriqueError> {
+    if index_table.len() < batch_size as usize {
+        return Err(BriqueError::Config(
+            "Batch size cannot be bigger than training dataset size".to_string(),
+        ));
+    }
+    if batch_size == 0 {
+        return Err(BriqueError::Config(
+            "Batch size must be strictly positive".to_string(),
+        ));
+    }
 
     let mut number_of_batches: usize = index_table.len() / batch_size as usize;
     if index_table.len() % (batch_size as usize) != 0 {
@@ -37,93 +53,96 @@ pub fn generate_batch_index(index_table: &Vec<u32>, batch_size: u32) -> Vec<Vec<
         output.push(tmp);
     }
 
-    output
-}
-
-fn convert_4_bytes_to_u32_big_endian(bytes: Vec<u8>) -> u32 {
-    assert_eq!(bytes.len(), 4, "byte array should be of size 4");
-    let output: u32 = (bytes[0] as u32) * 2_u32.pow(24)
-        + (bytes[1] as u32) * 2_u32.pow(16)
-        + (bytes[2] as u32) * 2_u32.pow(8)
-        + (bytes[3] as u32);
-
-    output
-}
-
-fn check_label_file_header(array: &Vec<u8>) {
-    // check out the documentation : http://yann.lecun.com/exdb/mnist/
-    let expected_file_header: Vec<u8> = vec![0, 0, 8, 1];
-    let array_size: u32 = convert_4_bytes_to_u32_big_endian(array[4..8].to_vec());
-
-    assert_eq!(
-        array[0..4].to_vec(),
-        expected_file_header,
-        "File incompatibility detected, are you sure you added the correct LABEL file ?"
-    );
-    assert_eq!(
-        array_size,
-        array.len() as u32 - 8,
-        "File incompatibility detected, are you sure you added the correct LABEL file ?"
-    );
-}
-
-fn check_image_file_header(array: &Vec<u8>) {
-    // check out the documentation : http://yann.lecun.com/exdb/mnist/
-    let expected_file_header: Vec<u8> = vec![0, 0, 8, 3];
-
-    let array_size: u32 = convert_4_bytes_to_u32_big_endian(array[4..8].to_vec());
-    let array_size_row: u32 = convert_4_bytes_to_u32_big_endian(array[8..12].to_vec());
-    let array_size_column: u32 = convert_4_bytes_to_u32_big_endian(array[12..16].to_vec());
-
-    assert_eq!(
-        array_size * array_size_column * array_size_row,
-        array.len() as u32 - 16,
-        "File incompatibility detected, are you sure you added the correct IMAGE file ?"
-    );
-    assert_eq!(
-        array[0..4].to_vec(),
-        expected_file_header,
-        "File incompatibility detected, are you sure you added the correct IMAGE file ?"
-    );
+    Ok(output)
 }
 
 pub fn extract_labels(path: &str) -> Matrix {
-    let res: Vec<u8> = read(path).unwrap();
-    check_label_file_header(&res);
-    let slice: Vec<u8> = res[8..].to_vec();
-    let mut output: Matrix = Matrix::init_zero(1, slice.len());
+    match try_extract_labels(path) {
+        Ok(output) => output,
+        Err(e) => panic!("{}", e),
+    }
+}
 
-    slice
-        .iter()
-        .enumerate()
-        .for_each(|(index, value)| output.set(*value as f64, 0, index));
+// reads any idx file with one dimension, see idx::read_idx
+pub fn try_extract_labels(path: &str) -> Result<Matrix, BriqueError> {
+    let array: IdxArray = read_idx(path)?;
+    if array.dimensions.len() != 1 {
+        return Err(BriqueError::Format(
+            "File incompatibility detected, are you sure you added the correct LABEL file ?"
+                .to_string(),
+        ));
+    }
 
-    output
+    Ok(array.to_matrix())
 }
 
 pub fn extract_images(path: &str) -> Matrix {
-    let res: Vec<u8> = read(path).unwrap();
-
-    check_image_file_header(&res);
-
-    let array_size: u32 = convert_4_bytes_to_u32_big_endian(res[4..8].to_vec());
-    let array_size_row: u32 = convert_4_bytes_to_u32_big_endian(res[8..12].to_vec());
-    let array_size_column: u32 = convert_4_bytes_to_u32_big_endian(res[12..16].to_vec());
+    match try_extract_images(path) {
+        Ok(output) => output,
+        Err(e) => panic!("{}", e),
+    }
+}
 
-    let pixels_per_image: u32 = array_size_row * array_size_column;
+// reads any idx file with three dimensions (images, rows, columns), one image per row
+// see idx::read_idx
+pub fn try_extract_images(path: &str) -> Result<Matrix, BriqueError> {
+    let array: IdxArray = read_idx(path)?;
+    if array.dimensions.len() != 3 {
+        return Err(BriqueError::Format(
+            "File incompatibility detected, are you sure you added the correct IMAGE file ?"
+                .to_string(),
+        ));
+    }
 
-    let mut output: Matrix = Matrix::init_zero(
-        array_size.try_into().unwrap(),
-        pixels_per_image.try_into().unwrap(),
-    );
-    let mut index = 0;
+    Ok(array.to_matrix())
+}
 
-    for i in res[16..].to_vec() {
-        let x: usize = (index / pixels_per_image).try_into().unwrap();
-        let y: usize = (index % pixels_per_image).try_into().unwrap();
-        output.set(i as f64, x, y);
-        index += 1;
+#[cfg(test)]
+mod tests {
+    use super::{try_extract_images, try_extract_labels, try_generate_batch_index};
+    use crate::error::BriqueError;
+    use std::fs;
+
+    #[test]
+    fn invalid_batch_size() {
+        let index_table: Vec<u32> = (0..10).collect();
+
+        assert!(matches!(
+            try_generate_batch_index(&index_table, 0),
+            Err(BriqueError::Config(_))
+        ));
+        assert!(matches!(
+            try_generate_batch_index(&index_table, 11),
+            Err(BriqueError::Config(_))
+        ));
+        assert_eq!(try_generate_batch_index(&index_table, 4).unwrap().len(), 3);
     }
 
-    output
+    #[test]
+    fn invalid_mnist_files() {
+        assert!(matches!(
+            try_extract_labels("file_that_does_not_exist"),
+            Err(BriqueError::Io(_))
+        ));
+
+        let file_path: String = std::env::temp_dir()
+            .join("brique_invalid_idx")
+            .to_str()
+            .unwrap()
+            .to_string();
+        // label header given to the images reader, then a truncated label file
+        fs::write(&file_path, [0, 0, 8, 1, 0, 0, 0, 2, 4, 7]).unwrap();
+        assert!(matches!(
+            try_extract_images(&file_path),
+            Err(BriqueError::Format(_))
+        ));
+        assert_eq!(try_extract_labels(&file_path).unwrap().data, vec![4.0, 7.0]);
+
+        fs::write(&file_path, [0, 0, 8, 1, 0, 0, 0, 3, 4, 7]).unwrap();
+        assert!(matches!(
+            try_extract_labels(&file_path),
+            Err(BriqueError::Format(_))
+        ));
+        fs::remove_file(file_path).unwrap();
+    }
 }
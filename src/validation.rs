@@ -0,0 +1,264 @@
+use crate::error::BriqueError;
+use crate::loss::TargetMode;
+use crate::matrix::Matrix;
+use crate::model::Model;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+// how the validation dataset is built
+#[derive(Clone)]
+pub enum ValidationSplit {
+    // size random rows of the training data, the behaviour of Model::train
+    Random { size: usize },
+    // a random fraction of the training data, in [0, 1[
+    Fraction(f64),
+    // a fraction of every class, the validation dataset keeps the class proportions
+    // the class of a soft target is its most probable class, not usable for multi-label
+    Stratified { fraction: f64 },
+    // the last rows of the training data, for sequential data
+    // the training data is not shuffled
+    TimeOrdered { fraction: f64 },
+    // a separate validation dataset, all the training data is used for the training
+    Explicit { data: Matrix, labels: Matrix },
+}
+
+impl ValidationSplit {
+    // the training data is shuffled before the training for every split but TimeOrdered
+    pub fn shuffle_training(&self) -> bool {
+        !matches!(self, ValidationSplit::TimeOrdered { .. })
+    }
+
+    // (training indexes, validation indexes) of the rows of the training data
+    // without shuffle (debug mode) the random splits take the first rows
+    // seed : seeds the shuffle, a random seed is used if None
+    pub fn split(
+        &self,
+        labels: &Matrix,
+        number_of_samples: usize,
+        target_mode: &TargetMode,
+        shuffle: bool,
+        seed: Option<u64>,
+    ) -> Result<(Vec<u32>, Vec<u32>), BriqueError> {
+        let mut rng: StdRng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+        let mut indexes: Vec<u32> = (0..number_of_samples as u32).collect();
+        if shuffle && self.shuffle_training() {
+            indexes.shuffle(&mut rng);
+        }
+
+        match self {
+            ValidationSplit::Random { size } => {
+                check_validation_size(*size, number_of_samples)?;
+                let training: Vec<u32> = indexes.split_off(*size);
+                Ok((training, indexes))
+            }
+            ValidationSplit::Fraction(fraction) => {
+                let size: usize = validation_size(*fraction, number_of_samples)?;
+                let training: Vec<u32> = indexes.split_off(size);
+                Ok((training, indexes))
+            }
+            ValidationSplit::TimeOrdered { fraction } => {
+                let size: usize = validation_size(*fraction, number_of_samples)?;
+                let validation: Vec<u32> = indexes.split_off(number_of_samples - size);
+                Ok((indexes, validation))
+            }
+            ValidationSplit::Stratified { fraction } => {
+                check_fraction(*fraction)?;
+                let rng: Option<&mut StdRng> = if shuffle { Some(&mut rng) } else { None };
+                stratified_split(labels, &indexes, *fraction, target_mode, rng)
+            }
+            ValidationSplit::Explicit { .. } => Ok((indexes, vec![])),
+        }
+    }
+}
+
+fn check_fraction(fraction: f64) -> Result<(), BriqueError> {
+    if !(0.0..1.0).contains(&fraction) {
+        return Err(BriqueError::Config(format!(
+            "the validation fraction should be in [0, 1[, found {}",
+            fraction
+        )));
+    }
+
+    Ok(())
+}
+
+fn validation_size(fraction: f64, number_of_samples: usize) -> Result<usize, BriqueError> {
+    check_fraction(fraction)?;
+    let size: usize = (fraction * number_of_samples as f64).round() as usize;
+    check_validation_size(size, number_of_samples)?;
+
+    Ok(size)
+}
+
+fn check_validation_size(size: usize, number_of_samples: usize) -> Result<(), BriqueError> {
+    if size >= number_of_samples {
+        return Err(BriqueError::Config(format!(
+            "the validation dataset ({} samples) should be smaller than the dataset ({} samples)",
+            size, number_of_samples
+        )));
+    }
+
+    Ok(())
+}
+
+// 1 x N class of every sample, used to stratify
+// the class of a soft target is its most probable class
+pub(crate) fn sample_classes(
+    labels: &Matrix,
+    target_mode: &TargetMode,
+) -> Result<Matrix, BriqueError> {
+    match target_mode {
+        TargetMode::ClassIndex => Ok(labels.clone()),
+        TargetMode::Probabilities => Ok(Model::evaluation_output(labels)),
+        TargetMode::MultiLabel { .. } => Err(BriqueError::Config(
+            "the stratification is not available for multi-label targets".to_string(),
+        )),
+    }
+}
+
+fn stratified_split(
+    labels: &Matrix,
+    indexes: &[u32],
+    fraction: f64,
+    target_mode: &TargetMode,
+    rng: Option<&mut StdRng>,
+) -> Result<(Vec<u32>, Vec<u32>), BriqueError> {
+    let classes: Matrix = sample_classes(labels, target_mode)?;
+
+    // indexes of every class, in the order of indexes
+    let mut indexes_per_class: Vec<Vec<u32>> = vec![];
+    for index in indexes {
+        let class: usize = classes.get(0, *index as usize) as usize;
+        if class >= indexes_per_class.len() {
+            indexes_per_class.resize(class + 1, vec![]);
+        }
+        indexes_per_class[class].push(*index);
+    }
+
+    let mut training: Vec<u32> = vec![];
+    let mut validation: Vec<u32> = vec![];
+    for class_indexes in indexes_per_class.iter_mut() {
+        let size: usize = (fraction * class_indexes.len() as f64).round() as usize;
+        training.extend_from_slice(&class_indexes[size..]);
+        validation.extend_from_slice(&class_indexes[..size]);
+    }
+
+    check_validation_size(validation.len(), indexes.len())?;
+    // the training indexes are grouped by class, they are mixed again
+    match rng {
+        Some(rng) => training.shuffle(rng),
+        None => training.sort(),
+    }
+
+    Ok((training, validation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValidationSplit;
+    use crate::{error::BriqueError, loss::TargetMode, matrix::Matrix};
+
+    fn get_labels() -> Matrix {
+        // 8 samples of class 0, 4 of class 1
+        Matrix::init(
+            1,
+            12,
+            vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+        )
+    }
+
+    fn assert_partition(training: &[u32], validation: &[u32], number_of_samples: usize) {
+        let mut all: Vec<u32> = training.iter().chain(validation.iter()).copied().collect();
+        all.sort();
+
+        assert_eq!(all, (0..number_of_samples as u32).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn fraction_split() {
+        let (training, validation) = ValidationSplit::Fraction(0.25)
+            .split(&get_labels(), 12, &TargetMode::ClassIndex, true, None)
+            .unwrap();
+
+        assert_eq!(validation.len(), 3);
+        assert_partition(&training, &validation, 12);
+    }
+
+    #[test]
+    fn stratified_split() {
+        let labels = get_labels();
+        let (training, validation) = ValidationSplit::Stratified { fraction: 0.25 }
+            .split(&labels, 12, &TargetMode::ClassIndex, true, None)
+            .unwrap();
+
+        let classes: Vec<f64> = validation
+            .iter()
+            .map(|index| labels.get(0, *index as usize))
+            .collect();
+        assert_eq!(classes.iter().filter(|c| **c == 0.0).count(), 2);
+        assert_eq!(classes.iter().filter(|c| **c == 1.0).count(), 1);
+        assert_partition(&training, &validation, 12);
+    }
+
+    #[test]
+    fn seeded_splits() {
+        let labels = get_labels();
+        for split in [
+            ValidationSplit::Fraction(0.25),
+            ValidationSplit::Stratified { fraction: 0.25 },
+        ] {
+            let first = split
+                .split(&labels, 12, &TargetMode::ClassIndex, true, Some(5))
+                .unwrap();
+            let second = split
+                .split(&labels, 12, &TargetMode::ClassIndex, true, Some(5))
+                .unwrap();
+            assert_eq!(first, second);
+        }
+    }
+
+    #[test]
+    fn time_ordered_split() {
+        let split = ValidationSplit::TimeOrdered { fraction: 0.25 };
+        let (training, validation) = split
+            .split(&get_labels(), 12, &TargetMode::ClassIndex, true, None)
+            .unwrap();
+
+        assert!(!split.shuffle_training());
+        assert_eq!(training, (0..9).collect::<Vec<u32>>());
+        assert_eq!(validation, vec![9, 10, 11]);
+    }
+
+    #[test]
+    fn invalid_splits() {
+        let labels = get_labels();
+
+        assert!(matches!(
+            ValidationSplit::Fraction(1.0).split(&labels, 12, &TargetMode::ClassIndex, true, None),
+            Err(BriqueError::Config(_))
+        ));
+        assert!(matches!(
+            ValidationSplit::Random { size: 12 }.split(
+                &labels,
+                12,
+                &TargetMode::ClassIndex,
+                true,
+                None
+            ),
+            Err(BriqueError::Config(_))
+        ));
+        assert!(ValidationSplit::Stratified { fraction: 0.5 }
+            .split(
+                &labels,
+                12,
+                &TargetMode::MultiLabel {
+                    thresholds: vec![0.5]
+                },
+                true,
+                None
+            )
+            .is_err());
+    }
+}
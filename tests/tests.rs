@@ -1,7 +1,11 @@
 #[cfg(test)]
 mod tests {
     use brique::{
-        layers::Layer, matrix::*, model::Model, optimizer::Optimizer,
+        autograd::{dense, DenseVars, Tape},
+        layers::Layer,
+        matrix::*,
+        model::Model,
+        optimizer::Optimizer,
         parse_test_csv::parse_test_csv,
     };
 
@@ -157,4 +161,116 @@ mod tests {
             index += 1;
         }
     }
+
+    // same network and numbers as end_to_end_model_test, but the gradients
+    // are computed by the autograd tape instead of Layer::backprop
+    #[test]
+    fn autograd_end_to_end_model_test() {
+        let input_weights: Vec<Matrix> =
+            parse_test_csv("tests/test_data/test_input_weights.csv".to_string());
+        let test_data: Vec<Matrix> = parse_test_csv("tests/test_data/test_data.csv".to_string());
+        let expected_params: Vec<Matrix> =
+            parse_test_csv("tests/test_data/expected_params.csv".to_string());
+
+        let mut layers = [
+            Layer::init_test(3, true, input_weights[0].clone()),
+            Layer::init_test(3, true, input_weights[1].clone()),
+            Layer::init_test(3, false, input_weights[2].clone()),
+        ];
+        let lambda: f64 = 0.001;
+        let learning_step: f64 = 0.1;
+        let precision: i32 = 10;
+        let batch_size = test_data[0].height as f64;
+
+        for index in 0..5 {
+            let mut tape = Tape::new();
+            let mut x = tape.leaf(test_data[0].clone());
+            let mut vars: Vec<DenseVars> = vec![];
+            for (i, layer) in layers.iter().enumerate() {
+                assert!(
+                    layer
+                        .weights_t
+                        .is_equal(&expected_params[(index * 21) + (i * 2)], precision),
+                    "Weights in iteration {}, layer {}, incorrect values",
+                    index + 1,
+                    i + 1
+                );
+
+                let dense_vars = dense(&mut tape, x, layer);
+                x = dense_vars.output;
+                assert!(
+                    tape.value(x)
+                        .is_equal(&expected_params[(index * 21) + i + 6], precision),
+                    "Intermediate evaluation result in iteration {}, layer {}, incorrect values",
+                    index + 1,
+                    i + 1
+                );
+                vars.push(dense_vars);
+            }
+
+            let probabilities = tape.softmax(x);
+            assert!(
+                tape.value(probabilities)
+                    .is_equal(&expected_params[(index * 21) + 10], precision),
+                "softmax output in iteration {}, incorrect values",
+                index + 1,
+            );
+
+            // the hand derived gradients are the ones of the summed cross entropy
+            let picked = tape.pick(probabilities, &test_data[1]);
+            let log = tape.log(picked);
+            let log_sum = tape.sum(log);
+            let data_loss = tape.mult(log_sum, -1.0);
+            let mut loss = data_loss;
+            for dense_vars in &vars {
+                let squared = tape.square(dense_vars.weights_t);
+                let squared_sum = tape.sum(squared);
+                let penalty = tape.mult(squared_sum, 0.5 * lambda);
+                loss = tape.add(loss, penalty);
+            }
+
+            let expected_loss = &expected_params[((index + 1) * 21) - 1];
+            let reg_loss = tape.value(loss).get(0, 0) - tape.value(data_loss).get(0, 0);
+            let loss_matrix = Matrix::init(
+                1,
+                3,
+                vec![
+                    tape.value(data_loss).get(0, 0) / batch_size,
+                    reg_loss,
+                    tape.value(data_loss).get(0, 0) / batch_size + reg_loss,
+                ],
+            );
+            assert!(
+                loss_matrix.is_equal(expected_loss, precision),
+                "Loss in iteration {}, incorrect values",
+                index + 1
+            );
+
+            let grads = tape.backward(loss);
+            for (i, dense_vars) in vars.iter().enumerate() {
+                // the expected gradients are stored from the last layer to the first
+                let offset = (index * 21) + ((layers.len() - 1 - i) * 3);
+                let d_w = grads.get(dense_vars.weights_t).unwrap();
+                let d_b = grads.get(dense_vars.biases).unwrap();
+
+                assert!(
+                    d_w.is_equal(&expected_params[offset + 12], precision),
+                    "Gradient of the weights in iteration {}, layer {}, incorrect values",
+                    index + 1,
+                    i + 1
+                );
+                assert!(
+                    d_b.is_equal(&expected_params[offset + 13], precision),
+                    "Gradient of the biases in iteration {}, layer {}, incorrect values",
+                    index + 1,
+                    i + 1
+                );
+
+                layers[i].weights_t = layers[i]
+                    .weights_t
+                    .add_two_matrices(&d_w.mult(-learning_step));
+                layers[i].biases = layers[i].biases.add_two_matrices(&d_b.mult(-learning_step));
+            }
+        }
+    }
 }